@@ -0,0 +1,215 @@
+//! This module exposes aggregate views over the listings that are currently
+//! stored on the market, such that its health can be monitored without an
+//! indexer. All numbers are computed on the fly from storage, so views that
+//! iterate over all listings become more expensive as the market grows.
+//!
+//! Listings that only exist while a signed order or a trait offer is being
+//! settled are included, as they occupy listing slots just like regular
+//! listings.
+
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+
+use mb_sdk::near_sdk::{
+    self,
+    json_types::{
+        U128,
+        U64,
+    },
+    AccountId,
+};
+
+use crate::{
+    data::*,
+    Market,
+    MarketExt,
+};
+
+#[near_sdk::near_bindgen]
+impl Market {
+    /// Show aggregate numbers over all listings and storage deposits.
+    pub fn get_listings_stats(&self) -> ListingsStatsJson {
+        let mut listings_with_offer = 0;
+        let mut listings_by_currency = HashMap::new();
+        for listing in self.listings.values() {
+            if listing.current_offer.is_some() {
+                listings_with_offer += 1;
+            }
+            listings_by_currency
+                .entry(listing.currency.to_string())
+                .or_insert(U64(0))
+                .0 += 1;
+        }
+
+        ListingsStatsJson {
+            listings: self.listings.len().into(),
+            listings_with_offer: U64(listings_with_offer),
+            listings_by_currency,
+            rentals: self.rentals.len().into(),
+            storage_deposits: U128(
+                self.storage_deposits_by_account.values().sum(),
+            ),
+        }
+    }
+
+    /// Show the number of listings per NFT contract, ordered by contract.
+    pub fn get_listings_count_by_collection(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<(AccountId, U64)> {
+        let mut counts = BTreeMap::new();
+        for listing in self.listings.values() {
+            *counts.entry(listing.nft_contract_id).or_insert(0) += 1;
+        }
+
+        counts
+            .into_iter()
+            .skip(from_index.unwrap_or(U64(0)).0 as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(nft_contract_id, count)| (nft_contract_id, U64(count)))
+            .collect()
+    }
+
+    /// Show the number of listings per seller. Rentals count towards this,
+    /// as they are backed by the same storage deposit.
+    pub fn get_listings_count_by_seller(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<(AccountId, U64)> {
+        self.listings_count_by_account
+            .iter()
+            .skip(from_index.unwrap_or(U64(0)).0 as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(seller_id, count)| (seller_id, U64(count)))
+            .collect()
+    }
+
+    /// Show the listings that are still locked, those that can be unlisted
+    /// soonest first. A listing can be unlisted after `created_at` plus
+    /// `listing_lock_seconds`.
+    pub fn get_listings_nearest_unlock(
+        &self,
+        limit: Option<u64>,
+    ) -> Vec<ListingJson> {
+        let locked_since = near_sdk::env::block_timestamp()
+            .saturating_sub(self.listing_lock_seconds * 1_000_000_000);
+        let mut listings: Vec<Listing> = self
+            .listings
+            .values()
+            .filter(|listing| {
+                !listing.is_transient() && listing.created_at >= locked_since
+            })
+            .collect();
+        listings.sort_by_key(|listing| listing.created_at);
+
+        listings
+            .into_iter()
+            .take(limit.unwrap_or(50) as usize)
+            .map(Into::into)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    /// Lists token `token_id` on `nft_contract_id`, created at `created_at`
+    /// seconds.
+    fn list(
+        market: &mut Market,
+        nft_contract_id: &str,
+        token_id: &str,
+        seller_id: &str,
+        created_at: u64,
+    ) {
+        let mut listing = listing(seller_id);
+        listing.nft_contract_id = account(nft_contract_id);
+        listing.nft_token_id = token_id.to_string();
+        listing.created_at = created_at * SECOND;
+        insert_listing(market, &listing);
+    }
+
+    #[test]
+    fn stats_count_listings_offers_and_deposits() {
+        let mut market = setup_market();
+        list(&mut market, "nft.near", "1", "alice.near", 0);
+        let mut listing = listing("bob.near");
+        listing.nft_token_id = "2".to_string();
+        listing.currency = Currency::FtContract(account("usdc.near"));
+        listing.current_offer = Some(offer("carol.near", "carol.near", None));
+        insert_listing(&mut market, &listing);
+
+        let stats = market.get_listings_stats();
+        assert_eq!(stats.listings, U64(2));
+        assert_eq!(stats.listings_with_offer, U64(1));
+        assert_eq!(stats.listings_by_currency["near"], U64(1));
+        assert_eq!(stats.listings_by_currency["ft::usdc.near"], U64(1));
+        assert_eq!(stats.rentals, U64(0));
+        assert_eq!(
+            stats.storage_deposits,
+            U128(2 * market.listing_storage_deposit)
+        );
+    }
+
+    #[test]
+    fn listings_are_counted_by_collection_and_seller() {
+        let mut market = setup_market();
+        list(&mut market, "b.near", "1", "alice.near", 0);
+        list(&mut market, "a.near", "1", "alice.near", 0);
+        list(&mut market, "a.near", "2", "bob.near", 0);
+
+        assert_eq!(
+            market.get_listings_count_by_collection(None, None),
+            vec![(account("a.near"), U64(2)), (account("b.near"), U64(1))]
+        );
+        assert_eq!(
+            market.get_listings_count_by_collection(Some(U64(1)), Some(1)),
+            vec![(account("b.near"), U64(1))]
+        );
+        let mut by_seller = market.get_listings_count_by_seller(None, None);
+        by_seller.sort();
+        assert_eq!(
+            by_seller,
+            vec![
+                (account("alice.near"), U64(2)),
+                (account("bob.near"), U64(1))
+            ]
+        );
+    }
+
+    #[test]
+    fn locked_listings_are_sorted_by_unlock() {
+        let mut market = setup_market();
+        market.listing_lock_seconds = 100;
+        list(&mut market, "nft.near", "unlocked", "alice.near", 800);
+        list(&mut market, "nft.near", "late", "alice.near", 990);
+        list(&mut market, "nft.near", "soon", "alice.near", 950);
+
+        let token_ids: Vec<String> = market
+            .get_listings_nearest_unlock(None)
+            .into_iter()
+            .map(|listing| listing.nft_token_id)
+            .collect();
+        assert_eq!(token_ids, vec!["soon", "late"]);
+        assert_eq!(market.get_listings_nearest_unlock(Some(1)).len(), 1);
+    }
+
+    #[test]
+    fn settling_orders_are_not_locked() {
+        let mut market = setup_market();
+        market.listing_lock_seconds = 100;
+        let mut listing = listing("alice.near");
+        listing.created_at = 1_000 * SECOND;
+        listing.order_nonce = Some(1);
+        market.listings.insert(&listing.token_key(), &listing);
+
+        assert!(market.get_listings_nearest_unlock(None).is_empty());
+        assert_eq!(market.get_listings_stats().listings, U64(1));
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use mb_sdk::near_sdk::{
     self,
     borsh::{
@@ -6,6 +8,7 @@ use mb_sdk::near_sdk::{
         BorshSerialize,
     },
     json_types::{
+        Base64VecU8,
         U128,
         U64,
     },
@@ -22,18 +25,65 @@ use mb_sdk::near_sdk::{
 pub const ERR_LISTING_NOT_FOUND: &str = "Listing not found";
 pub const ERR_OFFER_IN_PROGRESS: &str =
     "Cannot modify listing while offer is being processed";
+pub const ERR_INVALID_ORDER_SIGNATURE: &str = "Invalid order signature";
+pub const ERR_TRAIT_OFFER_NOT_FOUND: &str = "Trait offer not found";
 
 /// Storage deposit for 1 kB of data.
 pub const TEN_MILLINEAR: Balance = 10_000_000_000_000_000_000_000;
 
 pub const MAX_LEN_PAYOUT_NEAR: u32 = 50;
 pub const MAX_LEN_PAYOUT_FT: u32 = 10;
+/// FT payouts of listings that are checked on another contract before the
+/// transfer, i.e. signed orders and gated listings, are restricted further, as the check takes
+/// an additional cross-contract call.
+pub const MAX_LEN_PAYOUT_CHECKED_FT: u32 = 7;
 pub const LISTING_KIND_SIMPLE: &str = "simple";
+pub const ERR_RENTAL_NOT_FOUND: &str = "Rental not found";
+pub const ERR_RENT_IN_PROGRESS: &str =
+    "Cannot modify rental while it is rented out";
 pub const NFT_TRANSFER_PAYOUT_GAS: Gas = Gas(15_000_000_000_000);
 pub const NFT_RESOLVE_PAYOUT_NEAR_GAS: Gas = Gas(175_000_000_000_000);
-pub const NFT_RESOLVE_PAYOUT_FT_GAS: Gas = Gas(235_000_000_000_000);
+pub const NFT_RESOLVE_PAYOUT_FT_GAS: Gas =
+    nft_resolve_payout_ft_gas(MAX_LEN_PAYOUT_FT);
+pub const NFT_RESOLVE_PAYOUT_CHECKED_FT_GAS: Gas =
+    nft_resolve_payout_ft_gas(MAX_LEN_PAYOUT_CHECKED_FT);
+pub const NFT_TOKEN_GAS: Gas = Gas(5_000_000_000_000);
+/// Resolving an order check takes 5 TGas, plus the gas for the transfer and
+/// payout resolution it schedules.
+pub const NFT_RESOLVE_ORDER_NEAR_GAS: Gas = Gas(5_000_000_000_000
+    + NFT_TRANSFER_PAYOUT_GAS.0
+    + NFT_RESOLVE_PAYOUT_NEAR_GAS.0);
+pub const NFT_RESOLVE_ORDER_FT_GAS: Gas = Gas(5_000_000_000_000
+    + NFT_TRANSFER_PAYOUT_GAS.0
+    + NFT_RESOLVE_PAYOUT_CHECKED_FT_GAS.0);
+pub const NFT_TRANSFER_GAS: Gas = Gas(15_000_000_000_000);
+pub const NFT_RESOLVE_RENT_GAS: Gas = Gas(30_000_000_000_000);
+pub const NFT_RESOLVE_UNLIST_RENTAL_GAS: Gas = Gas(15_000_000_000_000);
+pub const NFT_RESOLVE_TRAIT_OFFER_GAS: Gas = Gas(195_000_000_000_000);
+pub const GATE_CHECK_GAS: Gas = Gas(10_000_000_000_000);
+/// Resolving a holder check takes 5 TGas, plus the gas for the transfer and
+/// payout resolution it schedules.
+pub const NFT_RESOLVE_GATE_NEAR_GAS: Gas = Gas(5_000_000_000_000
+    + NFT_TRANSFER_PAYOUT_GAS.0
+    + NFT_RESOLVE_PAYOUT_NEAR_GAS.0);
+pub const NFT_RESOLVE_GATE_FT_GAS: Gas = Gas(5_000_000_000_000
+    + NFT_TRANSFER_PAYOUT_GAS.0
+    + NFT_RESOLVE_PAYOUT_CHECKED_FT_GAS.0);
+/// One day in nanoseconds, the unit in which rentals are priced.
+pub const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// How many of the most recent sales are kept for each token.
+pub const SALE_HISTORY_LEN: usize = 10;
+/// Default cooldown before a token may change hands again between the same
+/// pair of accounts.
+pub const WASH_TRADE_COOLDOWN_SECONDS: u64 = 24 * 60 * 60;
 // const LISTING_KIND_AUCTION: &str = "auction";
 
+/// Gas for resolving an FT payout: 15 TGas for the market itself, and 20 TGas
+/// for each `ft_transfer` to a royalty holder or the referrer.
+pub const fn nft_resolve_payout_ft_gas(max_len_payout: u32) -> Gas {
+    Gas((15 + 20 * (max_len_payout as u64 + 1)) * 1_000_000_000_000)
+}
+
 /// A listing as it is stored on the blockchain.
 ///
 /// Storage calculation:
@@ -47,8 +97,11 @@ pub const NFT_RESOLVE_PAYOUT_FT_GAS: Gas = Gas(235_000_000_000_000);
 /// | `price`            | 16 bytes                                |
 /// | `currency`         | 65 bytes                                |
 /// | `created_at`       | 8 bytes                                 |
-/// | `current_offer`    | 149 bytes                               |
-/// | total              | 496 bytes                               |
+/// | `current_offer`    | 213 bytes                               |
+/// | `order_nonce`      | 9 bytes                                 |
+/// | `trait_offer_id`   | 9 bytes                                 |
+/// | `gate`             | 138 bytes                               |
+/// | total              | 716 bytes                               |
 ///
 /// Additionally, storing this requires a `token_key` with a maximum of 128 +
 /// 64 + 3 = 195 bytes. Each lister also has one-time storages:
@@ -56,9 +109,10 @@ pub const NFT_RESOLVE_PAYOUT_FT_GAS: Gas = Gas(235_000_000_000_000);
 /// - `storage_deposits_by_account`: 64 (Account ID) + 16 (u128) = 80 bytes
 /// - `listings_number_by_account`: 64 (Account ID) + 8 (u64) = 72 bytes
 ///
-/// The first listing should thus has to require a total deposit of 0.00843
-/// NEAR. For simplicity and to discourage stale listings, each listing is
-/// required to be backed by a storage deposit of 0.01 NEAR.
+/// The first listing should thus has to require a total deposit of 0.01063
+/// NEAR, but only gated listings come close to this. For simplicity and to
+/// discourage stale listings, each listing is required to be backed by a
+/// storage deposit of 0.01 NEAR, and gated listings by a second one.
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct Listing {
     /// Token ID of listed NFT
@@ -82,6 +136,16 @@ pub struct Listing {
     /// There are instances where other smart contracts do not attach sufficient
     /// gas to a buy call, creating a "stuck offer".
     pub current_offer: Option<Offer>,
+    /// Nonce of the signed order this listing has been created from. Such
+    /// listings only exist while the order is being settled and are not
+    /// backed by a storage deposit.
+    pub order_nonce: Option<u64>,
+    /// ID of the trait offer this listing has been created from. Such
+    /// listings only exist while the offer is being settled and are not
+    /// backed by a storage deposit.
+    pub trait_offer_id: Option<u64>,
+    /// Restricts purchases to holders of a specific token
+    pub gate: Option<ListingGate>,
 }
 
 /// Listing as it is serializedtowards end-users. Importantly, numbers are
@@ -99,6 +163,9 @@ pub struct ListingJson {
     pub currency: String,
     pub created_at: U64,
     pub current_offer: Option<OfferJson>,
+    pub order_nonce: Option<U64>,
+    pub trait_offer_id: Option<U64>,
+    pub gate: Option<ListingGate>,
 }
 
 impl Listing {
@@ -118,6 +185,88 @@ impl Listing {
             currency: msg.ft_contract.into(),
             created_at: near_sdk::env::block_timestamp(),
             current_offer: None,
+            order_nonce: None,
+            trait_offer_id: None,
+            gate: msg.gate,
+        }
+    }
+
+    /// Creates the listing under which a signed order is settled.
+    pub fn from_order(order: &Order, offer: Offer) -> Self {
+        Listing {
+            nft_token_id: order.token_id.clone(),
+            nft_approval_id: order.approval_id,
+            nft_owner_id: order.seller_id.clone(),
+            nft_contract_id: order.nft_contract_id.clone(),
+            price: order.price.into(),
+            currency: order.ft_contract.clone().into(),
+            created_at: near_sdk::env::block_timestamp(),
+            current_offer: Some(offer),
+            order_nonce: Some(order.nonce.into()),
+            trait_offer_id: None,
+            gate: None,
+        }
+    }
+
+    /// Creates the listing under which a trait offer is settled.
+    pub fn from_trait_offer(
+        offer_id: u64,
+        trait_offer: &TraitOffer,
+        nft_token_id: String,
+        nft_approval_id: u64,
+        nft_owner_id: AccountId,
+    ) -> Self {
+        Listing {
+            nft_token_id,
+            nft_approval_id,
+            nft_owner_id,
+            nft_contract_id: trait_offer.nft_contract_id.clone(),
+            price: trait_offer.price,
+            currency: Currency::Near,
+            created_at: near_sdk::env::block_timestamp(),
+            current_offer: Some(Offer {
+                offerer_id: trait_offer.offerer_id.clone(),
+                receiver_id: trait_offer.receiver_id.clone(),
+                amount: trait_offer.price,
+                referrer_id: None,
+                referral_cut: None,
+            }),
+            order_nonce: None,
+            trait_offer_id: Some(offer_id),
+            gate: None,
+        }
+    }
+
+    /// Whether this listing only exists for settling a signed order or a
+    /// trait offer, in which case it is not backed by a storage deposit.
+    pub fn is_transient(&self) -> bool {
+        self.order_nonce.is_some() || self.trait_offer_id.is_some()
+    }
+
+    /// Whether the listing is checked on another contract before the transfer,
+    /// which is the case for signed orders and gated listings.
+    fn is_checked(&self) -> bool {
+        self.order_nonce.is_some() || self.gate.is_some()
+    }
+
+    /// Maximum number of accounts that the NFT contract may pay out. Each FT
+    /// payout costs an `ft_transfer`, which limits the payout length.
+    pub fn max_len_payout(&self) -> u32 {
+        match (&self.currency, self.is_checked()) {
+            (Currency::Near, _) => MAX_LEN_PAYOUT_NEAR,
+            (Currency::FtContract(_), false) => MAX_LEN_PAYOUT_FT,
+            (Currency::FtContract(_), true) => MAX_LEN_PAYOUT_CHECKED_FT,
+        }
+    }
+
+    /// Gas to attach to the callback that resolves the payout.
+    pub fn resolve_payout_gas(&self) -> Gas {
+        match (&self.currency, self.is_checked()) {
+            (Currency::Near, _) => NFT_RESOLVE_PAYOUT_NEAR_GAS,
+            (Currency::FtContract(_), false) => NFT_RESOLVE_PAYOUT_FT_GAS,
+            (Currency::FtContract(_), true) => {
+                NFT_RESOLVE_PAYOUT_CHECKED_FT_GAS
+            }
         }
     }
 
@@ -137,6 +286,9 @@ impl From<Listing> for ListingJson {
             currency: listing.currency.to_string(),
             created_at: listing.created_at.into(),
             current_offer: listing.current_offer.map(|offer| offer.into()),
+            order_nonce: listing.order_nonce.map(Into::into),
+            trait_offer_id: listing.trait_offer_id.map(Into::into),
+            gate: listing.gate,
         }
     }
 }
@@ -148,14 +300,19 @@ impl From<Listing> for ListingJson {
 /// | Field              | Required storage              |
 /// | ------------------ | ------------------------------|
 /// | `offerer_id`       | 64 bytes (64 ASCII chars max) |
+/// | `receiver_id`      | 64 bytes (64 ASCII chars max) |
 /// | `amount`           | 16 bytes                      |
 /// | `referrer_id`      | 65 bytes                      |
 /// | `referral_cut`     | 3 bytes                       |
-/// | total              | 148 bytes                     |
+/// | total              | 212 bytes                     |
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct Offer {
-    /// The account that created the offer.
+    /// The account that created the offer. This account pays for the NFT and
+    /// is refunded if the sale fails.
     pub offerer_id: AccountId,
+    /// The account that receives the NFT on a successful sale. Equals the
+    /// `offerer_id` unless the offer has been made on behalf of someone else.
+    pub receiver_id: AccountId,
     /// The amount being offered in yoctoNEAR or atomic FT units, depending on
     /// the listings currency.
     pub amount: Balance,
@@ -169,10 +326,11 @@ pub struct Offer {
 /// An offer as it is serialized towards the end user. Numbers are stringified
 /// to prevent JS floating-point inaccuracies. Not needed on `u16` as JS can
 /// represent that accurately.
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct OfferJson {
     pub offerer_id: AccountId,
+    pub receiver_id: AccountId,
     pub amount: U128,
     pub referrer_id: Option<AccountId>,
     pub referral_cut: Option<u16>,
@@ -182,6 +340,7 @@ impl From<Offer> for OfferJson {
     fn from(offer: Offer) -> OfferJson {
         OfferJson {
             offerer_id: offer.offerer_id,
+            receiver_id: offer.receiver_id,
             amount: offer.amount.into(),
             referrer_id: offer.referrer_id,
             referral_cut: offer.referral_cut,
@@ -189,6 +348,74 @@ impl From<Offer> for OfferJson {
     }
 }
 
+impl From<OfferJson> for Offer {
+    fn from(offer: OfferJson) -> Offer {
+        Offer {
+            offerer_id: offer.offerer_id,
+            receiver_id: offer.receiver_id,
+            amount: offer.amount.into(),
+            referrer_id: offer.referrer_id,
+            referral_cut: offer.referral_cut,
+        }
+    }
+}
+
+/// Restricts purchases of a listing to accounts that hold a specific token.
+/// The account receiving the NFT is checked, not the one paying for it.
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum ListingGate {
+    /// Holders of any token on an NFT contract
+    NftHolder { nft_contract_id: AccountId },
+    /// Holders of an SBT class, issued by `issuer_id` on an SBT registry
+    SbtHolder {
+        registry_id: AccountId,
+        issuer_id: AccountId,
+        class: u64,
+    },
+}
+
+/// Owner-configured bounds for listing prices in a single currency.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PriceBounds {
+    /// Lowest price a token can be listed for
+    pub min: Balance,
+    /// Highest price a token can be listed for, unbounded if none
+    pub max: Option<Balance>,
+}
+
+/// Price bounds as they are serialized towards end-users. For field
+/// descriptions see the `PriceBounds` struct.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceBoundsJson {
+    pub currency: String,
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+impl PriceBounds {
+    pub fn into_json(self, currency: String) -> PriceBoundsJson {
+        PriceBoundsJson {
+            currency,
+            min: self.min.into(),
+            max: self.max.map(Into::into),
+        }
+    }
+}
+
+impl ToString for PriceBounds {
+    fn to_string(&self) -> String {
+        match self.max {
+            Some(max) => format!("{}..={}", self.min, max),
+            None => format!("{}..", self.min),
+        }
+    }
+}
+
 /// Enum to hold payment methods, which can be either native NEAR, or fungible
 /// tokens on NEAR protocol.
 #[derive(BorshSerialize, BorshDeserialize, Clone)]
@@ -239,6 +466,19 @@ pub struct CreateListingMsg {
     pub price: U128,
     /// FT contract to use. If none, the token is listed for native NEAR.
     pub ft_contract: Option<AccountId>,
+    /// Restricts purchases to holders of a specific token.
+    pub gate: Option<ListingGate>,
+}
+
+/// A new price for one of multiple listings that are updated at once.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ListingUpdate {
+    pub token_id: String,
+    /// Price in either yoctoNEAR or atomic units of the FT contract.
+    pub price: U128,
+    /// FT contract to use. If none, the token is listed for native NEAR.
+    pub currency: Option<AccountId>,
 }
 
 /// The message that will be passed form the FT contract to the market to
@@ -250,4 +490,537 @@ pub struct BuyWithFtMessage {
     pub token_id: String,
     pub referrer_id: Option<AccountId>,
     pub affiliate_id: Option<AccountId>,
+    /// Account that should receive the NFT. Defaults to the sender of the FT
+    /// transfer.
+    pub receiver_id: Option<AccountId>,
+}
+
+/// An order that a seller signs off-chain with the ed25519 key registered via
+/// `register_order_key`. The signature covers the Borsh serialization of this
+/// struct. The seller must have approved the market for the token on the NFT
+/// contract (without a `msg`, such that no listing is created).
+#[derive(BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Order {
+    /// Market that may settle the order, prevents replays on other markets.
+    pub market_id: AccountId,
+    /// Seller who signed the order and owns the token.
+    pub seller_id: AccountId,
+    /// NFT contract.
+    pub nft_contract_id: AccountId,
+    /// Token ID of the NFT that is being sold.
+    pub token_id: String,
+    /// Approval ID under which the seller approved this market for the token.
+    pub approval_id: u64,
+    /// Price either in yoctoNEAR or the atomic unit of an FT contract.
+    pub price: U128,
+    /// FT contract to use. If none, the order is filled with native NEAR.
+    pub ft_contract: Option<AccountId>,
+    /// Timestamp (in nanoseconds) after which the order can no longer be
+    /// filled.
+    pub expires_at: U64,
+    /// Seller-chosen nonce. Each nonce can be filled or cancelled only once.
+    pub nonce: U64,
+}
+
+/// The message that will be passed from the FT contract to the market to
+/// fill a signed order.
+#[derive(Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FillOrderWithFtMessage {
+    pub order: Order,
+    /// Base64-encoded ed25519 signature of the order.
+    pub signature: Base64VecU8,
+    pub affiliate_id: Option<AccountId>,
+    /// Account that should receive the NFT. Defaults to the sender of the FT
+    /// transfer.
+    pub receiver_id: Option<AccountId>,
+}
+
+/// A sale as it is stored on the blockchain.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct SaleRecord {
+    /// Owner of the NFT at the time of listing
+    pub seller_id: AccountId,
+    /// Account that paid for the NFT
+    pub offerer_id: AccountId,
+    /// Account that received the NFT
+    pub receiver_id: AccountId,
+    /// Amount paid in yoctoNEAR or atomic FT units
+    pub price: Balance,
+    /// Currency in which the NFT has been paid
+    pub currency: Currency,
+    /// Timestamp of the block in which the sale has been settled
+    pub sold_at: Timestamp,
+}
+
+/// Sale as it is serialized towards end-users. For field descriptions see the
+/// `SaleRecord` struct.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleRecordJson {
+    pub seller_id: AccountId,
+    pub offerer_id: AccountId,
+    pub receiver_id: AccountId,
+    pub price: U128,
+    pub currency: String,
+    pub sold_at: U64,
+}
+
+impl From<SaleRecord> for SaleRecordJson {
+    fn from(sale: SaleRecord) -> SaleRecordJson {
+        SaleRecordJson {
+            seller_id: sale.seller_id,
+            offerer_id: sale.offerer_id,
+            receiver_id: sale.receiver_id,
+            price: sale.price.into(),
+            currency: sale.currency.to_string(),
+            sold_at: sale.sold_at.into(),
+        }
+    }
+}
+
+/// Aggregated sales of a collection in a single currency.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct CurrencyStats {
+    /// Sum of all sale prices
+    pub volume: Balance,
+    /// Number of sales in this currency
+    pub sale_count: u64,
+    /// Price of the most recent sale
+    pub last_sale_price: Balance,
+    /// Highest price any token of the collection has been sold for
+    pub all_time_high: Balance,
+}
+
+/// Aggregated sales of a collection, as stored on the blockchain. Currencies
+/// are keyed by their string representation (`near` or `ft::<contract>`).
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct CollectionStats {
+    /// Number of sales across all currencies
+    pub sale_count: u64,
+    /// Timestamp of the most recent sale
+    pub last_sale_at: Timestamp,
+    /// Aggregates per currency
+    pub currencies: HashMap<String, CurrencyStats>,
+}
+
+/// Aggregated sales of a collection in a single currency, as serialized
+/// towards end-users.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CurrencyStatsJson {
+    pub currency: String,
+    pub volume: U128,
+    pub sale_count: U64,
+    pub last_sale_price: U128,
+    pub all_time_high: U128,
+}
+
+/// Aggregated sales of a collection, as serialized towards end-users.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollectionStatsJson {
+    pub nft_contract_id: AccountId,
+    pub sale_count: U64,
+    pub last_sale_at: U64,
+    pub currencies: Vec<CurrencyStatsJson>,
+}
+
+impl CollectionStats {
+    /// Includes a sale in the aggregates.
+    pub fn add_sale(&mut self, currency: &Currency, price: Balance) {
+        self.sale_count += 1;
+        self.last_sale_at = near_sdk::env::block_timestamp();
+
+        let stats = self.currencies.entry(currency.to_string()).or_default();
+        stats.volume += price;
+        stats.sale_count += 1;
+        stats.last_sale_price = price;
+        stats.all_time_high = stats.all_time_high.max(price);
+    }
+
+    pub fn into_json(self, nft_contract_id: AccountId) -> CollectionStatsJson {
+        CollectionStatsJson {
+            nft_contract_id,
+            sale_count: self.sale_count.into(),
+            last_sale_at: self.last_sale_at.into(),
+            currencies: self
+                .currencies
+                .into_iter()
+                .map(|(currency, stats)| CurrencyStatsJson {
+                    currency,
+                    volume: stats.volume.into(),
+                    sale_count: stats.sale_count.into(),
+                    last_sale_price: stats.last_sale_price.into(),
+                    all_time_high: stats.all_time_high.into(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A rental listing as it is stored on the blockchain. Unlike a sale listing,
+/// the NFT is held in escrow by the market while it is not rented out.
+///
+/// Storage calculation:
+///
+/// | Field              | Required storage                        |
+/// | ------------------ | --------------------------------------- |
+/// | `nft_contract_id`  | 64 bytes                                |
+/// | `nft_token_id`     | 128 bytes (limited by `nft_on_transfer`)|
+/// | `owner_id`         | 64 bytes                                |
+/// | `price_per_day`    | 16 bytes                                |
+/// | `max_days`         | 8 bytes                                 |
+/// | `collateral`       | 16 bytes                                |
+/// | `currency`         | 65 bytes                                |
+/// | `created_at`       | 8 bytes                                 |
+/// | `current_rent`     | 105 bytes                               |
+/// | total              | 474 bytes                               |
+///
+/// Like a sale listing, each rental must be backed by a listing storage
+/// deposit of the owner.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Rental {
+    /// NFT contract
+    pub nft_contract_id: AccountId,
+    /// Token ID of the escrowed NFT
+    pub nft_token_id: String,
+    /// Account that escrowed the NFT and receives the rent
+    pub owner_id: AccountId,
+    /// Rent for a single day, either in yoctoNEAR or the atomic unit of an FT
+    /// contract
+    pub price_per_day: Balance,
+    /// Maximum number of days for which the NFT can be rented at once
+    pub max_days: u64,
+    /// Collateral that a renter has to deposit, and which is forfeited to the
+    /// owner if the NFT is not returned in time
+    pub collateral: Balance,
+    /// Currency of rent and collateral
+    pub currency: Currency,
+    /// Timestamp of the block in which the NFT has been escrowed
+    pub created_at: Timestamp,
+    /// The currently running rent, if any
+    pub current_rent: Option<Rent>,
+}
+
+/// A rent as it is stored on the blockchain.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Rent {
+    /// Account that rented the NFT and currently holds it
+    pub renter_id: AccountId,
+    /// Total rent paid
+    pub payment: Balance,
+    /// Collateral held by the market until the NFT is returned
+    pub collateral: Balance,
+    /// Timestamp after which the collateral can be claimed by the owner
+    pub expires_at: Timestamp,
+}
+
+/// Rental as it is serialized towards end-users. For field descriptions see
+/// the `Rental` struct.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RentalJson {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+    pub owner_id: AccountId,
+    pub price_per_day: U128,
+    pub max_days: u64,
+    pub collateral: U128,
+    pub currency: String,
+    pub created_at: U64,
+    pub current_rent: Option<RentJson>,
+}
+
+/// Rent as it is serialized towards end-users. For field descriptions see the
+/// `Rent` struct.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RentJson {
+    pub renter_id: AccountId,
+    pub payment: U128,
+    pub collateral: U128,
+    pub expires_at: U64,
+}
+
+impl Rental {
+    pub fn new(
+        nft_token_id: String,
+        owner_id: AccountId,
+        nft_contract_id: AccountId,
+        msg: CreateRentalMsg,
+    ) -> Self {
+        Rental {
+            nft_contract_id,
+            nft_token_id,
+            owner_id,
+            price_per_day: msg.price_per_day.into(),
+            max_days: msg.max_days,
+            collateral: msg.collateral.into(),
+            currency: msg.ft_contract.into(),
+            created_at: near_sdk::env::block_timestamp(),
+            current_rent: None,
+        }
+    }
+
+    pub fn token_key(&self) -> String {
+        format!("{}<$>{}", self.nft_contract_id, self.nft_token_id)
+    }
+}
+
+impl From<Rental> for RentalJson {
+    fn from(rental: Rental) -> RentalJson {
+        RentalJson {
+            nft_contract_id: rental.nft_contract_id,
+            nft_token_id: rental.nft_token_id,
+            owner_id: rental.owner_id,
+            price_per_day: rental.price_per_day.into(),
+            max_days: rental.max_days,
+            collateral: rental.collateral.into(),
+            currency: rental.currency.to_string(),
+            created_at: rental.created_at.into(),
+            current_rent: rental.current_rent.map(|rent| RentJson {
+                renter_id: rent.renter_id,
+                payment: rent.payment.into(),
+                collateral: rent.collateral.into(),
+                expires_at: rent.expires_at.into(),
+            }),
+        }
+    }
+}
+
+impl From<RentalJson> for Rental {
+    fn from(rental: RentalJson) -> Rental {
+        let currency = match rental.currency.strip_prefix("ft::") {
+            None => Currency::Near,
+            Some(ft_contract_id) => {
+                Currency::FtContract(ft_contract_id.parse().unwrap())
+            }
+        };
+        Rental {
+            nft_contract_id: rental.nft_contract_id,
+            nft_token_id: rental.nft_token_id,
+            owner_id: rental.owner_id,
+            price_per_day: rental.price_per_day.into(),
+            max_days: rental.max_days,
+            collateral: rental.collateral.into(),
+            currency,
+            created_at: rental.created_at.into(),
+            current_rent: rental.current_rent.map(|rent| Rent {
+                renter_id: rent.renter_id,
+                payment: rent.payment.into(),
+                collateral: rent.collateral.into(),
+                expires_at: rent.expires_at.into(),
+            }),
+        }
+    }
+}
+
+/// A rental whose collateral has been forfeited to the owner. The renter keeps
+/// the NFT and cannot return it, while the owner has paid for storing this
+/// record and is refunded once the token is escrowed again.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ClaimedRental {
+    /// Account that rented the NFT and kept it
+    pub renter_id: AccountId,
+    /// Account that received the collateral and paid for this record
+    pub owner_id: AccountId,
+}
+
+/// The message that will be passed from the NFT contract to the market via
+/// `nft_transfer_call` to escrow a token for rent.
+#[derive(Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreateRentalMsg {
+    /// Rent for a single day in either yoctoNEAR or atomic units of the FT
+    /// contract.
+    pub price_per_day: U128,
+    /// Maximum number of days for which the NFT can be rented at once.
+    pub max_days: u64,
+    /// Collateral that renters have to deposit, which is forfeited to the
+    /// owner if the NFT is not returned in time. Must not be zero.
+    pub collateral: U128,
+    /// FT contract to use. If none, the token is rented for native NEAR.
+    pub ft_contract: Option<AccountId>,
+}
+
+/// The message that will be passed from the FT contract to the market to
+/// rent an escrowed NFT.
+#[derive(Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RentWithFtMessage {
+    pub nft_contract_id: AccountId,
+    pub token_id: String,
+    /// Number of days to rent the NFT for.
+    pub days: u64,
+}
+
+/// Market functionality that can be paused in an emergency.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum PauseKind {
+    /// Creating new sale or rental listings
+    Listings,
+    /// Buying listings, filling signed orders, and renting
+    Purchases,
+    /// Placing offers that do not settle right away
+    Offers,
+}
+
+impl ToString for PauseKind {
+    fn to_string(&self) -> String {
+        match self {
+            PauseKind::Listings => "listings".to_string(),
+            PauseKind::Purchases => "purchases".to_string(),
+            PauseKind::Offers => "offers".to_string(),
+        }
+    }
+}
+
+/// Which market functionality is currently paused. Resolving in-flight
+/// transfers, refunds, unlisting and returning rentals are never paused.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Default, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PauseFlags {
+    pub listings: bool,
+    pub purchases: bool,
+    pub offers: bool,
+}
+
+impl PauseFlags {
+    pub fn is_paused(&self, kind: PauseKind) -> bool {
+        match kind {
+            PauseKind::Listings => self.listings,
+            PauseKind::Purchases => self.purchases,
+            PauseKind::Offers => self.offers,
+        }
+    }
+
+    pub fn set(&mut self, kind: PauseKind, paused: bool) {
+        match kind {
+            PauseKind::Listings => self.listings = paused,
+            PauseKind::Purchases => self.purchases = paused,
+            PauseKind::Offers => self.offers = paused,
+        }
+    }
+}
+
+/// A change to the market configuration, as stored in the audit log. Values
+/// are recorded as strings, compound values (e.g. offers) as JSON. `None`
+/// means that the setting was absent before or has been removed, e.g. for
+/// affiliates or bans.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ConfigChange {
+    /// Name of the changed setting, e.g. `genadrop_cut` or `banned_accounts`
+    pub setting: String,
+    /// Account or token key that the change applies to, if the setting is not
+    /// a single value
+    pub subject: Option<String>,
+    /// Value before the change
+    pub old_value: Option<String>,
+    /// Value after the change
+    pub new_value: Option<String>,
+    /// Account that has made the change
+    pub caller_id: AccountId,
+    /// Timestamp of the block in which the change has been made
+    pub changed_at: Timestamp,
+}
+
+/// Configuration change as it is serialized towards end-users. For field
+/// descriptions see the `ConfigChange` struct.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConfigChangeJson {
+    pub setting: String,
+    pub subject: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub caller_id: AccountId,
+    pub changed_at: U64,
+}
+
+impl From<ConfigChange> for ConfigChangeJson {
+    fn from(change: ConfigChange) -> ConfigChangeJson {
+        ConfigChangeJson {
+            setting: change.setting,
+            subject: change.subject,
+            old_value: change.old_value,
+            new_value: change.new_value,
+            caller_id: change.caller_id,
+            changed_at: change.changed_at.into(),
+        }
+    }
+}
+
+/// An escrowed offer on any token of a collection whose metadata matches a
+/// set of traits. Trait offers are always made in NEAR.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct TraitOffer {
+    /// NFT contract on which the offer can be filled
+    pub nft_contract_id: AccountId,
+    /// The account that escrowed the price and is refunded on cancellation
+    pub offerer_id: AccountId,
+    /// The account that receives the NFT on a successful sale
+    pub receiver_id: AccountId,
+    /// Traits that the token needs to have, as found in the `extra` field of
+    /// its metadata
+    pub traits: HashMap<String, String>,
+    /// Amount offered in yoctoNEAR
+    pub price: Balance,
+    /// Deposit covering the storage of this offer, refunded on removal
+    pub storage_deposit: Balance,
+    /// Timestamp of the block in which this offer was created
+    pub created_at: Timestamp,
+    /// Set while the offer is being settled against a token, locking it up
+    /// for other sellers and for cancellation
+    pub in_progress: bool,
+}
+
+/// Trait offer as it is serialized towards end-users. For field descriptions
+/// see the `TraitOffer` struct.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TraitOfferJson {
+    pub offer_id: U64,
+    pub nft_contract_id: AccountId,
+    pub offerer_id: AccountId,
+    pub receiver_id: AccountId,
+    pub traits: HashMap<String, String>,
+    pub price: U128,
+    pub created_at: U64,
+    pub in_progress: bool,
+}
+
+impl TraitOffer {
+    pub fn into_json(self, offer_id: u64) -> TraitOfferJson {
+        TraitOfferJson {
+            offer_id: offer_id.into(),
+            nft_contract_id: self.nft_contract_id,
+            offerer_id: self.offerer_id,
+            receiver_id: self.receiver_id,
+            traits: self.traits,
+            price: self.price.into(),
+            created_at: self.created_at.into(),
+            in_progress: self.in_progress,
+        }
+    }
+}
+
+/// Aggregate numbers over all listings, as serialized towards end-users.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ListingsStatsJson {
+    /// Number of listings, including those of orders and trait offers in
+    /// settlement
+    pub listings: U64,
+    /// Number of listings with an offer that is currently being processed
+    pub listings_with_offer: U64,
+    /// Number of listings per currency (`near` or `ft::<contract>`)
+    pub listings_by_currency: HashMap<String, U64>,
+    /// Number of NFTs that are escrowed for rent
+    pub rentals: U64,
+    /// Sum of all storage deposits in yoctoNEAR
+    pub storage_deposits: U128,
 }
@@ -0,0 +1,242 @@
+//! Events emitted by the market that extend or are missing from
+//! `mb_sdk::events::market_v2`. They follow the NEP-297 format and are
+//! serialized through `serialize_event`, just like the `mb_sdk` events.
+
+use std::collections::HashMap;
+
+use mb_sdk::near_sdk::{
+    self,
+    env,
+    json_types::{
+        U128,
+        U64,
+    },
+    serde::Serialize,
+    serde_json,
+    AccountId,
+};
+
+use crate::data::ListingGate;
+
+/// Standard name under which the genadrop-specific market events are logged.
+pub const MARKET_STANDARD_NAME: &str = "genadrop_market";
+/// Version of the genadrop-specific market events.
+pub const MARKET_EVENTS_VERSION: &str = "1.0.0";
+
+/// NEP-297 envelope for market events.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct NearEvent<'a, T: Serialize> {
+    standard: &'static str,
+    version: &'static str,
+    event: &'static str,
+    data: &'a T,
+}
+
+/// Serializes event data into the `EVENT_JSON:` log format.
+fn serialize_event<T: Serialize>(event: &'static str, data: &T) -> String {
+    let json = serde_json::to_string(&NearEvent {
+        standard: MARKET_STANDARD_NAME,
+        version: MARKET_EVENTS_VERSION,
+        event,
+        data,
+    })
+    .unwrap_or_else(|_| env::abort());
+    format!("EVENT_JSON:{}", json)
+}
+
+/// Implements `serialize_event` for an event data struct.
+macro_rules! market_event {
+    ($data:ident, $event:literal) => {
+        impl $data {
+            pub fn serialize_event(self) -> String {
+                serialize_event($event, &self)
+            }
+        }
+    };
+}
+
+/// Emitted right after `mb_sdk::events::market_v2::NftMakeOfferData`, which
+/// cannot carry the account that will receive the NFT.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftOfferReceiverData {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+    pub offer_id: u64,
+    pub offerer_id: AccountId,
+    pub receiver_id: AccountId,
+}
+market_event!(NftOfferReceiverData, "nft_offer_receiver");
+
+/// Emitted right after `mb_sdk::events::market_v2::NftSaleData`, which cannot
+/// carry the offerer, the account that received the NFT, and a wash-trading
+/// flag.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftSaleReceiverData {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+    pub accepted_offer_id: u64,
+    pub offerer_id: AccountId,
+    pub receiver_id: AccountId,
+    /// Set if buyer and seller have traded this token before
+    pub suspicious: bool,
+}
+market_event!(NftSaleReceiverData, "nft_sale_receiver");
+
+/// Emitted when an NFT has been escrowed for rent.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRentalListData {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+    pub owner_id: AccountId,
+    pub currency: String,
+    pub price_per_day: U128,
+    pub max_days: u64,
+    pub collateral: U128,
+}
+market_event!(NftRentalListData, "nft_rental_list");
+
+/// Emitted when an escrowed NFT has been withdrawn by its owner.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRentalUnlistData {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+}
+market_event!(NftRentalUnlistData, "nft_rental_unlist");
+
+/// Emitted when an NFT has been rented out and transferred to the renter.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRentData {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+    pub renter_id: AccountId,
+    pub currency: String,
+    pub payment: U128,
+    pub collateral: U128,
+    pub expires_at: U64,
+}
+market_event!(NftRentData, "nft_rent");
+
+/// Emitted when a renter has returned an NFT and regained their collateral.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRentalReturnData {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+    pub renter_id: AccountId,
+}
+market_event!(NftRentalReturnData, "nft_rental_return");
+
+/// Emitted when an NFT has not been returned in time and the collateral has
+/// been forfeited to the owner.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRentalForfeitData {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+    pub renter_id: AccountId,
+    pub owner_id: AccountId,
+    pub collateral: U128,
+}
+market_event!(NftRentalForfeitData, "nft_rental_forfeit");
+
+/// Emitted when market functionality has been paused or unpaused.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MarketPauseData {
+    pub kind: String,
+    pub paused: bool,
+    pub caller_id: AccountId,
+}
+market_event!(MarketPauseData, "market_pause");
+
+/// Emitted for every change to the market configuration, with the values
+/// before and after the change. See `ConfigChange` for field descriptions.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MarketConfigChangeData {
+    pub setting: String,
+    pub subject: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub caller_id: AccountId,
+}
+market_event!(MarketConfigChangeData, "market_config_change");
+
+/// Emitted when the seller has changed the price or currency of a listing
+/// without relisting it.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftUpdateListingData {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+    pub nft_approval_id: u64,
+    pub old_currency: String,
+    pub old_price: U128,
+    pub currency: String,
+    pub price: U128,
+}
+market_event!(NftUpdateListingData, "nft_update_listing");
+
+/// Emitted when NEAR has been escrowed for any token of a collection that has
+/// the given traits.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTraitOfferData {
+    pub offer_id: U64,
+    pub nft_contract_id: AccountId,
+    pub offerer_id: AccountId,
+    pub receiver_id: AccountId,
+    pub traits: HashMap<String, String>,
+    pub price: U128,
+}
+market_event!(NftTraitOfferData, "nft_trait_offer");
+
+/// Emitted when a trait offer has been cancelled and the escrow refunded.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTraitOfferCancelData {
+    pub offer_id: U64,
+}
+market_event!(NftTraitOfferCancelData, "nft_trait_offer_cancel");
+
+/// Emitted when a token did not pass the checks for filling a trait offer.
+/// The offer remains available.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTraitOfferRejectData {
+    pub offer_id: U64,
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+}
+market_event!(NftTraitOfferRejectData, "nft_trait_offer_reject");
+
+/// Emitted when purchases of a listing have been restricted to holders of a
+/// specific token, or when such a restriction has been lifted.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftListingGateData {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+    pub gate: Option<ListingGate>,
+    pub caller_id: AccountId,
+}
+market_event!(NftListingGateData, "nft_listing_gate");
+
+/// Emitted when a purchase of a gated listing has been refunded, because the
+/// receiver does not hold the required token or the listing has changed in
+/// the meantime.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftGateRejectData {
+    pub nft_contract_id: AccountId,
+    pub nft_token_id: String,
+    pub offerer_id: AccountId,
+    pub receiver_id: AccountId,
+}
+market_event!(NftGateRejectData, "nft_gate_reject");
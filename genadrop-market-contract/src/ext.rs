@@ -0,0 +1,82 @@
+//! Interfaces of other contracts that are not covered by
+//! `mb_sdk::interfaces`.
+
+use std::collections::HashMap;
+
+use mb_sdk::near_sdk::{
+    self,
+    ext_contract,
+    json_types::U128,
+    serde::Deserialize,
+    AccountId,
+};
+
+/// NEP-171 view methods on NFT contracts.
+#[ext_contract(ext_nft_view)]
+pub trait ExtNftView {
+    fn nft_token(&self, token_id: String) -> Option<NftToken>;
+    fn nft_tokens_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<NftToken>;
+}
+
+/// Views on SBT registries, as also used by the contest contract.
+#[ext_contract(ext_sbtreg)]
+pub trait ExtSbtRegistry {
+    fn sbt_tokens_by_owner(
+        &self,
+        account: AccountId,
+        issuer: Option<AccountId>,
+        from_class: Option<u64>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+    ) -> Vec<(AccountId, Vec<OwnedToken>)>;
+}
+
+/// NEP-171 transfers of tokens that the market holds in escrow.
+#[ext_contract(ext_nft_escrow)]
+pub trait ExtNftEscrow {
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    );
+}
+
+/// The parts of a NEP-171 `JsonToken` that the market needs to inspect.
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftToken {
+    pub owner_id: AccountId,
+    #[serde(default)]
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    #[serde(default)]
+    pub metadata: Option<NftTokenMetadata>,
+}
+
+/// The parts of a NEP-177 `TokenMetadata` that the market needs to inspect.
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTokenMetadata {
+    pub extra: Option<String>,
+}
+
+/// The parts of an SBT in a `sbt_tokens_by_owner` response that the market
+/// needs to inspect.
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnedToken {
+    pub metadata: SbtMetadata,
+}
+
+/// The parts of SBT metadata that the market needs to inspect.
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SbtMetadata {
+    pub class: u64,
+}
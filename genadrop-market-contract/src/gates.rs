@@ -0,0 +1,531 @@
+//! This module implements token-gated listings. A seller can restrict
+//! purchases of a listing to holders of a specific token, either by passing a
+//! `gate` in the listing message or by calling `set_listing_gate`. The market
+//! owner may also gate listings, e.g. for holder-only drops and presales.
+//!
+//! Two kinds of gates are supported:
+//!
+//! - `nft_holder`: The receiver must hold any token on an NFT contract, as
+//!   reported by `nft_tokens_for_owner`.
+//! - `sbt_holder`: The receiver must hold an SBT of a given class from a given
+//!   issuer, as reported by `sbt_tokens_by_owner` on an SBT registry.
+//!
+//! Buying a gated listing via `buy` or `ft_on_transfer` does not create the
+//! offer right away. Instead, the holder check is made cross-contract, and
+//! the offer is only placed in the callback if the check passed and the
+//! listing has not changed in the meantime. Otherwise, the buyer is refunded.
+
+use mb_sdk::{
+    near_assert,
+    near_sdk::{
+        self,
+        env,
+        json_types::U128,
+        serde_json,
+        AccountId,
+        Promise,
+        PromiseOrValue,
+        PromiseResult,
+    },
+};
+
+use crate::{
+    data::*,
+    events::{
+        NftGateRejectData,
+        NftListingGateData,
+    },
+    ext::{
+        ext_nft_view,
+        ext_sbtreg,
+        NftToken,
+        OwnedToken,
+    },
+    Market,
+    MarketExt,
+};
+
+#[near_sdk::near_bindgen]
+impl Market {
+    /// Restrict purchases of a listing to holders of a specific token, or
+    /// lift the restriction by passing no `gate`. The seller and the market
+    /// owner can call this, but not while an offer is being processed.
+    /// Gating a listing requires a second storage deposit from the seller,
+    /// which is refunded when the gate is lifted.
+    #[payable]
+    pub fn set_listing_gate(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        gate: Option<ListingGate>,
+    ) {
+        near_sdk::assert_one_yocto();
+        let caller_id = env::predecessor_account_id();
+        let token_key = format!("{}<$>{}", nft_contract_id, token_id);
+        let mut listing = match self.get_listing_internal(&token_key) {
+            None => env::panic_str(ERR_LISTING_NOT_FOUND),
+            Some(l) => l,
+        };
+
+        near_assert!(
+            caller_id == listing.nft_owner_id || caller_id == self.owner,
+            "Method is restricted to the seller and market owner"
+        );
+        if listing.current_offer.is_some() {
+            env::panic_str(ERR_OFFER_IN_PROGRESS);
+        }
+
+        // Gated listings are backed by a second storage deposit
+        match (listing.gate.is_some(), gate.is_some()) {
+            (false, true) => self.add_listing_gate(&listing.nft_owner_id),
+            (true, false) => {
+                self.refund_listing_gate(&listing.nft_owner_id);
+            }
+            _ => {}
+        }
+        listing.gate = gate.clone();
+        self.listings.insert(&token_key, &listing);
+        env::log_str(
+            &NftListingGateData {
+                nft_contract_id,
+                nft_token_id: token_id,
+                gate,
+                caller_id,
+            }
+            .serialize_event(),
+        );
+    }
+
+    /// Checks whether the receiver of an offer holds the token required by
+    /// the gate of a listing. The offer is placed in the callback.
+    pub(crate) fn check_gate(&self, listing: Listing, offer: Offer) -> Promise {
+        let gate = listing.gate.clone().unwrap();
+        let check = match &gate {
+            ListingGate::NftHolder { nft_contract_id } => {
+                ext_nft_view::ext(nft_contract_id.clone())
+                    .with_static_gas(GATE_CHECK_GAS)
+                    .with_unused_gas_weight(0)
+                    .nft_tokens_for_owner(
+                        offer.receiver_id.clone(),
+                        None,
+                        Some(1),
+                    )
+            }
+            ListingGate::SbtHolder {
+                registry_id,
+                issuer_id,
+                class,
+            } => ext_sbtreg::ext(registry_id.clone())
+                .with_static_gas(GATE_CHECK_GAS)
+                .with_unused_gas_weight(0)
+                .sbt_tokens_by_owner(
+                    offer.receiver_id.clone(),
+                    Some(issuer_id.clone()),
+                    Some(*class),
+                    Some(1),
+                    Some(false),
+                ),
+        };
+
+        let callback = Self::ext(env::current_account_id());
+        let callback = if listing.currency.is_near() {
+            callback
+                .with_static_gas(NFT_RESOLVE_GATE_NEAR_GAS)
+                .nft_resolve_gate_near(
+                    listing.nft_contract_id,
+                    listing.nft_token_id,
+                    listing.nft_approval_id,
+                    listing.currency.to_string(),
+                    gate,
+                    offer.into(),
+                )
+        } else {
+            callback
+                .with_static_gas(NFT_RESOLVE_GATE_FT_GAS)
+                .nft_resolve_gate_ft(
+                    listing.nft_contract_id,
+                    listing.nft_token_id,
+                    listing.nft_approval_id,
+                    listing.currency.to_string(),
+                    gate,
+                    offer.into(),
+                )
+        };
+
+        check.then(callback)
+    }
+
+    /// Resolves the holder check of a gated listing that is bought with NEAR.
+    /// If the check fails or the listing has changed, the offerer is refunded,
+    /// otherwise the offer is placed and processed as for any other listing.
+    #[private]
+    pub fn nft_resolve_gate_near(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        approval_id: u64,
+        currency: String,
+        gate: ListingGate,
+        offer: OfferJson,
+    ) -> PromiseOrValue<()> {
+        let offer: Offer = offer.into();
+        match self.gated_listing(
+            nft_contract_id,
+            token_id,
+            approval_id,
+            currency,
+            &gate,
+            &offer,
+        ) {
+            None => {
+                Promise::new(offer.offerer_id).transfer(offer.amount);
+                PromiseOrValue::Value(())
+            }
+            Some(listing) => {
+                PromiseOrValue::Promise(self.place_offer(listing, offer))
+            }
+        }
+    }
+
+    /// Resolves the holder check of a gated listing that is bought with FTs.
+    /// If the check fails or the listing has changed, the full amount is
+    /// returned to the FT contract for refunding.
+    #[private]
+    pub fn nft_resolve_gate_ft(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        approval_id: u64,
+        currency: String,
+        gate: ListingGate,
+        offer: OfferJson,
+    ) -> PromiseOrValue<U128> {
+        let offer: Offer = offer.into();
+        match self.gated_listing(
+            nft_contract_id,
+            token_id,
+            approval_id,
+            currency,
+            &gate,
+            &offer,
+        ) {
+            None => PromiseOrValue::Value(offer.amount.into()),
+            Some(listing) => {
+                PromiseOrValue::Promise(self.place_offer(listing, offer))
+            }
+        }
+    }
+
+    /// Returns the listing if the receiver holds the required token and the
+    /// listing can still be bought as it was when the offer has been made.
+    /// Otherwise, the rejection is logged.
+    fn gated_listing(
+        &self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        approval_id: u64,
+        currency: String,
+        gate: &ListingGate,
+        offer: &Offer,
+    ) -> Option<Listing> {
+        let token_key = format!("{}<$>{}", nft_contract_id, token_id);
+        let listing = self.get_listing_internal(&token_key).filter(|listing| {
+            listing.nft_approval_id == approval_id
+                && listing.currency.to_string() == currency
+                && listing.gate.as_ref() == Some(gate)
+                && listing.current_offer.is_none()
+                && listing.price <= offer.amount
+        });
+
+        match listing {
+            Some(listing) if holds_gate_token(gate) => Some(listing),
+            _ => {
+                env::log_str(
+                    &NftGateRejectData {
+                        nft_contract_id,
+                        nft_token_id: token_id,
+                        offerer_id: offer.offerer_id.clone(),
+                        receiver_id: offer.receiver_id.clone(),
+                    }
+                    .serialize_event(),
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Checks the result of the holder check that precedes placing an offer on a
+/// gated listing.
+fn holds_gate_token(gate: &ListingGate) -> bool {
+    let value = match env::promise_result(0) {
+        PromiseResult::Successful(value) => value,
+        _ => return false,
+    };
+
+    match gate {
+        ListingGate::NftHolder { .. } => {
+            serde_json::from_slice::<Vec<NftToken>>(&value)
+                .is_ok_and(|tokens| !tokens.is_empty())
+        }
+        ListingGate::SbtHolder { class, .. } => {
+            serde_json::from_slice::<Vec<(AccountId, Vec<OwnedToken>)>>(&value)
+                .is_ok_and(|issuers| {
+                    issuers
+                        .iter()
+                        .flat_map(|(_, tokens)| tokens)
+                        .any(|token| token.metadata.class == *class)
+                })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mb_sdk::near_sdk::{
+        test_utils::get_logs,
+        testing_env,
+    };
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn nft_gate() -> ListingGate {
+        ListingGate::NftHolder {
+            nft_contract_id: account("pass.near"),
+        }
+    }
+
+    fn sbt_gate() -> ListingGate {
+        ListingGate::SbtHolder {
+            registry_id: account("registry.near"),
+            issuer_id: account("issuer.near"),
+            class: 1,
+        }
+    }
+
+    /// Deposits storage for one more listing or gate of `seller.near`.
+    fn deposit_storage(market: &mut Market) {
+        let deposit = market.listing_storage_deposit;
+        testing_env!(context("seller.near").attached_deposit(deposit).build());
+        market.deposit_storage();
+    }
+
+    fn gated_listing(market: &mut Market, gate: ListingGate) -> String {
+        let mut listing = listing("seller.near");
+        listing.gate = Some(gate);
+        let token_key = insert_listing(market, &listing);
+        deposit_storage(market);
+        market.add_listing_gate(&account("seller.near"));
+        token_key
+    }
+
+    fn resolve_gate(
+        market: &mut Market,
+        gate: ListingGate,
+        result: &str,
+    ) -> PromiseOrValue<()> {
+        set_promise_result(PromiseResult::Successful(result.into()));
+        market.nft_resolve_gate_near(
+            account("nft.near"),
+            "1".to_string(),
+            0,
+            Currency::Near.to_string(),
+            gate,
+            offer("buyer.near", "buyer.near", None).into(),
+        )
+    }
+
+    #[test]
+    fn seller_gates_a_listing() {
+        let mut market = setup_market();
+        let token_key = insert_listing(&mut market, &listing("seller.near"));
+        deposit_storage(&mut market);
+
+        testing_env!(context("seller.near").attached_deposit(1).build());
+        market.set_listing_gate(
+            account("nft.near"),
+            "1".to_string(),
+            Some(nft_gate()),
+        );
+        let listing = market.listings.get(&token_key).unwrap();
+        assert!(listing.gate == Some(nft_gate()));
+        assert!(get_logs()[0].contains(r#""event":"nft_listing_gate""#));
+        assert_eq!(market.free_storage_deposit(&account("seller.near")), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Storage for listing gate not covered")]
+    fn gate_requires_a_second_storage_deposit() {
+        let mut market = setup_market();
+        insert_listing(&mut market, &listing("seller.near"));
+
+        testing_env!(context("seller.near").attached_deposit(1).build());
+        market.set_listing_gate(
+            account("nft.near"),
+            "1".to_string(),
+            Some(nft_gate()),
+        );
+    }
+
+    #[test]
+    fn owner_lifts_a_gate() {
+        let mut market = setup_market();
+        let token_key = gated_listing(&mut market, nft_gate());
+
+        testing_env!(context("owner.near").attached_deposit(1).build());
+        market.set_listing_gate(account("nft.near"), "1".to_string(), None);
+        assert!(market.listings.get(&token_key).unwrap().gate.is_none());
+        // The second storage deposit is refunded
+        assert_eq!(
+            market.storage_deposit_by(&account("seller.near")),
+            market.listing_storage_deposit
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Method is restricted to the seller and market owner"
+    )]
+    fn stranger_cannot_gate_a_listing() {
+        let mut market = setup_market();
+        insert_listing(&mut market, &listing("seller.near"));
+
+        testing_env!(context("stranger.near").attached_deposit(1).build());
+        market.set_listing_gate(
+            account("nft.near"),
+            "1".to_string(),
+            Some(nft_gate()),
+        );
+    }
+
+    #[test]
+    fn buying_a_gated_listing_waits_for_the_check() {
+        let mut market = setup_market();
+        let token_key = gated_listing(&mut market, nft_gate());
+
+        testing_env!(context("buyer.near").attached_deposit(ONE_NEAR).build());
+        market.buy(account("nft.near"), "1".to_string(), None, None, None);
+        assert!(market
+            .listings
+            .get(&token_key)
+            .unwrap()
+            .current_offer
+            .is_none());
+    }
+
+    #[test]
+    fn holder_gets_the_offer_placed() {
+        let mut market = setup_market();
+        let token_key = gated_listing(&mut market, nft_gate());
+
+        let result = resolve_gate(
+            &mut market,
+            nft_gate(),
+            r#"[{"owner_id":"buyer.near"}]"#,
+        );
+        assert!(matches!(result, PromiseOrValue::Promise(_)));
+        let offer = market.listings.get(&token_key).unwrap().current_offer;
+        assert_eq!(offer.unwrap().offerer_id, account("buyer.near"));
+    }
+
+    #[test]
+    fn non_holder_is_refunded() {
+        let mut market = setup_market();
+        let token_key = gated_listing(&mut market, nft_gate());
+
+        let result = resolve_gate(&mut market, nft_gate(), "[]");
+        assert!(matches!(result, PromiseOrValue::Value(())));
+        let listing = market.listings.get(&token_key).unwrap();
+        assert!(listing.current_offer.is_none());
+        assert!(get_logs()[0].contains(r#""event":"nft_gate_reject""#));
+    }
+
+    #[test]
+    fn changed_listing_rejects_the_offer() {
+        let mut market = setup_market();
+        let mut listing = listing("seller.near");
+        listing.gate = Some(nft_gate());
+        listing.price = 2 * ONE_NEAR;
+        let token_key = insert_listing(&mut market, &listing);
+
+        let result = resolve_gate(
+            &mut market,
+            nft_gate(),
+            r#"[{"owner_id":"buyer.near"}]"#,
+        );
+        assert!(matches!(result, PromiseOrValue::Value(())));
+        assert!(market
+            .listings
+            .get(&token_key)
+            .unwrap()
+            .current_offer
+            .is_none());
+    }
+
+    #[test]
+    fn sbt_holder_needs_the_class() {
+        let mut market = setup_market();
+        let token_key = gated_listing(&mut market, sbt_gate());
+        let other_class =
+            r#"[["issuer.near",[{"token":1,"metadata":{"class":2}}]]]"#;
+        resolve_gate(&mut market, sbt_gate(), other_class);
+        assert!(market
+            .listings
+            .get(&token_key)
+            .unwrap()
+            .current_offer
+            .is_none());
+
+        let class = r#"[["issuer.near",[{"token":2,"metadata":{"class":1}}]]]"#;
+        resolve_gate(&mut market, sbt_gate(), class);
+        assert!(market
+            .listings
+            .get(&token_key)
+            .unwrap()
+            .current_offer
+            .is_some());
+    }
+
+    #[test]
+    fn ft_offer_is_returned_to_the_ft_contract() {
+        let mut market = setup_market();
+        let mut listing = listing("seller.near");
+        listing.gate = Some(nft_gate());
+        listing.currency = Currency::FtContract(account("usdc.near"));
+        insert_listing(&mut market, &listing);
+
+        set_promise_result(PromiseResult::Failed);
+        let result = market.nft_resolve_gate_ft(
+            account("nft.near"),
+            "1".to_string(),
+            0,
+            listing.currency.to_string(),
+            nft_gate(),
+            offer("buyer.near", "buyer.near", None).into(),
+        );
+        assert!(matches!(
+            result,
+            PromiseOrValue::Value(U128(amount)) if amount == ONE_NEAR
+        ));
+    }
+
+    #[test]
+    fn gated_ft_listing_fits_into_the_gas_of_ft_on_transfer() {
+        // Parsing the message and validating the offer needs to fit into the
+        // remainder
+        let nested_gas = GATE_CHECK_GAS.0 + NFT_RESOLVE_GATE_FT_GAS.0;
+        assert!(nested_gas + 50_000_000_000_000 <= FT_ON_TRANSFER_GAS.0);
+
+        let mut listing = listing("seller.near");
+        listing.gate = Some(nft_gate());
+        listing.currency = Currency::FtContract(account("usdc.near"));
+        assert_eq!(listing.max_len_payout(), MAX_LEN_PAYOUT_CHECKED_FT);
+        assert_eq!(
+            NFT_RESOLVE_GATE_FT_GAS.0,
+            5_000_000_000_000
+                + NFT_TRANSFER_PAYOUT_GAS.0
+                + listing.resolve_payout_gas().0
+        );
+    }
+}
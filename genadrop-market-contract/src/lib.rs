@@ -8,8 +8,11 @@ use mb_sdk::{
             BorshSerialize,
         },
         collections::{
+            LookupMap,
+            LookupSet,
             UnorderedMap,
             UnorderedSet,
+            Vector,
         },
         env,
         json_types::{
@@ -19,15 +22,34 @@ use mb_sdk::{
         AccountId,
         Balance,
         Promise,
+        PublicKey,
     },
 };
 
+mod analytics;
 /// Contains constants and type definitions
 mod data;
+/// Contains events that are not covered by `mb_sdk`
+mod events;
+/// Contains interfaces of other contracts that are not covered by `mb_sdk`
+mod ext;
+mod gates;
 mod listing;
+/// Contains the migration of the market state from previous versions
+mod migrate;
 mod offers;
+mod orders;
+mod rentals;
+mod stats;
+#[cfg(test)]
+mod test_utils;
+mod trait_offers;
 
 use data::*;
+use events::{
+    MarketConfigChangeData,
+    MarketPauseData,
+};
 
 // ------------------------- market smart contract -------------------------- //
 /// Storage of the market contract
@@ -46,6 +68,9 @@ pub struct Market {
     /// Simple counter how many listings a given account has with the market,
     /// required for book-keeping
     pub listings_count_by_account: UnorderedMap<AccountId, u64>,
+    /// How many of the listings of a given account are gated, as these need a
+    /// second storage deposit
+    pub gated_listings_count_by_account: LookupMap<AccountId, u64>,
     /// How much storage deposit we require for a single listing
     pub listing_storage_deposit: Balance,
     /// How long (in seconds) a listing must be active in the market before it
@@ -60,6 +85,36 @@ pub struct Market {
     pub fallback_cut: u16,
     /// The owner of the market, who is allowed to configure it.
     pub owner: AccountId,
+    /// The ed25519 public keys with which sellers sign off-chain orders
+    pub order_keys: UnorderedMap<AccountId, PublicKey>,
+    /// Nonces of signed orders that have been filled or cancelled, keyed by
+    /// `<seller_id><$><nonce>`
+    pub used_order_nonces: LookupSet<String>,
+    /// The most recent sales of each token, keyed by token key
+    pub sales_by_token: LookupMap<String, Vec<SaleRecord>>,
+    /// Aggregated sales of each NFT contract
+    pub collection_stats: UnorderedMap<AccountId, CollectionStats>,
+    /// How long (in seconds) a token cannot be traded again between the same
+    /// pair of accounts
+    pub wash_trade_cooldown_seconds: u64,
+    /// Contains all NFTs that are escrowed for rent
+    pub rentals: UnorderedMap<String, Rental>,
+    /// Rentals whose collateral has been claimed, keyed by token key. Their
+    /// renters cannot return the NFT anymore.
+    pub claimed_rentals: LookupMap<String, ClaimedRental>,
+    /// Market functionality that is currently paused
+    pub paused: PauseFlags,
+    /// Accounts that may pause, but not unpause, the market
+    pub guardians: UnorderedSet<AccountId>,
+    /// Contains all escrowed offers on tokens with specific traits
+    pub trait_offers: UnorderedMap<u64, TraitOffer>,
+    /// ID that will be assigned to the next trait offer
+    pub next_trait_offer_id: u64,
+    /// Bounds for listing prices, keyed by currency (`near` or
+    /// `ft::<contract>`)
+    pub price_bounds: UnorderedMap<String, PriceBounds>,
+    /// Audit log of all changes to the market configuration, oldest first
+    pub config_changes: Vector<ConfigChange>,
 }
 
 #[near_sdk::near_bindgen]
@@ -77,11 +132,25 @@ impl Market {
             referrers: UnorderedMap::new(&b"r"[..]),
             storage_deposits_by_account: UnorderedMap::new(&b"a2d"[..]),
             listings_count_by_account: UnorderedMap::new(&b"a2l"[..]),
+            gated_listings_count_by_account: LookupMap::new(&b"a2g"[..]),
             listing_storage_deposit: TEN_MILLINEAR,
             listing_lock_seconds: listing_lock_seconds.0,
             genadrop_cut,
             fallback_cut,
             owner,
+            order_keys: UnorderedMap::new(&b"ok"[..]),
+            used_order_nonces: LookupSet::new(&b"on"[..]),
+            sales_by_token: LookupMap::new(&b"k2s"[..]),
+            collection_stats: UnorderedMap::new(&b"c2s"[..]),
+            wash_trade_cooldown_seconds: WASH_TRADE_COOLDOWN_SECONDS,
+            rentals: UnorderedMap::new(&b"k2r"[..]),
+            claimed_rentals: LookupMap::new(&b"k2c"[..]),
+            paused: PauseFlags::default(),
+            guardians: UnorderedSet::new(&b"g"[..]),
+            trait_offers: UnorderedMap::new(&b"to"[..]),
+            next_trait_offer_id: 0,
+            price_bounds: UnorderedMap::new(&b"pb"[..]),
+            config_changes: Vector::new(&b"cc"[..]),
         }
     }
 
@@ -92,6 +161,12 @@ impl Market {
     #[payable]
     pub fn set_owner(&mut self, new_owner: AccountId) {
         self.assert_predecessor_is_owner();
+        self.record_config_change(
+            "owner",
+            None,
+            Some(self.owner.to_string()),
+            Some(new_owner.to_string()),
+        );
         self.owner = new_owner;
     }
     /// Show owner of the market contract
@@ -106,6 +181,12 @@ impl Market {
     #[payable]
     pub fn set_genadrop_cut(&mut self, new_cut: u16) {
         self.assert_predecessor_is_owner();
+        self.record_config_change(
+            "genadrop_cut",
+            None,
+            Some(self.genadrop_cut.to_string()),
+            Some(new_cut.to_string()),
+        );
         self.genadrop_cut = new_cut;
     }
     /// Show cut that genadrop takes from each affiliate sale
@@ -119,6 +200,12 @@ impl Market {
     #[payable]
     pub fn set_fallback_cut(&mut self, new_cut: u16) {
         self.assert_predecessor_is_owner();
+        self.record_config_change(
+            "fallback_cut",
+            None,
+            Some(self.fallback_cut.to_string()),
+            Some(new_cut.to_string()),
+        );
         self.fallback_cut = new_cut;
     }
     /// Show the cut that the market keeps on non-affiliated sales.
@@ -132,6 +219,12 @@ impl Market {
     #[payable]
     pub fn set_listing_lock_seconds(&mut self, secs: U64) {
         self.assert_predecessor_is_owner();
+        self.record_config_change(
+            "listing_lock_seconds",
+            None,
+            Some(self.listing_lock_seconds.to_string()),
+            Some(secs.0.to_string()),
+        );
         self.listing_lock_seconds = secs.0;
     }
     /// Show duration (in seconds) that each listing is locked after creation.
@@ -139,12 +232,38 @@ impl Market {
         self.listing_lock_seconds.into()
     }
 
+    // -------- wash trading cooldown
+    /// Set the duration (in seconds) during which a token cannot change hands
+    /// again between the same pair of accounts. Only the owner can call this.
+    #[payable]
+    pub fn set_wash_trade_cooldown_seconds(&mut self, secs: U64) {
+        self.assert_predecessor_is_owner();
+        self.record_config_change(
+            "wash_trade_cooldown_seconds",
+            None,
+            Some(self.wash_trade_cooldown_seconds.to_string()),
+            Some(secs.0.to_string()),
+        );
+        self.wash_trade_cooldown_seconds = secs.0;
+    }
+    /// Show duration (in seconds) during which a token cannot change hands
+    /// again between the same pair of accounts.
+    pub fn get_wash_trade_cooldown_seconds(&self) -> U64 {
+        self.wash_trade_cooldown_seconds.into()
+    }
+
     // -------- storage deposit for single listing
     /// Set the deposit in yoctoNEAR that each listing will (maximally) require.
     /// Only the owner can call this.
     #[payable]
     pub fn set_listing_storage_deposit(&mut self, deposit: U128) {
         self.assert_predecessor_is_owner();
+        self.record_config_change(
+            "listing_storage_deposit",
+            None,
+            Some(self.listing_storage_deposit.to_string()),
+            Some(deposit.0.to_string()),
+        );
         self.listing_storage_deposit = deposit.0;
     }
     /// Show current deposit in yoctoNEAR that each listing will (maximally)
@@ -153,6 +272,57 @@ impl Market {
         self.listing_storage_deposit.into()
     }
 
+    // -------- price bounds per currency
+    /// Set the lowest and (optionally) highest price at which tokens can be
+    /// listed, sold by signed orders, bought by trait offers, or rented per
+    /// day for a currency. The currency is given by its FT contract, or none
+    /// for NEAR. Only the owner can call this.
+    #[payable]
+    pub fn set_price_bounds(
+        &mut self,
+        currency: Option<AccountId>,
+        min: U128,
+        max: Option<U128>,
+    ) {
+        self.assert_predecessor_is_owner();
+        near_assert!(
+            !matches!(max, Some(max) if min.0 > max.0),
+            "Minimum price cannot exceed maximum price"
+        );
+        let currency = Currency::from(currency).to_string();
+        let bounds = PriceBounds {
+            min: min.0,
+            max: max.map(|max| max.0),
+        };
+        let old_bounds = self.price_bounds.insert(&currency, &bounds);
+        self.record_config_change(
+            "price_bounds",
+            Some(currency),
+            old_bounds.map(|bounds| bounds.to_string()),
+            Some(bounds.to_string()),
+        );
+    }
+    /// Remove the price bounds for a currency. Only the owner can call this.
+    #[payable]
+    pub fn remove_price_bounds(&mut self, currency: Option<AccountId>) {
+        self.assert_predecessor_is_owner();
+        let currency = Currency::from(currency).to_string();
+        let old_bounds = self.price_bounds.remove(&currency);
+        self.record_config_change(
+            "price_bounds",
+            Some(currency),
+            old_bounds.map(|bounds| bounds.to_string()),
+            None,
+        );
+    }
+    /// Show the price bounds of all currencies that have them.
+    pub fn price_bounds(&self) -> Vec<PriceBoundsJson> {
+        self.price_bounds
+            .iter()
+            .map(|(currency, bounds)| bounds.into_json(currency))
+            .collect()
+    }
+
     // -------- banning accounts
     /// Add an account to the banlist. These might be misbehaving NFT contracts,
     /// FT contracts, sellers, or buyers. Banned accounts will still be
@@ -160,13 +330,25 @@ impl Market {
     #[payable]
     pub fn ban(&mut self, account_id: AccountId) {
         self.assert_predecessor_is_owner();
-        self.banned_accounts.insert(&account_id);
+        let was_banned = !self.banned_accounts.insert(&account_id);
+        self.record_config_change(
+            "banned_accounts",
+            Some(account_id.to_string()),
+            was_banned.then(|| true.to_string()),
+            Some(true.to_string()),
+        );
     }
     /// Remove an account from the banlist.  Only the owner can call this.
     #[payable]
     pub fn unban(&mut self, account_id: AccountId) {
         self.assert_predecessor_is_owner();
-        self.banned_accounts.remove(&account_id);
+        let was_banned = self.banned_accounts.remove(&account_id);
+        self.record_config_change(
+            "banned_accounts",
+            Some(account_id.to_string()),
+            was_banned.then(|| true.to_string()),
+            None,
+        );
     }
     /// Show a list of all accounts that are banned from interacting with the
     /// market.
@@ -181,19 +363,105 @@ impl Market {
     #[payable]
     pub fn add_affiliate(&mut self, account_id: AccountId, cut: u16) {
         self.assert_predecessor_is_owner();
-        self.referrers.insert(&account_id, &cut);
+        let old_cut = self.referrers.insert(&account_id, &cut);
+        self.record_config_change(
+            "affiliates",
+            Some(account_id.to_string()),
+            old_cut.map(|cut| cut.to_string()),
+            Some(cut.to_string()),
+        );
     }
     /// Remove a registered affiliate. Only the owner can call this.
     #[payable]
     pub fn del_affiliate(&mut self, account_id: AccountId) {
         self.assert_predecessor_is_owner();
-        self.referrers.remove(&account_id);
+        let old_cut = self.referrers.remove(&account_id);
+        self.record_config_change(
+            "affiliates",
+            Some(account_id.to_string()),
+            old_cut.map(|cut| cut.to_string()),
+            None,
+        );
     }
     /// Show all registered affiliates together with their custom fees.
     pub fn affiliates(&self) -> Vec<(AccountId, u16)> {
         self.referrers.iter().collect()
     }
 
+    // -------- emergency pause
+    /// Add a guardian, who is allowed to pause the market. Only the owner can
+    /// call this.
+    #[payable]
+    pub fn add_guardian(&mut self, account_id: AccountId) {
+        self.assert_predecessor_is_owner();
+        let was_guardian = !self.guardians.insert(&account_id);
+        self.record_config_change(
+            "guardians",
+            Some(account_id.to_string()),
+            was_guardian.then(|| true.to_string()),
+            Some(true.to_string()),
+        );
+    }
+    /// Remove a guardian. Only the owner can call this.
+    #[payable]
+    pub fn remove_guardian(&mut self, account_id: AccountId) {
+        self.assert_predecessor_is_owner();
+        let was_guardian = self.guardians.remove(&account_id);
+        self.record_config_change(
+            "guardians",
+            Some(account_id.to_string()),
+            was_guardian.then(|| true.to_string()),
+            None,
+        );
+    }
+    /// Show all guardians.
+    pub fn guardians(&self) -> Vec<AccountId> {
+        self.guardians.iter().collect()
+    }
+
+    /// Pause the given market functionality. Resolving in-flight transfers,
+    /// refunds, unlisting and returning rentals continue to work. The owner
+    /// and guardians can call this.
+    #[payable]
+    pub fn pause(&mut self, kinds: Vec<PauseKind>) {
+        near_sdk::assert_one_yocto();
+        let caller_id = env::predecessor_account_id();
+        near_assert!(
+            caller_id == self.owner || self.guardians.contains(&caller_id),
+            "Method is restricted to market owner and guardians"
+        );
+        self.set_paused(kinds, true, caller_id);
+    }
+    /// Unpause the given market functionality. Only the owner can call this.
+    #[payable]
+    pub fn unpause(&mut self, kinds: Vec<PauseKind>) {
+        self.assert_predecessor_is_owner();
+        self.set_paused(kinds, false, env::predecessor_account_id());
+    }
+    /// Show which market functionality is currently paused.
+    pub fn get_paused(&self) -> PauseFlags {
+        self.paused.clone()
+    }
+
+    // -------- audit log
+    /// Show changes to the market configuration, oldest first.
+    pub fn get_config_changes(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<ConfigChangeJson> {
+        self.config_changes
+            .iter()
+            .skip(from_index.unwrap_or(U64(0)).0 as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(Into::into)
+            .collect()
+    }
+    /// Show the number of changes to the market configuration.
+    pub fn get_config_changes_count(&self) -> U64 {
+        self.config_changes.len().into()
+    }
+
     // ---------------------- anything related to storage ----------------------
     /// Get the number of listings created by a specific account ID
     pub fn get_listings_count(&self, account: &AccountId) -> U64 {
@@ -217,6 +485,45 @@ impl Market {
         }
     }
 
+    /// Get the number of storage deposits required for the listings of a
+    /// specific account ID. Gated listings require two deposits.
+    fn get_required_deposits_count(&self, account: &AccountId) -> u64 {
+        self.get_listings_count(account).0
+            + self
+                .gated_listings_count_by_account
+                .get(account)
+                .unwrap_or(0)
+    }
+    /// Backs the gate of a listing with a second storage deposit.
+    fn add_listing_gate(&mut self, account: &AccountId) {
+        near_assert!(
+            self.free_storage_deposit(account) >= self.listing_storage_deposit,
+            "Storage for listing gate not covered"
+        );
+        let new_count = self
+            .gated_listings_count_by_account
+            .get(account)
+            .unwrap_or(0)
+            + 1;
+        self.gated_listings_count_by_account
+            .insert(account, &new_count);
+    }
+    /// Refunds the second storage deposit of a gated listing.
+    fn refund_listing_gate(&mut self, account: &AccountId) -> Promise {
+        let new_count = self
+            .gated_listings_count_by_account
+            .get(account)
+            .unwrap_or(0)
+            - 1;
+        if new_count == 0 {
+            self.gated_listings_count_by_account.remove(account);
+        } else {
+            self.gated_listings_count_by_account
+                .insert(account, &new_count);
+        }
+        self.refund_storage_deposit(account, self.listing_storage_deposit, 0)
+    }
+
     /// Get the storage deposit required for all the listings of a specific
     /// account ID.
     pub fn get_storage_deposit(&self, account: &AccountId) -> U128 {
@@ -244,7 +551,7 @@ impl Market {
 
         // get required amount
         let deposit = self.storage_deposit_by(&account);
-        let required = self.get_listings_count(&account).0 as Balance
+        let required = self.get_required_deposits_count(&account) as Balance
             * self.listing_storage_deposit;
         let refund = deposit - required;
 
@@ -278,6 +585,70 @@ impl Market {
         Promise::new(account.to_owned()).transfer(refund - retain)
     }
 
+    /// Refund the storage deposit of a single listing that has been removed.
+    /// Listings created from signed orders are not backed by a deposit, and
+    /// for trait offers the settled offer is removed instead.
+    ///
+    /// `storage_fee` pays for the record of a sale. It is kept back from the
+    /// listing deposit, from the storage deposit of a trait offer, or taken
+    /// from the seller's storage deposit for signed orders.
+    fn refund_listing(
+        &mut self,
+        listing: &Listing,
+        storage_fee: Balance,
+        retain: Balance,
+    ) {
+        if let Some(offer_id) = listing.trait_offer_id {
+            self.remove_trait_offer(offer_id, storage_fee);
+        } else if listing.order_nonce.is_some() {
+            self.take_storage_deposit(&listing.nft_owner_id, storage_fee);
+        } else {
+            let storage_fee =
+                storage_fee.min(self.listing_storage_deposit - retain);
+            self.refund_listings(
+                &listing.nft_owner_id,
+                1,
+                retain + storage_fee,
+            );
+            if listing.gate.is_some() {
+                self.refund_listing_gate(&listing.nft_owner_id);
+            }
+        }
+    }
+
+    /// Keeps up to `amount` of the free storage deposit of an account, e.g.
+    /// for storage that has been used on their behalf.
+    fn take_storage_deposit(&mut self, account: &AccountId, amount: Balance) {
+        let amount = amount.min(self.free_storage_deposit(account));
+        if amount == 0 {
+            return;
+        }
+        let new_deposit = self.storage_deposit_by(account) - amount;
+        if new_deposit == 0 {
+            self.storage_deposits_by_account.remove(account);
+        } else {
+            self.storage_deposits_by_account
+                .insert(account, &new_deposit);
+        }
+    }
+
+    /// Panics if the attached deposit does not cover the storage that has
+    /// been used since `initial_storage_usage`, and refunds the excess.
+    fn charge_storage(&self, initial_storage_usage: u64) {
+        let used = env::storage_usage().saturating_sub(initial_storage_usage);
+        let required = env::storage_byte_cost() * used as Balance;
+        let deposit = env::attached_deposit();
+        near_assert!(
+            deposit >= required,
+            "Must attach {} yoctoNEAR to cover storage",
+            required
+        );
+        if deposit > required {
+            Promise::new(env::predecessor_account_id())
+                .transfer(deposit - required);
+        }
+    }
+
     /// Decrease listings count and refund the lister with the deposits.
     fn refund_listings(
         &mut self,
@@ -306,6 +677,93 @@ impl Market {
         );
     }
 
+    /// Panics if the price is outside the bounds for the currency. FT prices
+    /// must not be zero, as each FT transfer costs the market one yoctoNEAR.
+    fn assert_valid_price(&self, currency: &Currency, price: Balance) {
+        near_assert!(
+            currency.is_near() || price > 0,
+            "Cannot trade for zero FTs"
+        );
+        if let Some(bounds) = self.price_bounds.get(&currency.to_string()) {
+            near_assert!(
+                price >= bounds.min,
+                "Price must be at least {}",
+                bounds.min
+            );
+            if let Some(max) = bounds.max {
+                near_assert!(price <= max, "Price must be at most {}", max);
+            }
+        }
+    }
+
+    /// Panics if the given market functionality is paused
+    fn assert_not_paused(&self, kind: PauseKind) {
+        near_assert!(
+            !self.paused.is_paused(kind),
+            "The market has paused {}",
+            kind.to_string()
+        );
+    }
+
+    /// Updates pause flags and logs an event for each changed flag.
+    fn set_paused(
+        &mut self,
+        kinds: Vec<PauseKind>,
+        paused: bool,
+        caller_id: AccountId,
+    ) {
+        for kind in kinds {
+            if self.paused.is_paused(kind) == paused {
+                continue;
+            }
+            self.paused.set(kind, paused);
+            self.record_config_change(
+                "paused",
+                Some(kind.to_string()),
+                Some((!paused).to_string()),
+                Some(paused.to_string()),
+            );
+            env::log_str(
+                &MarketPauseData {
+                    kind: kind.to_string(),
+                    paused,
+                    caller_id: caller_id.clone(),
+                }
+                .serialize_event(),
+            );
+        }
+    }
+
+    /// Appends a change to the configuration audit log and logs an event for
+    /// it. The caller is the predecessor of the current call.
+    fn record_config_change(
+        &mut self,
+        setting: &str,
+        subject: Option<String>,
+        old_value: Option<String>,
+        new_value: Option<String>,
+    ) {
+        let caller_id = env::predecessor_account_id();
+        env::log_str(
+            &MarketConfigChangeData {
+                setting: setting.to_string(),
+                subject: subject.clone(),
+                old_value: old_value.clone(),
+                new_value: new_value.clone(),
+                caller_id: caller_id.clone(),
+            }
+            .serialize_event(),
+        );
+        self.config_changes.push(&ConfigChange {
+            setting: setting.to_string(),
+            subject,
+            old_value,
+            new_value,
+            caller_id,
+            changed_at: env::block_timestamp(),
+        });
+    }
+
     /// Panics if the current call is not from the market owner.
     fn assert_predecessor_is_owner(&self) {
         near_sdk::assert_one_yocto();
@@ -319,8 +777,233 @@ impl Market {
     /// needed to cover listings
     fn free_storage_deposit(&self, account: &AccountId) -> Balance {
         let deposit = self.storage_deposit_by(account);
-        let required = self.get_listings_count(account).0 as u128
+        let required = self.get_required_deposits_count(account) as u128
             * self.listing_storage_deposit;
         deposit - required
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mb_sdk::near_sdk::{
+        test_utils::get_logs,
+        testing_env,
+    };
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn pause(market: &mut Market, caller_id: &str, kind: PauseKind) {
+        testing_env!(context(caller_id).attached_deposit(1).build());
+        market.pause(vec![kind]);
+    }
+
+    #[test]
+    fn guardian_pauses_the_market() {
+        let mut market = setup_market();
+        testing_env!(context("owner.near").attached_deposit(1).build());
+        market.add_guardian(account("guardian.near"));
+
+        pause(&mut market, "guardian.near", PauseKind::Purchases);
+        let paused = market.get_paused();
+        assert!(paused.purchases);
+        assert!(!paused.listings && !paused.offers);
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""kind":"purchases","paused":true"#));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Method is restricted to market owner and guardians"
+    )]
+    fn stranger_cannot_pause_the_market() {
+        let mut market = setup_market();
+        pause(&mut market, "stranger.near", PauseKind::Listings);
+    }
+
+    #[test]
+    #[should_panic(expected = "Method is restricted to market owner")]
+    fn guardian_cannot_unpause_the_market() {
+        let mut market = setup_market();
+        testing_env!(context("owner.near").attached_deposit(1).build());
+        market.add_guardian(account("guardian.near"));
+        pause(&mut market, "guardian.near", PauseKind::Listings);
+
+        testing_env!(context("guardian.near").attached_deposit(1).build());
+        market.unpause(vec![PauseKind::Listings]);
+    }
+
+    #[test]
+    fn owner_unpauses_the_market() {
+        let mut market = setup_market();
+        pause(&mut market, "owner.near", PauseKind::Listings);
+        testing_env!(context("owner.near").attached_deposit(1).build());
+        market.unpause(vec![PauseKind::Listings]);
+        assert!(!market.get_paused().listings);
+    }
+
+    #[test]
+    #[should_panic(expected = "The market has paused listings")]
+    fn paused_market_rejects_listings() {
+        let mut market = setup_market();
+        pause(&mut market, "owner.near", PauseKind::Listings);
+        testing_env!(context("nft.near").build());
+        market.nft_on_approve(
+            "1".to_string(),
+            account("seller.near"),
+            0,
+            r#"{"price":"1"}"#.to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The market has paused purchases")]
+    fn paused_market_rejects_purchases() {
+        let mut market = setup_market();
+        insert_listing(&mut market, &listing("seller.near"));
+        pause(&mut market, "owner.near", PauseKind::Purchases);
+        testing_env!(context("buyer.near").attached_deposit(ONE_NEAR).build());
+        market.buy(account("nft.near"), "1".to_string(), None, None, None);
+    }
+
+    #[test]
+    fn config_changes_are_recorded() {
+        let mut market = setup_market();
+        testing_env!(context("owner.near").attached_deposit(1).build());
+        market.set_fallback_cut(500);
+        market.ban(account("spammer.near"));
+        market.unban(account("spammer.near"));
+
+        assert_eq!(market.get_config_changes_count(), U64(3));
+        let changes = market.get_config_changes(None, None);
+        assert_eq!(changes[0].setting, "fallback_cut");
+        assert_eq!(changes[0].subject, None);
+        assert_eq!(changes[0].old_value, Some("250".to_string()));
+        assert_eq!(changes[0].new_value, Some("500".to_string()));
+        assert_eq!(changes[0].caller_id, account("owner.near"));
+        assert_eq!(changes[0].changed_at, U64(1000 * SECOND));
+        assert_eq!(changes[1].subject, Some("spammer.near".to_string()));
+        assert_eq!(changes[1].old_value, None);
+        assert_eq!(changes[2].old_value, Some("true".to_string()));
+        assert_eq!(changes[2].new_value, None);
+        assert!(get_logs()[0].contains(r#""event":"market_config_change""#));
+    }
+
+    #[test]
+    fn config_changes_are_paginated() {
+        let mut market = setup_market();
+        testing_env!(context("owner.near").attached_deposit(1).build());
+        for cut in 1..=5 {
+            market.set_genadrop_cut(cut);
+        }
+
+        let changes = market.get_config_changes(Some(U64(1)), Some(2));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].new_value, Some("2".to_string()));
+        assert_eq!(changes[1].new_value, Some("3".to_string()));
+        assert!(market.get_config_changes(Some(U64(5)), None).is_empty());
+    }
+
+    #[test]
+    fn repeated_pause_is_recorded_once() {
+        let mut market = setup_market();
+        pause(&mut market, "owner.near", PauseKind::Offers);
+        pause(&mut market, "owner.near", PauseKind::Offers);
+
+        let changes = market.get_config_changes(None, None);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].setting, "paused");
+        assert_eq!(changes[0].subject, Some("offers".to_string()));
+    }
+
+    fn setup_bounded_market(
+        currency: Option<&str>,
+        max: Option<u128>,
+    ) -> Market {
+        let mut market = setup_market();
+        testing_env!(context("owner.near").attached_deposit(1).build());
+        market.set_price_bounds(
+            currency.map(account),
+            U128(100),
+            max.map(U128),
+        );
+        market
+    }
+
+    fn ft() -> Currency {
+        Currency::FtContract(account("usdc.near"))
+    }
+
+    #[test]
+    fn any_price_is_valid_without_bounds() {
+        let market = setup_market();
+        market.assert_valid_price(&Currency::Near, 0);
+        market.assert_valid_price(&Currency::Near, u128::MAX);
+        market.assert_valid_price(&ft(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot trade for zero FTs")]
+    fn zero_ft_price_is_invalid() {
+        let market = setup_market();
+        market.assert_valid_price(&ft(), 0);
+    }
+
+    #[test]
+    fn prices_within_bounds_are_valid() {
+        let market = setup_bounded_market(None, Some(200));
+        market.assert_valid_price(&Currency::Near, 100);
+        market.assert_valid_price(&Currency::Near, 200);
+        // Bounds only apply to their own currency
+        market.assert_valid_price(&ft(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Price must be at least 100")]
+    fn price_below_min_is_invalid() {
+        let market = setup_bounded_market(None, Some(200));
+        market.assert_valid_price(&Currency::Near, 99);
+    }
+
+    #[test]
+    #[should_panic(expected = "Price must be at most 200")]
+    fn price_above_max_is_invalid() {
+        let market = setup_bounded_market(Some("usdc.near"), Some(200));
+        market.assert_valid_price(&ft(), 201);
+    }
+
+    #[test]
+    fn prices_without_max_are_unbounded() {
+        let market = setup_bounded_market(None, None);
+        market.assert_valid_price(&Currency::Near, u128::MAX);
+    }
+
+    #[test]
+    fn removed_bounds_no_longer_apply() {
+        let mut market = setup_bounded_market(None, None);
+        market.remove_price_bounds(None);
+        market.assert_valid_price(&Currency::Near, 1);
+        assert!(market.price_bounds().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Minimum price cannot exceed maximum price")]
+    fn min_price_cannot_exceed_max_price() {
+        setup_bounded_market(None, Some(99));
+    }
+
+    #[test]
+    #[should_panic(expected = "Price must be at least 100")]
+    fn listing_below_min_is_rejected() {
+        let mut market = setup_bounded_market(None, None);
+        testing_env!(context("nft.near").build());
+        market.nft_on_approve(
+            "1".to_string(),
+            account("seller.near"),
+            0,
+            r#"{"price":"99"}"#.to_string(),
+        );
+    }
+}
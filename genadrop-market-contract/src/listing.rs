@@ -4,6 +4,7 @@ use mb_sdk::{
     near_sdk::{
         self,
         env,
+        json_types::U128,
         AccountId,
     },
     utils::{
@@ -14,6 +15,10 @@ use mb_sdk::{
 
 use crate::{
     data::*,
+    events::{
+        NftListingGateData,
+        NftUpdateListingData,
+    },
     Market,
     MarketExt,
 };
@@ -28,6 +33,13 @@ impl Market {
     /// - The `token_id` must not be larger than 128 bytes. This is to prevent
     ///   a storage staking attack by large token IDs
     /// - The owner must have sufficient storage deposits to cover the listing.
+    ///   Gated listings require a second storage deposit.
+    /// - Listings must not be paused.
+    /// - The price must be within the bounds configured for its currency, and
+    ///   must not be zero for FTs.
+    ///
+    /// If the message contains a `gate`, purchases are restricted to holders
+    /// of the specified token, see the `gates` module.
     pub fn nft_on_approve(
         &mut self,
         token_id: String,
@@ -35,6 +47,7 @@ impl Market {
         approval_id: u64,
         msg: String,
     ) {
+        self.assert_not_paused(PauseKind::Listings);
         let nft_contract_id = env::predecessor_account_id();
         let msg: CreateListingMsg =
             near_parse(&msg, "Invalid arguments to create listing");
@@ -53,6 +66,7 @@ impl Market {
             listing.nft_token_id.len() <= 128,
             "Cannot process token IDs with more than 128 bytes"
         );
+        self.assert_valid_price(&listing.currency, listing.price);
         // Lister must have purchased storage for processing
         near_assert!(
             self.free_storage_deposit(&listing.nft_owner_id)
@@ -61,12 +75,22 @@ impl Market {
         );
 
         self.increase_listings_count(&listing.nft_owner_id, 1);
+        if listing.gate.is_some() {
+            self.add_listing_gate(&listing.nft_owner_id);
+        }
         if let Some(old_listing) =
             self.listings.insert(&listing.token_key(), &listing)
         {
-            if listing.current_offer.is_some() {
+            // Listings that are settling an offer, including those created
+            // for signed orders and trait offers, must not be replaced
+            if old_listing.current_offer.is_some() || old_listing.is_transient()
+            {
                 env::panic_str(ERR_OFFER_IN_PROGRESS);
             }
+            // The gate of the replaced listing is no longer backed by a deposit
+            if old_listing.gate.is_some() {
+                self.refund_listing_gate(&old_listing.nft_owner_id);
+            }
             env::log_str(
                 &events::NftUnlistData {
                     nft_contract_id: old_listing.nft_contract_id,
@@ -77,6 +101,15 @@ impl Market {
             );
         }
 
+        let gate_event = listing.gate.clone().map(|gate| {
+            NftListingGateData {
+                nft_contract_id: listing.nft_contract_id.clone(),
+                nft_token_id: listing.nft_token_id.clone(),
+                gate: Some(gate),
+                caller_id: listing.nft_owner_id.clone(),
+            }
+            .serialize_event()
+        });
         env::log_str(
             &events::NftListData {
                 kind: LISTING_KIND_SIMPLE.to_string(),
@@ -88,7 +121,10 @@ impl Market {
                 price: listing.price.into(),
             }
             .serialize_event(),
-        )
+        );
+        if let Some(gate_event) = gate_event {
+            env::log_str(&gate_event);
+        }
     }
 
     /// Allows a token owner to unlist tokens from this marketplace. The
@@ -115,6 +151,9 @@ impl Market {
                 }
                 .serialize_event(),
             );
+            if listing.gate.is_some() {
+                self.refund_listing_gate(&listing.nft_owner_id);
+            }
         }
 
         self.refund_listings(
@@ -124,6 +163,92 @@ impl Market {
         );
     }
 
+    /// Allows a token owner to change the price and currency of a listing
+    /// without relisting it. Unlike approving the token again, this keeps the
+    /// creation time of the listing and therefore its lock period.
+    ///
+    /// - The listing must not have an offer in progress.
+    /// - The owner must not be banned. If the NFT is listed for an FT, the FT
+    ///   contract must not be banned.
+    /// - The price must be within the bounds configured for its currency, and
+    ///   must not be zero for FTs.
+    /// - Listings must not be paused.
+    #[payable]
+    pub fn update_listing(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        price: U128,
+        currency: Option<AccountId>,
+    ) {
+        near_sdk::assert_one_yocto();
+        self.update_single_listing(
+            &format!("{}<$>{}", nft_contract_id, token_id),
+            price,
+            currency.into(),
+        );
+    }
+
+    /// Same as `update_listing`, but for multiple tokens at once, as long as
+    /// they live on the same smart contract.
+    #[payable]
+    pub fn update_listings(
+        &mut self,
+        nft_contract_id: AccountId,
+        updates: Vec<ListingUpdate>,
+    ) {
+        near_sdk::assert_one_yocto();
+        for update in updates {
+            self.update_single_listing(
+                &format!("{}<$>{}", nft_contract_id, update.token_id),
+                update.price,
+                update.currency.into(),
+            );
+        }
+    }
+
+    /// Internally used for updating listings, panics if the listing cannot be
+    /// updated or method is not called by token owner
+    fn update_single_listing(
+        &mut self,
+        token_key: &String,
+        price: U128,
+        currency: Currency,
+    ) {
+        self.assert_not_paused(PauseKind::Listings);
+        let mut listing = match self.get_listing_internal(token_key) {
+            None => env::panic_str(ERR_LISTING_NOT_FOUND),
+            Some(l) => l,
+        };
+
+        if listing.current_offer.is_some() {
+            env::panic_str(ERR_OFFER_IN_PROGRESS);
+        }
+        assert_predecessor(&listing.nft_owner_id);
+        self.assert_not_banned(&listing.nft_owner_id);
+        if let Currency::FtContract(ft_contract_id) = &currency {
+            self.assert_not_banned(ft_contract_id)
+        }
+        self.assert_valid_price(&currency, price.0);
+
+        let old_currency = std::mem::replace(&mut listing.currency, currency);
+        let old_price = std::mem::replace(&mut listing.price, price.0);
+        self.listings.insert(token_key, &listing);
+
+        env::log_str(
+            &NftUpdateListingData {
+                nft_contract_id: listing.nft_contract_id,
+                nft_token_id: listing.nft_token_id,
+                nft_approval_id: listing.nft_approval_id,
+                old_currency: old_currency.to_string(),
+                old_price: old_price.into(),
+                currency: listing.currency.to_string(),
+                price,
+            }
+            .serialize_event(),
+        );
+    }
+
     /// Internally used for unlisting NFTs, panics if withdrawal is impossible
     /// or method is not called by token owner
     fn unlist_single_nft(&mut self, token_key: &String) -> Listing {
@@ -170,3 +295,142 @@ impl Market {
         self.listings.get(token_key)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mb_sdk::near_sdk::{
+        test_utils::get_logs,
+        testing_env,
+        Balance,
+    };
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn update(market: &mut Market, caller_id: &str, price: Balance) {
+        testing_env!(context(caller_id).attached_deposit(1).build());
+        market.update_listing(
+            account("nft.near"),
+            "1".to_string(),
+            U128(price),
+            None,
+        );
+    }
+
+    #[test]
+    fn seller_updates_the_price() {
+        let mut market = setup_market();
+        let mut listing = listing("seller.near");
+        listing.created_at = 500 * SECOND;
+        let token_key = insert_listing(&mut market, &listing);
+
+        update(&mut market, "seller.near", 2 * ONE_NEAR);
+        let listing = market.listings.get(&token_key).unwrap();
+        assert_eq!(listing.price, 2 * ONE_NEAR);
+        assert_eq!(listing.created_at, 500 * SECOND);
+        assert!(get_logs()[0].contains(r#""event":"nft_update_listing""#));
+        assert!(get_logs()[0]
+            .contains(r#""old_price":"1000000000000000000000000""#));
+    }
+
+    #[test]
+    fn seller_switches_to_an_ft() {
+        let mut market = setup_market();
+        let token_key = insert_listing(&mut market, &listing("seller.near"));
+
+        testing_env!(context("seller.near").attached_deposit(1).build());
+        market.update_listings(
+            account("nft.near"),
+            vec![ListingUpdate {
+                token_id: "1".to_string(),
+                price: U128(100),
+                currency: Some(account("usdc.near")),
+            }],
+        );
+        let listing = market.listings.get(&token_key).unwrap();
+        assert_eq!(listing.price, 100);
+        assert_eq!(
+            listing.currency.get_ft_contract_id(),
+            Some(account("usdc.near"))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn stranger_cannot_update_a_listing() {
+        let mut market = setup_market();
+        insert_listing(&mut market, &listing("seller.near"));
+        update(&mut market, "stranger.near", 2 * ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Cannot modify listing while offer is being processed"
+    )]
+    fn listing_with_an_offer_cannot_be_updated() {
+        let mut market = setup_market();
+        let mut listing = listing("seller.near");
+        listing.current_offer = Some(offer("buyer.near", "buyer.near", None));
+        insert_listing(&mut market, &listing);
+        update(&mut market, "seller.near", 2 * ONE_NEAR);
+    }
+
+    /// Re-approves token `1` for a listing of `seller.near`, who has deposited
+    /// storage for one more listing.
+    fn reapprove(market: &mut Market) {
+        let seller_id = account("seller.near");
+        let deposit = market.storage_deposit_by(&seller_id)
+            + market.listing_storage_deposit;
+        market
+            .storage_deposits_by_account
+            .insert(&seller_id, &deposit);
+        testing_env!(context("nft.near").build());
+        market.nft_on_approve(
+            "1".to_string(),
+            seller_id,
+            1,
+            r#"{"price":"2"}"#.to_string(),
+        );
+    }
+
+    #[test]
+    fn reapproval_replaces_the_listing() {
+        let mut market = setup_market();
+        let token_key = insert_listing(&mut market, &listing("seller.near"));
+        reapprove(&mut market);
+        let listing = market.listings.get(&token_key).unwrap();
+        assert_eq!(listing.nft_approval_id, 1);
+        assert_eq!(listing.price, 2);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Cannot modify listing while offer is being processed"
+    )]
+    fn listing_with_an_offer_cannot_be_replaced() {
+        let mut market = setup_market();
+        let mut listing = listing("seller.near");
+        listing.current_offer = Some(offer("buyer.near", "buyer.near", None));
+        insert_listing(&mut market, &listing);
+        reapprove(&mut market);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Cannot modify listing while offer is being processed"
+    )]
+    fn listing_of_a_signed_order_cannot_be_replaced() {
+        let mut market = setup_market();
+        let mut listing = listing("seller.near");
+        listing.order_nonce = Some(7);
+        insert_listing(&mut market, &listing);
+        reapprove(&mut market);
+    }
+
+    #[test]
+    #[should_panic(expected = "Listing not found")]
+    fn missing_listing_cannot_be_updated() {
+        let mut market = setup_market();
+        update(&mut market, "seller.near", 2 * ONE_NEAR);
+    }
+}
@@ -0,0 +1,244 @@
+//! This module upgrades the state of a market that has been deployed before
+//! signed orders, sale records, rentals, pausing, trait offers, token gates
+//! and price bounds were introduced.
+//!
+//! `migrate` has to be called right after deploying the new code, ideally in
+//! the same batch transaction. It reads the market with its previous layout,
+//! initializes all new fields the same way `init` does, and converts every
+//! stored listing to the current layout. Offers that are still being
+//! processed are kept and deliver the NFT to the offerer, as they did before.
+
+use mb_sdk::near_sdk::{
+    self,
+    borsh::{
+        self,
+        BorshDeserialize,
+        BorshSerialize,
+    },
+    collections::{
+        LookupMap,
+        LookupSet,
+        UnorderedMap,
+        UnorderedSet,
+        Vector,
+    },
+    env,
+    AccountId,
+    Balance,
+    Timestamp,
+};
+
+use crate::{
+    data::*,
+    Market,
+    MarketExt,
+};
+
+/// Storage of the market contract before the upgrade
+#[derive(BorshDeserialize, BorshSerialize)]
+struct MarketV1 {
+    listings: UnorderedMap<String, ListingV1>,
+    banned_accounts: UnorderedSet<AccountId>,
+    referrers: UnorderedMap<AccountId, u16>,
+    storage_deposits_by_account: UnorderedMap<AccountId, Balance>,
+    listings_count_by_account: UnorderedMap<AccountId, u64>,
+    listing_storage_deposit: Balance,
+    listing_lock_seconds: u64,
+    genadrop_cut: u16,
+    fallback_cut: u16,
+    owner: AccountId,
+}
+
+/// A listing as it has been stored before the upgrade
+#[derive(BorshDeserialize, BorshSerialize)]
+struct ListingV1 {
+    nft_token_id: String,
+    nft_approval_id: u64,
+    nft_owner_id: AccountId,
+    nft_contract_id: AccountId,
+    price: Balance,
+    currency: Currency,
+    created_at: Timestamp,
+    current_offer: Option<OfferV1>,
+}
+
+/// An offer as it has been stored before the upgrade
+#[derive(BorshDeserialize, BorshSerialize)]
+struct OfferV1 {
+    offerer_id: AccountId,
+    amount: Balance,
+    referrer_id: Option<AccountId>,
+    referral_cut: Option<u16>,
+}
+
+impl From<ListingV1> for Listing {
+    fn from(listing: ListingV1) -> Listing {
+        Listing {
+            nft_token_id: listing.nft_token_id,
+            nft_approval_id: listing.nft_approval_id,
+            nft_owner_id: listing.nft_owner_id,
+            nft_contract_id: listing.nft_contract_id,
+            price: listing.price,
+            currency: listing.currency,
+            created_at: listing.created_at,
+            current_offer: listing.current_offer.map(Into::into),
+            order_nonce: None,
+            trait_offer_id: None,
+            gate: None,
+        }
+    }
+}
+
+impl From<OfferV1> for Offer {
+    fn from(offer: OfferV1) -> Offer {
+        Offer {
+            receiver_id: offer.offerer_id.clone(),
+            offerer_id: offer.offerer_id,
+            amount: offer.amount,
+            referrer_id: offer.referrer_id,
+            referral_cut: offer.referral_cut,
+        }
+    }
+}
+
+#[near_sdk::near_bindgen]
+impl Market {
+    /// Upgrades the state of the market to the current layout. Can only be
+    /// called by the market account itself.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let mut old: MarketV1 =
+            env::state_read().unwrap_or_else(|| env::panic_str("No state"));
+
+        // Listings are read with the previous layout and written back with
+        // the current one. Both maps share the same storage prefix, so the
+        // old map has to be cleared before the new one is written.
+        let old_listings: Vec<(String, ListingV1)> =
+            old.listings.iter().collect();
+        old.listings.clear();
+        let mut listings: UnorderedMap<String, Listing> =
+            UnorderedMap::new(&b"k2l"[..]);
+        for (token_key, listing) in old_listings {
+            listings.insert(&token_key, &listing.into());
+        }
+
+        Self {
+            listings,
+            banned_accounts: old.banned_accounts,
+            referrers: old.referrers,
+            storage_deposits_by_account: old.storage_deposits_by_account,
+            listings_count_by_account: old.listings_count_by_account,
+            gated_listings_count_by_account: LookupMap::new(&b"a2g"[..]),
+            listing_storage_deposit: old.listing_storage_deposit,
+            listing_lock_seconds: old.listing_lock_seconds,
+            genadrop_cut: old.genadrop_cut,
+            fallback_cut: old.fallback_cut,
+            owner: old.owner,
+            order_keys: UnorderedMap::new(&b"ok"[..]),
+            used_order_nonces: LookupSet::new(&b"on"[..]),
+            sales_by_token: LookupMap::new(&b"k2s"[..]),
+            collection_stats: UnorderedMap::new(&b"c2s"[..]),
+            wash_trade_cooldown_seconds: WASH_TRADE_COOLDOWN_SECONDS,
+            rentals: UnorderedMap::new(&b"k2r"[..]),
+            claimed_rentals: LookupMap::new(&b"k2c"[..]),
+            paused: PauseFlags::default(),
+            guardians: UnorderedSet::new(&b"g"[..]),
+            trait_offers: UnorderedMap::new(&b"to"[..]),
+            next_trait_offer_id: 0,
+            price_bounds: UnorderedMap::new(&b"pb"[..]),
+            config_changes: Vector::new(&b"cc"[..]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mb_sdk::near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn listing_v1(token_id: &str, current_offer: Option<OfferV1>) -> ListingV1 {
+        ListingV1 {
+            nft_token_id: token_id.to_string(),
+            nft_approval_id: 3,
+            nft_owner_id: account("seller.near"),
+            nft_contract_id: account("nft.near"),
+            price: ONE_NEAR,
+            currency: Currency::Near,
+            created_at: 500 * SECOND,
+            current_offer,
+        }
+    }
+
+    /// Writes the state of a market deployed before the upgrade, with two
+    /// listings of which one is settling an offer.
+    fn setup_market_v1() {
+        testing_env!(context("market.near").build());
+        let mut listings = UnorderedMap::new(&b"k2l"[..]);
+        let first = listing_v1("1", None);
+        listings.insert(&format!("nft.near<$>{}", first.nft_token_id), &first);
+        let offer = OfferV1 {
+            offerer_id: account("buyer.near"),
+            amount: ONE_NEAR,
+            referrer_id: None,
+            referral_cut: None,
+        };
+        let second = listing_v1("2", Some(offer));
+        listings
+            .insert(&format!("nft.near<$>{}", second.nft_token_id), &second);
+
+        let old = MarketV1 {
+            listings,
+            banned_accounts: UnorderedSet::new(&b"b"[..]),
+            referrers: UnorderedMap::new(&b"r"[..]),
+            storage_deposits_by_account: UnorderedMap::new(&b"a2d"[..]),
+            listings_count_by_account: UnorderedMap::new(&b"a2l"[..]),
+            listing_storage_deposit: 2 * TEN_MILLINEAR,
+            listing_lock_seconds: 60,
+            genadrop_cut: 250,
+            fallback_cut: 300,
+            owner: account("owner.near"),
+        };
+        env::state_write(&old);
+    }
+
+    #[test]
+    fn listings_are_converted() {
+        setup_market_v1();
+        let market = Market::migrate();
+
+        assert_eq!(market.listings.len(), 2);
+        let listing = market.listings.get(&"nft.near<$>1".to_string()).unwrap();
+        assert_eq!(listing.nft_approval_id, 3);
+        assert_eq!(listing.price, ONE_NEAR);
+        assert_eq!(listing.created_at, 500 * SECOND);
+        assert!(listing.current_offer.is_none());
+        assert!(listing.order_nonce.is_none());
+        assert!(listing.gate.is_none());
+
+        // Offers that are being processed deliver the NFT to the offerer
+        let offer = market
+            .listings
+            .get(&"nft.near<$>2".to_string())
+            .unwrap()
+            .current_offer
+            .unwrap();
+        assert_eq!(offer.offerer_id, account("buyer.near"));
+        assert_eq!(offer.receiver_id, account("buyer.near"));
+    }
+
+    #[test]
+    fn settings_are_kept() {
+        setup_market_v1();
+        let market = Market::migrate();
+
+        assert_eq!(market.owner, account("owner.near"));
+        assert_eq!(market.listing_storage_deposit, 2 * TEN_MILLINEAR);
+        assert_eq!(market.listing_lock_seconds, 60);
+        assert_eq!(market.genadrop_cut, 250);
+        assert_eq!(market.fallback_cut, 300);
+        assert!(!market.get_paused().listings);
+    }
+}
@@ -13,10 +13,9 @@
 //! - Calls to `ft_transfer` require one yoctoNEAR to be attached, and that
 //!   yoctoNEAR is neither taken from the lister nor from the offerer. This
 //!   presents an attack vector, where NFTs could be listed for 0 $FT and
-//!   "traded", slowly draining the market of $NEAR. With 10 royalty holders,
-//!   1 $NEAR could be drained by 10e23 trades, making this extremely unlikely.
-//!   The attacker will also have to fund the gas fees for doing so, and has no
-//!   direct economical reward for doing this.
+//!   "traded", slowly draining the market of $NEAR. Zero-value FT listings,
+//!   orders, rentals and transfers are therefore rejected, and the owner can
+//!   configure minimum prices per currency via `set_price_bounds`.
 //! - Removing an offer while it is being processed could lead to an NFT being
 //!   transferred and the payout failing because `Listing::current_offer` is
 //!   `None`, and thus the callback panicking. Thus, `remove_offer` should only
@@ -30,11 +29,14 @@
 //!   existence of a failure receipt for `nft_resolve_payout_{near,ft}` before
 //!   removing offers closes this attack vector.
 
+use std::collections::HashMap;
+
 use mb_sdk::{
     data::store::Payout,
     events::market_v2::{
-        self as events,
         NftFailedSaleData,
+        NftMakeOfferData,
+        NftSaleData,
     },
     interfaces::{
         ext_new_market,
@@ -58,6 +60,7 @@ use mb_sdk::{
 
 use crate::{
     data::*,
+    events,
     Market,
     MarketExt,
 };
@@ -68,7 +71,11 @@ impl Market {
     /// Buying an NFT with native NEAR tokens. The transaction takes place
     /// according to the following rules:
     ///
+    /// - Purchases must not be paused.
     /// - The buyer must not be banned from using the market.
+    /// - If a `receiver_id` is given, the NFT will be transferred to that
+    ///   account instead of the buyer, which must not be banned either. The
+    ///   buyer remains the offerer and will receive any refunds.
     /// - The NFT must be listed for NEAR, not an FT.
     /// - The listing must exist, otherwise the method panics and the buyer is
     ///   automatically refunded.
@@ -76,6 +83,12 @@ impl Market {
     ///   listed for. If it is larger, the whole deposit will be shared between
     ///   royalty holders and the market fee applies to the full deposit.
     /// - There must be no other offer currently executing on this listing.
+    /// - The buyer must not be the seller, the affiliate must be neither of
+    ///   them, and the token must not have changed hands between buyer and
+    ///   seller within the wash-trading cooldown.
+    /// - If the listing is gated, the receiver must hold the required token.
+    ///   This is checked cross-contract before the offer is inserted, see the
+    ///   `gates` module.
     ///
     /// Should all these requirements be fullfilled, the offer will be inserted
     /// into the listing, blocking any other offers from executing on it.
@@ -90,11 +103,16 @@ impl Market {
         token_id: String,
         referrer_id: Option<AccountId>,
         affiliate_id: Option<AccountId>,
+        receiver_id: Option<AccountId>,
     ) -> Promise {
+        self.assert_not_paused(PauseKind::Purchases);
         self.assert_not_banned(&env::predecessor_account_id());
+        let receiver_id =
+            receiver_id.unwrap_or_else(env::predecessor_account_id);
+        self.assert_not_banned(&receiver_id);
 
         let token_key = format!("{}<$>{}", nft_contract_id, token_id);
-        let mut listing = match self.get_listing_internal(&token_key) {
+        let listing = match self.get_listing_internal(&token_key) {
             None => env::panic_str(ERR_LISTING_NOT_FOUND),
             Some(l) => l,
         };
@@ -132,46 +150,47 @@ impl Market {
         // Happy path: insert offer, log event, process stuff
         let offer = Offer {
             offerer_id: env::predecessor_account_id(),
+            receiver_id,
             amount: env::attached_deposit(),
-            referrer_id: referrer_id.clone(),
+            referrer_id,
             referral_cut,
         };
+        self.assert_not_wash_trade(&listing, &offer);
 
-        let (ref_earning, _) = self.get_affiliate_genadrop_amounts(&offer);
-        env::log_str(
-            &events::NftMakeOfferData {
-                nft_contract_id,
-                nft_token_id: token_id,
-                nft_approval_id: listing.nft_approval_id,
-                offer_id: 0,
-                offerer_id: env::predecessor_account_id(),
-                currency: listing.currency.to_string(),
-                price: env::attached_deposit().into(),
-                affiliate_id: referrer_id,
-                affiliate_amount: ref_earning.map(Into::into),
-            }
-            .serialize_event(),
-        );
+        // Gated listings only receive the offer after checking the receiver
+        if listing.gate.is_some() {
+            return self.check_gate(listing, offer);
+        }
+        self.place_offer(listing, offer)
+    }
+
+    /// Inserts an offer into its listing, logs the event, and starts the
+    /// transfer. Any checks must happen prior to calling this.
+    pub(crate) fn place_offer(
+        &mut self,
+        mut listing: Listing,
+        offer: Offer,
+    ) -> Promise {
+        self.log_make_offer(&listing, &offer, 0);
 
+        let (receiver_id, amount) = (offer.receiver_id.clone(), offer.amount);
         listing.current_offer = Some(offer);
-        self.listings.insert(&token_key, &listing);
+        self.listings.insert(&listing.token_key(), &listing);
 
-        self.execute_transfer(
-            listing,
-            env::predecessor_account_id(),
-            env::attached_deposit(),
-        )
+        self.execute_transfer(listing, receiver_id, amount)
     }
 
     /// Helper method to execute transfers for both NEAR or FT. Any checks must
     /// happen prior to calling this.
-    fn execute_transfer(
+    pub(crate) fn execute_transfer(
         &mut self,
         listing: Listing,
         receiver_id: AccountId,
         balance: Balance,
     ) -> Promise {
         let token_key = listing.token_key();
+        let max_len_payout = listing.max_len_payout();
+        let resolve_payout_gas = listing.resolve_payout_gas();
         let offer = listing.current_offer.unwrap();
         let payout_percentage = match offer.referral_cut {
             Some(cut) => 10000 - cut,
@@ -186,20 +205,16 @@ impl Market {
                 listing.nft_token_id,
                 listing.nft_approval_id,
                 (payout_percentage as u128 * balance / 10000).into(),
-                if listing.currency.is_near() {
-                    MAX_LEN_PAYOUT_NEAR
-                } else {
-                    MAX_LEN_PAYOUT_FT
-                },
+                max_len_payout,
             );
 
         let callback = if listing.currency.is_near() {
             ext_new_market::ext(env::current_account_id())
-                .with_static_gas(NFT_RESOLVE_PAYOUT_NEAR_GAS)
+                .with_static_gas(resolve_payout_gas)
                 .nft_resolve_payout_near(token_key)
         } else {
             ext_new_market::ext(env::current_account_id())
-                .with_static_gas(NFT_RESOLVE_PAYOUT_FT_GAS)
+                .with_static_gas(resolve_payout_gas)
                 .nft_resolve_payout_ft(token_key)
         };
 
@@ -226,8 +241,8 @@ impl Market {
         &mut self,
         token_key: String,
     ) -> PromiseOrValue<()> {
-        let listing = self.get_listing_internal(&token_key).unwrap();
-        let offer = listing.current_offer.unwrap();
+        let mut listing = self.get_listing_internal(&token_key).unwrap();
+        let offer = listing.current_offer.take().unwrap();
         let mut payout = match env::promise_result(0) {
             near_sdk::PromiseResult::NotReady => {
                 return PromiseOrValue::Promise(
@@ -272,21 +287,8 @@ impl Market {
             return PromiseOrValue::Value(());
         }
 
-        env::log_str(
-            &events::NftSaleData {
-                nft_contract_id: listing.nft_contract_id.clone(),
-                nft_token_id: listing.nft_token_id.clone(),
-                nft_approval_id: listing.nft_approval_id,
-                accepted_offer_id: 0,
-                payout: payout.clone(),
-                currency: listing.currency.to_string(),
-                price: offer.amount.into(),
-                affiliate_id: offer.referrer_id.clone(),
-                affiliate_amount: ref_earning.map(Into::into),
-                genadrop_amount: mb_earning.into(),
-            }
-            .serialize_event(),
-        );
+        self.log_sale(&listing, &offer, &payout, ref_earning, mb_earning);
+        let storage_fee = self.record_sale(&listing, &offer);
 
         for (account, amount) in payout.drain() {
             Promise::new(account).transfer(amount.0);
@@ -295,7 +297,7 @@ impl Market {
             Promise::new(referrer_id).transfer(ref_earning.unwrap());
         }
         self.listings.remove(&token_key);
-        self.refund_listings(&listing.nft_owner_id, 1, 0);
+        self.refund_listing(&listing, storage_fee, 0);
 
         PromiseOrValue::Value(())
     }
@@ -310,14 +312,21 @@ impl Market {
     ///
     /// - The FT contract must not be banned.
     /// - The NFT must be listed for tokens from the calling FT contract.
+    /// - The NFT is transferred to `msg.receiver_id` if given, otherwise to
+    ///   the `sender_id`. Refunds always go back to the `sender_id`.
     ///
     /// The following chain of cross-contract calls is the same as for the
     /// `buy` call. Due to gas constraints, FT listings are restricted to
-    /// paying out 10 royalty holders.
+    /// paying out 10 royalty holders. Signed orders and gated listings are
+    /// restricted to 7, as they are checked on another contract first.
     ///
     /// In general gas limits require lots of fine tuning, and might differ from
     /// FT contract to FT contract. If using this, make sure to attach the
     /// maximum of your open gas budget.
+    ///
+    /// If `msg` is a `FillOrderWithFtMessage`, a signed order is filled
+    /// instead, see the `orders` module. If `msg` is a `RentWithFtMessage`,
+    /// an escrowed NFT is rented, see the `rentals` module.
     pub fn ft_on_transfer(
         &mut self,
         sender_id: AccountId,
@@ -335,15 +344,36 @@ impl Market {
             };
         }
 
+        self.assert_not_paused(PauseKind::Purchases);
+        // Each FT transfer costs the market one yoctoNEAR
+        if amount.0 == 0 {
+            refund!("Cannot trade for zero FTs, refunding.");
+        }
+
+        // Signed orders are filled using their own message format
+        if let Ok(msg) =
+            near_sdk::serde_json::from_str::<FillOrderWithFtMessage>(&msg)
+        {
+            return self.fill_order_ft(sender_id, amount, msg);
+        }
+        // Rentals are paid using their own message format
+        if let Ok(msg) =
+            near_sdk::serde_json::from_str::<RentWithFtMessage>(&msg)
+        {
+            return self.rent_ft(sender_id, amount, msg);
+        }
+
         let ft_contract_id = env::predecessor_account_id();
         let mut msg: BuyWithFtMessage =
             near_parse(&msg, "Invalid arguments to buy using FT");
 
         self.assert_not_banned(&sender_id);
         self.assert_not_banned(&ft_contract_id);
+        let receiver_id = msg.receiver_id.clone().unwrap_or(sender_id.clone());
+        self.assert_not_banned(&receiver_id);
 
         let token_key = format!("{}<$>{}", msg.nft_contract_id, msg.token_id);
-        let mut listing = match self.get_listing_internal(&token_key) {
+        let listing = match self.get_listing_internal(&token_key) {
             None => env::panic_str(ERR_LISTING_NOT_FOUND),
             Some(l) => l,
         };
@@ -392,34 +422,19 @@ impl Market {
 
         // Happy path: insert offer, log event, process stuff
         let offer = Offer {
-            offerer_id: sender_id.clone(),
+            offerer_id: sender_id,
+            receiver_id,
             amount: amount.0,
-            referrer_id: msg.referrer_id.clone(),
+            referrer_id: msg.referrer_id,
             referral_cut,
         };
+        self.assert_not_wash_trade(&listing, &offer);
 
-        let (ref_earning, _) = self.get_affiliate_genadrop_amounts(&offer);
-        env::log_str(
-            &events::NftMakeOfferData {
-                nft_contract_id: msg.nft_contract_id,
-                nft_token_id: msg.token_id,
-                nft_approval_id: listing.nft_approval_id,
-                offer_id: 0,
-                offerer_id: sender_id.clone(),
-                currency: listing.currency.to_string(),
-                price: amount,
-                affiliate_id: msg.referrer_id,
-                affiliate_amount: ref_earning.map(Into::into),
-            }
-            .serialize_event(),
-        );
-
-        listing.current_offer = Some(offer);
-        self.listings.insert(&token_key, &listing);
-
-        PromiseOrValue::Promise(
-            self.execute_transfer(listing, sender_id, amount.0),
-        )
+        // Gated listings only receive the offer after checking the receiver
+        if listing.gate.is_some() {
+            return PromiseOrValue::Promise(self.check_gate(listing, offer));
+        }
+        PromiseOrValue::Promise(self.place_offer(listing, offer))
     }
 
     /// Payout resolution similar to `resolve_payout_near`, but with FT payouts
@@ -429,8 +444,8 @@ impl Market {
         &mut self,
         token_key: String,
     ) -> PromiseOrValue<U128> {
-        let listing = self.get_listing_internal(&token_key).unwrap();
-        let offer = listing.current_offer.unwrap();
+        let mut listing = self.get_listing_internal(&token_key).unwrap();
+        let offer = listing.current_offer.take().unwrap();
         let ft_contract_id = listing.currency.get_ft_contract_id().unwrap();
         let mut payout = match env::promise_result(0) {
             near_sdk::PromiseResult::NotReady => {
@@ -465,26 +480,13 @@ impl Market {
             return PromiseOrValue::Value(offer.amount.into());
         }
         // Given payout length is too large
-        if payout.len() as u32 > MAX_LEN_PAYOUT_FT {
+        if payout.len() as u32 > listing.max_len_payout() {
             self.fail_listing(&token_key, true);
             return PromiseOrValue::Value(offer.amount.into());
         }
 
-        env::log_str(
-            &events::NftSaleData {
-                nft_contract_id: listing.nft_contract_id.clone(),
-                nft_token_id: listing.nft_token_id.clone(),
-                nft_approval_id: listing.nft_approval_id,
-                accepted_offer_id: 0,
-                payout: payout.clone(),
-                currency: listing.currency.to_string(),
-                price: offer.amount.into(),
-                affiliate_id: offer.referrer_id.clone(),
-                affiliate_amount: ref_earning.map(Into::into),
-                genadrop_amount: mb_earning.into(),
-            }
-            .serialize_event(),
-        );
+        self.log_sale(&listing, &offer, &payout, ref_earning, mb_earning);
+        let storage_fee = self.record_sale(&listing, &offer);
 
         let payout_len = payout.len();
         for (account, amount) in payout.drain() {
@@ -494,7 +496,7 @@ impl Market {
             ft_transfer(ft_contract_id, referrer_id, ref_earning.unwrap());
         }
         self.listings.remove(&token_key);
-        self.refund_listings(&listing.nft_owner_id, 1, payout_len as u128 + 1);
+        self.refund_listing(&listing, storage_fee, payout_len as u128 + 1);
 
         PromiseOrValue::Value(0.into())
     }
@@ -502,7 +504,7 @@ impl Market {
     // ---------------------------- offers (common) ----------------------------
     /// Calculate the amount that should be transferred to the affiliate and
     /// retained by the market, based on an offer.
-    fn get_affiliate_genadrop_amounts(
+    pub(crate) fn get_affiliate_genadrop_amounts(
         &self,
         offer: &Offer,
     ) -> (Option<Balance>, Balance) {
@@ -518,22 +520,96 @@ impl Market {
         }
     }
 
+    /// Logs the `market_v2` event for an offer, followed by the event that
+    /// carries the account that will receive the NFT.
+    pub(crate) fn log_make_offer(
+        &self,
+        listing: &Listing,
+        offer: &Offer,
+        offer_id: u64,
+    ) {
+        let (ref_earning, _) = self.get_affiliate_genadrop_amounts(offer);
+        env::log_str(
+            &NftMakeOfferData {
+                nft_contract_id: listing.nft_contract_id.clone(),
+                nft_token_id: listing.nft_token_id.clone(),
+                nft_approval_id: listing.nft_approval_id,
+                offer_id,
+                offerer_id: offer.offerer_id.clone(),
+                currency: listing.currency.to_string(),
+                price: offer.amount.into(),
+                affiliate_id: offer.referrer_id.clone(),
+                affiliate_amount: ref_earning.map(Into::into),
+            }
+            .serialize_event(),
+        );
+        env::log_str(
+            &events::NftOfferReceiverData {
+                nft_contract_id: listing.nft_contract_id.clone(),
+                nft_token_id: listing.nft_token_id.clone(),
+                offer_id,
+                offerer_id: offer.offerer_id.clone(),
+                receiver_id: offer.receiver_id.clone(),
+            }
+            .serialize_event(),
+        );
+    }
+
+    /// Logs the `market_v2` event for a settled sale, followed by the event
+    /// that carries the account that received the NFT and whether the sale
+    /// looks like a wash trade.
+    pub(crate) fn log_sale(
+        &self,
+        listing: &Listing,
+        offer: &Offer,
+        payout: &HashMap<AccountId, U128>,
+        ref_earning: Option<Balance>,
+        mb_earning: Balance,
+    ) {
+        env::log_str(
+            &NftSaleData {
+                nft_contract_id: listing.nft_contract_id.clone(),
+                nft_token_id: listing.nft_token_id.clone(),
+                nft_approval_id: listing.nft_approval_id,
+                accepted_offer_id: 0,
+                payout: payout.clone(),
+                currency: listing.currency.to_string(),
+                price: offer.amount.into(),
+                affiliate_id: offer.referrer_id.clone(),
+                affiliate_amount: ref_earning.map(Into::into),
+                genadrop_amount: mb_earning.into(),
+            }
+            .serialize_event(),
+        );
+        env::log_str(
+            &events::NftSaleReceiverData {
+                nft_contract_id: listing.nft_contract_id.clone(),
+                nft_token_id: listing.nft_token_id.clone(),
+                accepted_offer_id: 0,
+                offerer_id: offer.offerer_id.clone(),
+                receiver_id: offer.receiver_id.clone(),
+                suspicious: self.is_repeat_trade(listing, offer),
+            }
+            .serialize_event(),
+        );
+    }
+
     /// Removes a listing, refunds the storage deposit to the lister, and bans
     /// the NFT contract from using the market. This does explicitly NOT refund
     /// the offer amount, as the mechanism for differs between payments with
     /// FTs and payments with NEAR.
-    fn fail_listing(&mut self, token_key: &String, ban: bool) {
+    pub(crate) fn fail_listing(&mut self, token_key: &String, ban: bool) {
         let listing = self.listings.remove(token_key).unwrap();
         env::log_str(
             &NftFailedSaleData {
                 nft_contract_id: listing.nft_contract_id.clone(),
-                nft_token_id: listing.nft_token_id,
+                nft_token_id: listing.nft_token_id.clone(),
                 nft_approval_id: listing.nft_approval_id,
                 offer_id: 0,
             }
             .serialize_event(),
         );
-        self.refund_listings(&listing.nft_owner_id, 1, 0);
+        self.refund_listing(&listing, 0, 0);
         if ban {
             self.banned_accounts.insert(&listing.nft_contract_id);
         }
@@ -548,6 +624,12 @@ impl Market {
     /// an offer "is stuck" and the listing can no longer be processed. Make
     /// sure that the XCC originating from the call to `buy` or
     /// `ft_transfer_call` that created the offer has terminated in a failure.
+    ///
+    /// Listings that have been created from a signed order or a trait offer
+    /// are removed altogether, as they only exist for the duration of the
+    /// settlement. Trait offers become available for filling and
+    /// cancellation again.
+    /// The removed offer is recorded in the configuration audit log.
     #[payable]
     pub fn remove_offer(
         &mut self,
@@ -567,8 +649,112 @@ impl Market {
             "Listing does not have an offer"
         );
 
-        // remove offer and store
-        listing.current_offer = None;
+        // record removed offer in the audit log
+        let offer = listing.current_offer.take().unwrap();
+        self.record_config_change(
+            "offer",
+            Some(token_key.clone()),
+            near_sdk::serde_json::to_string(&OfferJson::from(offer)).ok(),
+            None,
+        );
+
+        // orders and trait offers are never listed, drop them entirely
+        if listing.is_transient() {
+            self.listings.remove(&token_key);
+            if let Some(offer_id) = listing.trait_offer_id {
+                self.release_trait_offer(offer_id);
+            }
+            return;
+        }
+
+        // store listing without the offer
         self.listings.insert(&token_key, &listing);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mb_sdk::near_sdk::{
+        test_utils::get_logs,
+        testing_env,
+    };
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn buy(market: &mut Market, receiver_id: Option<&str>) -> Offer {
+        let token_key = insert_listing(market, &listing("seller.near"));
+        testing_env!(context("buyer.near").attached_deposit(ONE_NEAR).build());
+        market.buy(
+            account("nft.near"),
+            "1".to_string(),
+            None,
+            None,
+            receiver_id.map(account),
+        );
+        market
+            .listings
+            .get(&token_key)
+            .unwrap()
+            .current_offer
+            .unwrap()
+    }
+
+    #[test]
+    fn buyer_receives_the_nft_by_default() {
+        let mut market = setup_market();
+        let offer = buy(&mut market, None);
+        assert_eq!(offer.offerer_id, account("buyer.near"));
+        assert_eq!(offer.receiver_id, account("buyer.near"));
+    }
+
+    #[test]
+    fn buy_on_behalf_of_a_receiver() {
+        let mut market = setup_market();
+        let offer = buy(&mut market, Some("friend.near"));
+        assert_eq!(offer.offerer_id, account("buyer.near"));
+        assert_eq!(offer.receiver_id, account("friend.near"));
+        let logs = get_logs();
+        assert!(logs[0]
+            .contains(r#""event":"nft_make_offer","standard":"mb_market""#));
+        assert!(logs[1].contains(r#""event":"nft_offer_receiver""#));
+        assert!(logs[1].contains(r#""receiver_id":"friend.near""#));
+    }
+
+    #[test]
+    #[should_panic(expected = "friend.near is banned from the market")]
+    fn cannot_buy_for_a_banned_receiver() {
+        let mut market = setup_market();
+        market.banned_accounts.insert(&account("friend.near"));
+        buy(&mut market, Some("friend.near"));
+    }
+
+    #[test]
+    fn buy_with_ft_on_behalf_of_a_receiver() {
+        let mut market = setup_market();
+        let token_key = insert_listing(
+            &mut market,
+            &Listing {
+                currency: Currency::FtContract(account("usdc.near")),
+                ..listing("seller.near")
+            },
+        );
+
+        testing_env!(context("usdc.near").build());
+        market.ft_on_transfer(
+            account("buyer.near"),
+            U128(ONE_NEAR),
+            r#"{"nft_contract_id":"nft.near","token_id":"1","receiver_id":"friend.near"}"#
+                .to_string(),
+        );
+
+        let offer = market
+            .listings
+            .get(&token_key)
+            .unwrap()
+            .current_offer
+            .unwrap();
+        assert_eq!(offer.offerer_id, account("buyer.near"));
+        assert_eq!(offer.receiver_id, account("friend.near"));
+    }
+}
@@ -0,0 +1,817 @@
+//! This module implements settlement of signed off-chain orders. Instead of
+//! listing a token via `nft_on_approve` and paying a storage deposit, a seller
+//! can:
+//!
+//! - Register an ed25519 public key via `register_order_key`
+//! - Approve the market for the token on the NFT contract without a `msg`
+//! - Sign an `Order` with the registered key and hand it to an off-chain order
+//!   book
+//!
+//! A buyer fills the order by calling `fill_order` with NEAR attached, or via
+//! `ft_transfer_call` with a `FillOrderWithFtMessage`. The market verifies the
+//! signature, expiry and nonce, then checks on the NFT contract that the
+//! seller still owns the token and that the signed approval ID is current.
+//! Only then the order is settled through the same `nft_transfer_payout` and
+//! `nft_resolve_payout_{near,ft}` path as regular listings.
+//!
+//! While an order is being settled, it occupies a listing slot for the token,
+//! such that no other offer can execute on it concurrently. Each nonce can be
+//! used only once, and sellers can cancel nonces with `cancel_orders`. The
+//! nonce is only consumed once the check on the NFT contract has passed, and
+//! an order that is cancelled in the meantime is not settled.
+//!
+//! Sellers still need a free storage deposit of `listing_storage_deposit`
+//! when an order is filled, from which the storage of the consumed nonce and
+//! the sale record is paid.
+
+use mb_sdk::{
+    near_assert,
+    near_sdk::{
+        self,
+        borsh::BorshSerialize,
+        env,
+        json_types::{
+            Base64VecU8,
+            U128,
+            U64,
+        },
+        AccountId,
+        Balance,
+        CurveType,
+        Promise,
+        PromiseOrValue,
+        PromiseResult,
+        PublicKey,
+    },
+};
+
+use crate::{
+    data::*,
+    ext::{
+        ext_nft_view,
+        NftToken,
+    },
+    Market,
+    MarketExt,
+};
+
+#[near_sdk::near_bindgen]
+impl Market {
+    // ------------------------- order key registry ----------------------------
+    /// Register the ed25519 public key with which the caller signs off-chain
+    /// orders. This replaces a previously registered key, invalidating all
+    /// orders signed with it. The attached deposit must cover the required
+    /// storage, any excess is refunded.
+    #[payable]
+    pub fn register_order_key(&mut self, public_key: PublicKey) {
+        let seller_id = env::predecessor_account_id();
+        self.assert_not_banned(&seller_id);
+        near_assert!(
+            public_key.curve_type() == CurveType::ED25519,
+            "Orders can only be signed with ed25519 keys"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        self.order_keys.insert(&seller_id, &public_key);
+        self.charge_storage(initial_storage_usage);
+    }
+
+    /// Remove the caller's order key, invalidating all orders signed with it.
+    #[payable]
+    pub fn remove_order_key(&mut self) {
+        near_sdk::assert_one_yocto();
+        self.order_keys.remove(&env::predecessor_account_id());
+    }
+
+    /// Show the key with which a seller signs orders.
+    pub fn get_order_key(&self, seller_id: AccountId) -> Option<PublicKey> {
+        self.order_keys.get(&seller_id)
+    }
+
+    /// Cancel signed orders by their nonces. The attached deposit must cover
+    /// the required storage, any excess is refunded.
+    #[payable]
+    pub fn cancel_orders(&mut self, nonces: Vec<U64>) {
+        let seller_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        for nonce in nonces {
+            self.used_order_nonces
+                .insert(&order_nonce_key(&seller_id, nonce.0));
+        }
+        self.charge_storage(initial_storage_usage);
+    }
+
+    /// Show whether an order nonce has already been filled or cancelled.
+    pub fn is_order_nonce_used(
+        &self,
+        seller_id: AccountId,
+        nonce: U64,
+    ) -> bool {
+        self.used_order_nonces
+            .contains(&order_nonce_key(&seller_id, nonce.0))
+    }
+
+    // ---------------------------- filling orders -----------------------------
+    /// Fill a signed order with native NEAR tokens. The same rules as for
+    /// `buy` apply, and additionally:
+    ///
+    /// - The signature must have been created with the seller's registered
+    ///   order key, and the order must be meant for this market.
+    /// - The order must not be expired, and its nonce must not have been used.
+    /// - The token must not be listed on the market at the same time.
+    ///
+    /// Before the transfer, `nft_token` is queried on the NFT contract to
+    /// check that the seller still owns the token and the market is approved
+    /// with the signed approval ID. Should that fail, the buyer is refunded.
+    #[payable]
+    pub fn fill_order(
+        &mut self,
+        order: Order,
+        signature: Base64VecU8,
+        affiliate_id: Option<AccountId>,
+        receiver_id: Option<AccountId>,
+    ) -> Promise {
+        self.assert_not_paused(PauseKind::Purchases);
+        // Order must be for NEAR
+        if let Some(ft_contract) = order.ft_contract.as_ref() {
+            env::panic_str(&format!(
+                "This order is not for NEAR, you must instead use `ft_transfer_call` on `{}`",
+                ft_contract
+            ))
+        }
+        // NEAR amount needs to be at least the order price
+        near_assert!(
+            env::attached_deposit() >= order.price.0,
+            "Deposit needs to be higher than order price"
+        );
+
+        let listing = self.start_order_settlement(
+            order,
+            signature,
+            env::predecessor_account_id(),
+            env::attached_deposit(),
+            affiliate_id,
+            receiver_id,
+        );
+
+        ext_nft_view::ext(listing.nft_contract_id.clone())
+            .with_static_gas(NFT_TOKEN_GAS)
+            .with_unused_gas_weight(0)
+            .nft_token(listing.nft_token_id.clone())
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(NFT_RESOLVE_ORDER_NEAR_GAS)
+                    .nft_resolve_order_near(listing.token_key()),
+            )
+    }
+
+    /// Fill a signed order with FTs. This is called from `ft_on_transfer` if
+    /// the message can be parsed as `FillOrderWithFtMessage`. Any panic here
+    /// results in the FT contract refunding the sender.
+    pub(crate) fn fill_order_ft(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: FillOrderWithFtMessage,
+    ) -> PromiseOrValue<U128> {
+        let ft_contract_id = env::predecessor_account_id();
+        // Order must be for the transferred FT
+        near_assert!(
+            msg.order.ft_contract.as_ref() == Some(&ft_contract_id),
+            "This order cannot be filled with FTs from {}",
+            ft_contract_id
+        );
+        // FT amount needs to be at least the order price
+        near_assert!(
+            amount.0 >= msg.order.price.0,
+            "You have not supplied sufficient funds to fill this order"
+        );
+
+        let listing = self.start_order_settlement(
+            msg.order,
+            msg.signature,
+            sender_id,
+            amount.0,
+            msg.affiliate_id,
+            msg.receiver_id,
+        );
+
+        PromiseOrValue::Promise(
+            ext_nft_view::ext(listing.nft_contract_id.clone())
+                .with_static_gas(NFT_TOKEN_GAS)
+                .with_unused_gas_weight(0)
+                .nft_token(listing.nft_token_id.clone())
+                .then(
+                    Self::ext(env::current_account_id())
+                        .with_static_gas(NFT_RESOLVE_ORDER_FT_GAS)
+                        .nft_resolve_order_ft(listing.token_key()),
+                ),
+        )
+    }
+
+    /// Resolves the ownership and approval check of an order filled with
+    /// NEAR. If the check fails, the buyer is refunded, otherwise the token
+    /// is transferred and paid out as for any other listing.
+    #[private]
+    pub fn nft_resolve_order_near(
+        &mut self,
+        token_key: String,
+    ) -> PromiseOrValue<()> {
+        let listing = self.get_listing_internal(&token_key).unwrap();
+        let offer = listing.current_offer.as_ref().unwrap();
+        let (offerer_id, receiver_id, amount) = (
+            offer.offerer_id.clone(),
+            offer.receiver_id.clone(),
+            offer.amount,
+        );
+
+        if !order_token_is_valid(&listing) || !self.use_order_nonce(&listing) {
+            Promise::new(offerer_id).transfer(amount);
+            self.fail_listing(&token_key, false);
+            return PromiseOrValue::Value(());
+        }
+
+        PromiseOrValue::Promise(self.execute_transfer(
+            listing,
+            receiver_id,
+            amount,
+        ))
+    }
+
+    /// Resolves the ownership and approval check of an order filled with FTs.
+    /// If the check fails, the full amount is returned to the FT contract for
+    /// refunding.
+    #[private]
+    pub fn nft_resolve_order_ft(
+        &mut self,
+        token_key: String,
+    ) -> PromiseOrValue<U128> {
+        let listing = self.get_listing_internal(&token_key).unwrap();
+        let offer = listing.current_offer.as_ref().unwrap();
+        let (receiver_id, amount) = (offer.receiver_id.clone(), offer.amount);
+
+        if !order_token_is_valid(&listing) || !self.use_order_nonce(&listing) {
+            self.fail_listing(&token_key, false);
+            return PromiseOrValue::Value(amount.into());
+        }
+
+        PromiseOrValue::Promise(self.execute_transfer(
+            listing,
+            receiver_id,
+            amount,
+        ))
+    }
+
+    /// Validates a signed order and inserts the listing under which it will be
+    /// settled. Logs the offer event.
+    fn start_order_settlement(
+        &mut self,
+        order: Order,
+        signature: Base64VecU8,
+        offerer_id: AccountId,
+        amount: Balance,
+        affiliate_id: Option<AccountId>,
+        receiver_id: Option<AccountId>,
+    ) -> Listing {
+        let receiver_id = receiver_id.unwrap_or_else(|| offerer_id.clone());
+
+        // No involved party must be banned from using the market
+        self.assert_not_banned(&offerer_id);
+        self.assert_not_banned(&receiver_id);
+        self.assert_not_banned(&order.seller_id);
+        self.assert_not_banned(&order.nft_contract_id);
+        if let Some(ft_contract_id) = order.ft_contract.as_ref() {
+            self.assert_not_banned(ft_contract_id);
+        }
+
+        // Order must be valid and authorized by the seller
+        near_assert!(
+            order.market_id == env::current_account_id(),
+            "Order is meant for {}",
+            order.market_id
+        );
+        near_assert!(
+            env::block_timestamp() < order.expires_at.0,
+            "Order has expired"
+        );
+        near_assert!(
+            order.token_id.len() <= 128,
+            "Cannot process token IDs with more than 128 bytes"
+        );
+        self.assert_valid_order_signature(&order, &signature);
+        near_assert!(order.price.0 > 0, "Orders must have a price");
+        self.assert_valid_price(
+            &Currency::from(order.ft_contract.clone()),
+            order.price.0,
+        );
+
+        // Each nonce may only be used once
+        near_assert!(
+            !self
+                .used_order_nonces
+                .contains(&order_nonce_key(&order.seller_id, order.nonce.0)),
+            "Order has already been filled or cancelled"
+        );
+
+        // The token must not be listed or in settlement already
+        let token_key =
+            format!("{}<$>{}", order.nft_contract_id, order.token_id);
+        near_assert!(
+            self.get_listing_internal(&token_key).is_none(),
+            "Token is listed on the market, buy the listing instead"
+        );
+        // The seller pays for the nonce and the sale record from their
+        // storage deposit
+        near_assert!(
+            self.free_storage_deposit(&order.seller_id)
+                >= self.listing_storage_deposit,
+            "Seller has not deposited storage for the sale record"
+        );
+
+        // Insert default cut for non-whitelisted referrers
+        let referral_cut = affiliate_id.as_ref().map(|account| {
+            self.referrers.get(account).unwrap_or(self.fallback_cut)
+        });
+        let offer = Offer {
+            offerer_id,
+            receiver_id,
+            amount,
+            referrer_id: affiliate_id,
+            referral_cut,
+        };
+
+        let listing = Listing::from_order(&order, offer);
+        self.assert_not_wash_trade(
+            &listing,
+            listing.current_offer.as_ref().unwrap(),
+        );
+        self.log_make_offer(
+            &listing,
+            listing.current_offer.as_ref().unwrap(),
+            0,
+        );
+
+        self.listings.insert(&token_key, &listing);
+        listing
+    }
+
+    /// Consumes the nonce of the order that is settled under the listing, and
+    /// pays for its storage from the seller's storage deposit. Returns false
+    /// if the nonce has been cancelled since the order was filled.
+    fn use_order_nonce(&mut self, listing: &Listing) -> bool {
+        let initial_storage_usage = env::storage_usage();
+        let nonce_key = order_nonce_key(
+            &listing.nft_owner_id,
+            listing.order_nonce.unwrap(),
+        );
+        if !self.used_order_nonces.insert(&nonce_key) {
+            return false;
+        }
+        let used = env::storage_usage().saturating_sub(initial_storage_usage);
+        self.take_storage_deposit(
+            &listing.nft_owner_id,
+            env::storage_byte_cost() * used as Balance,
+        );
+        true
+    }
+
+    /// Panics if the signature has not been created over the order by the
+    /// seller's registered order key.
+    fn assert_valid_order_signature(
+        &self,
+        order: &Order,
+        signature: &Base64VecU8,
+    ) {
+        let public_key = match self.order_keys.get(&order.seller_id) {
+            None => env::panic_str("Seller has not registered an order key"),
+            Some(key) => key,
+        };
+        // The first byte of a `PublicKey` specifies the curve type
+        let public_key: [u8; 32] = public_key.as_bytes()[1..]
+            .try_into()
+            .unwrap_or_else(|_| env::abort());
+        let signature: [u8; 64] =
+            signature.0.as_slice().try_into().unwrap_or_else(|_| {
+                env::panic_str(ERR_INVALID_ORDER_SIGNATURE)
+            });
+        let message = order.try_to_vec().unwrap();
+
+        if !ed25519_verify(&signature, &message, &public_key) {
+            env::panic_str(ERR_INVALID_ORDER_SIGNATURE);
+        }
+    }
+}
+
+/// Verifies an ed25519 signature with the host function of the runtime,
+/// which `near_sdk::env` does not wrap yet.
+#[cfg(target_arch = "wasm32")]
+fn ed25519_verify(
+    signature: &[u8; 64],
+    message: &[u8],
+    public_key: &[u8; 32],
+) -> bool {
+    unsafe {
+        near_sdk::sys::ed25519_verify(
+            signature.len() as u64,
+            signature.as_ptr() as u64,
+            message.len() as u64,
+            message.as_ptr() as u64,
+            public_key.len() as u64,
+            public_key.as_ptr() as u64,
+        ) == 1
+    }
+}
+
+/// The mocked blockchain of unit tests does not implement the host function,
+/// so signatures are verified with `ed25519-dalek` instead.
+#[cfg(not(target_arch = "wasm32"))]
+fn ed25519_verify(
+    signature: &[u8; 64],
+    message: &[u8],
+    public_key: &[u8; 32],
+) -> bool {
+    use ed25519_dalek::{
+        PublicKey,
+        Signature,
+        Verifier,
+    };
+
+    match (
+        PublicKey::from_bytes(public_key),
+        Signature::from_bytes(signature),
+    ) {
+        (Ok(public_key), Ok(signature)) => {
+            public_key.verify(message, &signature).is_ok()
+        }
+        _ => false,
+    }
+}
+
+/// Storage key for used order nonces.
+fn order_nonce_key(seller_id: &AccountId, nonce: u64) -> String {
+    format!("{}<$>{}", seller_id, nonce)
+}
+
+/// Checks the result of the `nft_token` call that precedes settlement of an
+/// order: The seller must still own the token, and the market must be
+/// approved with the approval ID that has been signed.
+fn order_token_is_valid(listing: &Listing) -> bool {
+    let token = match env::promise_result(0) {
+        PromiseResult::Successful(value) => {
+            near_sdk::serde_json::from_slice::<Option<NftToken>>(&value)
+                .ok()
+                .flatten()
+        }
+        _ => None,
+    };
+
+    match token {
+        None => false,
+        Some(token) => {
+            token.owner_id == listing.nft_owner_id
+                && token.approved_account_ids.get(&env::current_account_id())
+                    == Some(&listing.nft_approval_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{
+        Keypair,
+        SecretKey,
+        Signer,
+    };
+    use mb_sdk::near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn keypair(seed: u8) -> Keypair {
+        let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = (&secret).into();
+        Keypair { secret, public }
+    }
+
+    fn order_key(seed: u8) -> PublicKey {
+        let mut key = vec![CurveType::ED25519 as u8];
+        key.extend(keypair(seed).public.to_bytes());
+        key.try_into().unwrap()
+    }
+
+    fn sign(order: &Order, seed: u8) -> Base64VecU8 {
+        let message = order.try_to_vec().unwrap();
+        keypair(seed).sign(&message).to_bytes().to_vec().into()
+    }
+
+    fn order(nonce: u64) -> Order {
+        Order {
+            market_id: account("market.near"),
+            seller_id: account("seller.near"),
+            nft_contract_id: account("nft.near"),
+            token_id: "1".to_string(),
+            approval_id: 0,
+            price: U128(ONE_NEAR),
+            ft_contract: None,
+            expires_at: U64(2_000 * SECOND),
+            nonce: U64(nonce),
+        }
+    }
+
+    fn setup_seller() -> Market {
+        let mut market = setup_market();
+        testing_env!(context("seller.near")
+            .attached_deposit(TEN_MILLINEAR)
+            .build());
+        market.register_order_key(order_key(1));
+        // Storage for the sale record
+        let deposit = market.listing_storage_deposit;
+        testing_env!(context("seller.near").attached_deposit(deposit).build());
+        market.deposit_storage();
+        market
+    }
+
+    fn fill(market: &mut Market, order: Order, signature: Base64VecU8) {
+        testing_env!(context("buyer.near").attached_deposit(ONE_NEAR).build());
+        market.fill_order(order, signature, None, None);
+    }
+
+    /// Resolves the check of the order for token `1`, with the seller still
+    /// owning it if `valid`.
+    fn resolve(market: &mut Market, valid: bool) -> PromiseOrValue<()> {
+        let owner_id = if valid { "seller.near" } else { "buyer.near" };
+        let token = near_sdk::serde_json::json!({
+            "owner_id": owner_id,
+            "approved_account_ids": { "market.near": 0 },
+        });
+        set_promise_result(PromiseResult::Successful(
+            token.to_string().into_bytes(),
+        ));
+        market.nft_resolve_order_near("nft.near<$>1".to_string())
+    }
+
+    #[test]
+    fn fill_signed_order() {
+        let mut market = setup_seller();
+        let order = order(1);
+        fill(&mut market, order.clone(), sign(&order, 1));
+
+        let listing = market
+            .get_listing_internal(&"nft.near<$>1".to_string())
+            .unwrap();
+        assert_eq!(listing.nft_owner_id, account("seller.near"));
+        assert_eq!(listing.order_nonce, Some(1));
+        let offer = listing.current_offer.unwrap();
+        assert_eq!(offer.offerer_id, account("buyer.near"));
+        assert_eq!(offer.amount, ONE_NEAR);
+        // The nonce is consumed once the token has been checked
+        assert!(!market.is_order_nonce_used(account("seller.near"), U64(1)));
+        assert!(matches!(
+            resolve(&mut market, true),
+            PromiseOrValue::Promise(_)
+        ));
+        assert!(market.is_order_nonce_used(account("seller.near"), U64(1)));
+        assert!(!market.is_order_nonce_used(account("seller.near"), U64(2)));
+    }
+
+    #[test]
+    fn failed_check_does_not_consume_the_nonce() {
+        let mut market = setup_seller();
+        let order = order(1);
+        fill(&mut market, order.clone(), sign(&order, 1));
+        assert!(matches!(
+            resolve(&mut market, false),
+            PromiseOrValue::Value(())
+        ));
+        assert!(market
+            .get_listing_internal(&"nft.near<$>1".to_string())
+            .is_none());
+        assert!(!market.is_order_nonce_used(account("seller.near"), U64(1)));
+
+        // The order can be filled again
+        fill(&mut market, order.clone(), sign(&order, 1));
+        assert!(market
+            .get_listing_internal(&"nft.near<$>1".to_string())
+            .is_some());
+    }
+
+    #[test]
+    fn order_cancelled_during_the_check_is_not_settled() {
+        let mut market = setup_seller();
+        let order = order(1);
+        fill(&mut market, order.clone(), sign(&order, 1));
+        testing_env!(context("seller.near")
+            .attached_deposit(TEN_MILLINEAR)
+            .build());
+        market.cancel_orders(vec![U64(1)]);
+
+        assert!(matches!(
+            resolve(&mut market, true),
+            PromiseOrValue::Value(())
+        ));
+        assert!(market
+            .get_listing_internal(&"nft.near<$>1".to_string())
+            .is_none());
+    }
+
+    #[test]
+    fn consumed_nonce_is_charged_to_the_seller() {
+        let mut market = setup_seller();
+        let order = order(1);
+        fill(&mut market, order.clone(), sign(&order, 1));
+        let deposit = market.storage_deposit_by(&account("seller.near"));
+        resolve(&mut market, true);
+        assert!(market.storage_deposit_by(&account("seller.near")) < deposit);
+    }
+
+    #[test]
+    #[should_panic(expected = "Orders must have a price")]
+    fn zero_price_order_is_rejected() {
+        let mut market = setup_seller();
+        let order = Order {
+            price: U128(0),
+            ..order(1)
+        };
+        fill(&mut market, order.clone(), sign(&order, 1));
+    }
+
+    #[test]
+    fn ft_order_fits_into_the_gas_of_ft_on_transfer() {
+        // Parsing the message, verifying the signature and creating the
+        // listing needs to fit into the remainder
+        let nested_gas = NFT_TOKEN_GAS.0 + NFT_RESOLVE_ORDER_FT_GAS.0;
+        assert!(nested_gas + 50_000_000_000_000 <= FT_ON_TRANSFER_GAS.0);
+
+        let listing = Listing {
+            currency: Currency::FtContract(account("usdc.near")),
+            order_nonce: Some(1),
+            ..listing("seller.near")
+        };
+        assert_eq!(listing.max_len_payout(), MAX_LEN_PAYOUT_CHECKED_FT);
+        assert_eq!(
+            NFT_RESOLVE_ORDER_FT_GAS.0,
+            5_000_000_000_000
+                + NFT_TRANSFER_PAYOUT_GAS.0
+                + listing.resolve_payout_gas().0
+        );
+    }
+
+    #[test]
+    fn fill_signed_order_with_ft() {
+        let mut market = setup_seller();
+        let order = Order {
+            ft_contract: Some(account("usdc.near")),
+            ..order(1)
+        };
+        let msg = near_sdk::serde_json::json!({
+            "order": order,
+            "signature": sign(&order, 1),
+        });
+
+        testing_env!(context("usdc.near").build());
+        market.ft_on_transfer(
+            account("buyer.near"),
+            U128(ONE_NEAR),
+            msg.to_string(),
+        );
+
+        let listing = market
+            .get_listing_internal(&"nft.near<$>1".to_string())
+            .unwrap();
+        assert_eq!(
+            listing.currency.get_ft_contract_id(),
+            Some(account("usdc.near"))
+        );
+        assert_eq!(listing.current_offer.unwrap().amount, ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid order signature")]
+    fn tampered_order_is_rejected() {
+        let mut market = setup_seller();
+        let signature = sign(&order(1), 1);
+        let order = Order {
+            price: U128(1),
+            ..order(1)
+        };
+        fill(&mut market, order, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid order signature")]
+    fn order_signed_with_another_key_is_rejected() {
+        let mut market = setup_seller();
+        let order = order(1);
+        fill(&mut market, order.clone(), sign(&order, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Seller has not registered an order key")]
+    fn order_without_key_is_rejected() {
+        let mut market = setup_market();
+        let order = order(1);
+        fill(&mut market, order.clone(), sign(&order, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Order has already been filled or cancelled")]
+    fn nonce_cannot_be_reused() {
+        let mut market = setup_seller();
+        let first = order(1);
+        fill(&mut market, first.clone(), sign(&first, 1));
+        resolve(&mut market, true);
+
+        let second = Order {
+            token_id: "2".to_string(),
+            ..order(1)
+        };
+        fill(&mut market, second.clone(), sign(&second, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Order has already been filled or cancelled")]
+    fn cancelled_order_cannot_be_filled() {
+        let mut market = setup_seller();
+        testing_env!(context("seller.near")
+            .attached_deposit(TEN_MILLINEAR)
+            .build());
+        market.cancel_orders(vec![U64(1)]);
+
+        let order = order(1);
+        fill(&mut market, order.clone(), sign(&order, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Order has expired")]
+    fn expired_order_is_rejected() {
+        let mut market = setup_seller();
+        let order = Order {
+            expires_at: U64(1_000 * SECOND),
+            ..order(1)
+        };
+        fill(&mut market, order.clone(), sign(&order, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Order is meant for other-market.near")]
+    fn order_for_another_market_is_rejected() {
+        let mut market = setup_seller();
+        let order = Order {
+            market_id: account("other-market.near"),
+            ..order(1)
+        };
+        fill(&mut market, order.clone(), sign(&order, 1));
+    }
+
+    #[test]
+    fn filled_order_charges_the_sale_record_to_the_seller() {
+        let mut market = setup_seller();
+        let order = order(1);
+        fill(&mut market, order.clone(), sign(&order, 1));
+        let listing = market
+            .get_listing_internal(&"nft.near<$>1".to_string())
+            .unwrap();
+
+        let deposit = market.storage_deposit_by(&account("seller.near"));
+        market.refund_listing(&listing, 100, 0);
+        assert_eq!(
+            market.storage_deposit_by(&account("seller.near")),
+            deposit - 100
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Seller has not deposited storage for the sale record"
+    )]
+    fn order_requires_storage_for_the_sale_record() {
+        let mut market = setup_market();
+        testing_env!(context("seller.near")
+            .attached_deposit(TEN_MILLINEAR)
+            .build());
+        market.register_order_key(order_key(1));
+        let order = order(1);
+        fill(&mut market, order.clone(), sign(&order, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is listed on the market")]
+    fn listed_token_cannot_be_bought_by_order() {
+        let mut market = setup_seller();
+        insert_listing(&mut market, &listing("seller.near"));
+        let order = order(1);
+        fill(&mut market, order.clone(), sign(&order, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Orders can only be signed with ed25519 keys")]
+    fn order_key_must_be_ed25519() {
+        let mut market = setup_market();
+        let mut key = vec![CurveType::SECP256K1 as u8];
+        key.extend([1; 64]);
+        testing_env!(context("seller.near")
+            .attached_deposit(TEN_MILLINEAR)
+            .build());
+        market.register_order_key(key.try_into().unwrap());
+    }
+}
@@ -0,0 +1,907 @@
+//! This module implements rental listings. Unlike sale listings, which only
+//! require an approval, rented NFTs are held in escrow by the market:
+//!
+//! - The owner calls `nft_transfer_call` on the NFT contract with the market as
+//!   receiver and a `CreateRentalMsg`, which creates the rental listing. Like
+//!   sale listings, each rental requires a listing storage deposit.
+//! - A renter calls `rent` with NEAR attached, or uses `ft_transfer_call` with
+//!   a `RentWithFtMessage`. The payment has to cover the price per day for the
+//!   rented days plus the collateral. The market transfers the NFT to the
+//!   renter, pays the rent (minus the fallback cut) to the owner, and holds
+//!   the collateral.
+//! - The renter returns the NFT by calling `nft_transfer_call` with the market
+//!   as receiver, and is refunded the collateral. The NFT remains escrowed for
+//!   the next renter.
+//! - If the NFT has not been returned when the rent expires, anyone can call
+//!   `claim_rental_collateral`, which forfeits the collateral to the owner and
+//!   ends the rental. The renter keeps the NFT and can no longer return it.
+//!   Rentals therefore always require a collateral. The owner pays for
+//!   remembering the renter until the NFT is escrowed for rent again.
+//!
+//! The owner can withdraw the NFT via `unlist_rental` while it is not rented
+//! out, subject to the same lock period as sale listings. If the transfer
+//! fails, the rental is restored.
+
+use mb_sdk::{
+    near_assert,
+    near_sdk::{
+        self,
+        env,
+        json_types::{
+            U128,
+            U64,
+        },
+        AccountId,
+        Balance,
+        Promise,
+        PromiseOrValue,
+        PromiseResult,
+    },
+    utils::{
+        assert_predecessor,
+        ft_transfer,
+        near_parse,
+    },
+};
+
+use crate::{
+    data::*,
+    events,
+    ext::ext_nft_escrow,
+    Market,
+    MarketExt,
+};
+
+#[near_sdk::near_bindgen]
+impl Market {
+    // --------------------------- escrow and return ---------------------------
+    /// This is called when a token is transferred to this market via
+    /// `nft_transfer_call`. If the token is currently rented out, this is
+    /// treated as the renter returning it. Otherwise, a rental listing is
+    /// created according to the following rules:
+    ///
+    /// - The NFT contract, the token owner and the sender must not be banned.
+    ///   If the NFT is rented for an FT, the FT contract must not be banned.
+    /// - The `token_id` must not be larger than 128 bytes.
+    /// - The owner must have sufficient storage deposits to cover the rental.
+    /// - Listings must not be paused. Returning rented tokens is always
+    ///   possible.
+    /// - Rentals must require a collateral.
+    /// - The price per day must be within the price bounds of the currency.
+    ///
+    /// Renters whose collateral has been claimed cannot transfer the token
+    /// back, as their rental has ended.
+    ///
+    /// Any panic results in the NFT contract returning the token.
+    pub fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: String,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let nft_contract_id = env::predecessor_account_id();
+        let token_key = format!("{}<$>{}", nft_contract_id, token_id);
+
+        if let Some(rental) = self.rentals.get(&token_key) {
+            self.return_rental(rental, previous_owner_id);
+            return PromiseOrValue::Value(false);
+        }
+
+        // Renters that kept the NFT past the rent have forfeited the
+        // collateral, so the rental is over and cannot be returned
+        near_assert!(
+            !matches!(
+                self.claimed_rentals.get(&token_key),
+                Some(claimed) if claimed.renter_id == previous_owner_id
+            ),
+            "The collateral of this rental has been claimed, it cannot be returned"
+        );
+
+        self.assert_not_paused(PauseKind::Listings);
+        let msg: CreateRentalMsg =
+            near_parse(&msg, "Invalid arguments to create rental");
+        let rental =
+            Rental::new(token_id, previous_owner_id, nft_contract_id, msg);
+
+        // No involved party must be banned from using the market
+        self.assert_not_banned(&sender_id);
+        self.assert_not_banned(&rental.owner_id);
+        self.assert_not_banned(&rental.nft_contract_id);
+        if let Currency::FtContract(ft_contract_id) = rental.currency.clone() {
+            self.assert_not_banned(&ft_contract_id)
+        }
+        // Token IDs must not be longer than 128 bytes to guard against the
+        // million cheap data additions attack
+        near_assert!(
+            rental.nft_token_id.len() <= 128,
+            "Cannot process token IDs with more than 128 bytes"
+        );
+        near_assert!(
+            rental.max_days > 0,
+            "Rentals must allow renting for at least one day"
+        );
+        // Without collateral, renters could keep the NFT for the rent alone
+        near_assert!(rental.collateral > 0, "Rentals require a collateral");
+        self.assert_valid_price(&rental.currency, rental.price_per_day);
+        // Owner must have purchased storage for processing
+        near_assert!(
+            self.free_storage_deposit(&rental.owner_id)
+                >= self.listing_storage_deposit,
+            "Storage for rental not covered"
+        );
+
+        // Once escrowed again, the renter of a claimed rental no longer holds
+        // the token, and the owner that paid for the record is refunded
+        self.clear_claimed_rental(&token_key);
+        self.increase_listings_count(&rental.owner_id, 1);
+        self.rentals.insert(&token_key, &rental);
+        log_rental_list(&rental);
+
+        PromiseOrValue::Value(false)
+    }
+
+    /// Allows the owner to withdraw an escrowed NFT that is not currently
+    /// rented out. The storage deposit will be refunded once the NFT has been
+    /// transferred, and the rental is restored if the transfer fails.
+    #[payable]
+    pub fn unlist_rental(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+    ) -> Promise {
+        near_sdk::assert_one_yocto();
+        let token_key = format!("{}<$>{}", nft_contract_id, token_id);
+        let rental = self.get_rental_internal(&token_key);
+
+        assert_predecessor(&rental.owner_id);
+        if rental.current_rent.is_some() {
+            env::panic_str(ERR_RENT_IN_PROGRESS);
+        }
+        let minimum_withdrawal_timestamp =
+            rental.created_at + self.listing_lock_seconds * 1_000_000_000;
+        near_assert!(
+            env::block_timestamp() > minimum_withdrawal_timestamp,
+            "Rental cannot be withdrawn before timestamp {}",
+            minimum_withdrawal_timestamp / 1_000_000_000
+        );
+
+        self.rentals.remove(&token_key);
+        env::log_str(
+            &events::NftRentalUnlistData {
+                nft_contract_id: nft_contract_id.clone(),
+                nft_token_id: token_id.clone(),
+            }
+            .serialize_event(),
+        );
+
+        // The attached yoctoNEAR authorizes the transfer
+        ext_nft_escrow::ext(nft_contract_id)
+            .with_attached_deposit(1)
+            .with_static_gas(NFT_TRANSFER_GAS)
+            .nft_transfer(rental.owner_id.clone(), token_id, None, None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(NFT_RESOLVE_UNLIST_RENTAL_GAS)
+                    .nft_resolve_unlist_rental(rental.into()),
+            )
+    }
+
+    /// Resolves the withdrawal of an escrowed NFT. Refunds the storage deposit
+    /// of the owner on success, and restores the rental otherwise.
+    #[private]
+    pub fn nft_resolve_unlist_rental(&mut self, rental: RentalJson) {
+        let rental: Rental = rental.into();
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            self.refund_listings(&rental.owner_id, 1, 0);
+            return;
+        }
+
+        self.rentals.insert(&rental.token_key(), &rental);
+        log_rental_list(&rental);
+    }
+
+    /// Once a rent has expired without the NFT being returned, the collateral
+    /// is forfeited to the owner and the rental ends. Anyone can call this,
+    /// the collateral always goes to the owner.
+    pub fn claim_rental_collateral(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+    ) {
+        let token_key = format!("{}<$>{}", nft_contract_id, token_id);
+        let rental = self.get_rental_internal(&token_key);
+        let rent = match rental.current_rent.as_ref() {
+            None => env::panic_str("Rental is not rented out"),
+            Some(rent) => rent,
+        };
+        near_assert!(
+            env::block_timestamp() > rent.expires_at,
+            "Rent does not expire before timestamp {}",
+            rent.expires_at / 1_000_000_000
+        );
+
+        self.rentals.remove(&token_key);
+        // The owner pays for remembering the renter, which rejects returns
+        let initial_storage_usage = env::storage_usage();
+        self.claimed_rentals.insert(
+            &token_key,
+            &ClaimedRental {
+                renter_id: rent.renter_id.clone(),
+                owner_id: rental.owner_id.clone(),
+            },
+        );
+        let used = env::storage_usage().saturating_sub(initial_storage_usage);
+        let storage_fee = env::storage_byte_cost() * used as Balance;
+        env::log_str(
+            &events::NftRentalForfeitData {
+                nft_contract_id,
+                nft_token_id: token_id,
+                renter_id: rent.renter_id.clone(),
+                owner_id: rental.owner_id.clone(),
+                collateral: rent.collateral.into(),
+            }
+            .serialize_event(),
+        );
+        if rent.collateral > 0 {
+            transfer_funds(
+                &rental.currency,
+                rental.owner_id.clone(),
+                rent.collateral,
+            );
+        }
+        self.refund_listings(
+            &rental.owner_id,
+            1,
+            storage_fee.min(self.listing_storage_deposit),
+        );
+    }
+
+    /// Handles an escrowed NFT being transferred back to the market. Only the
+    /// renter may return it, and is refunded the collateral.
+    fn return_rental(
+        &mut self,
+        mut rental: Rental,
+        previous_owner_id: AccountId,
+    ) {
+        let rent = match rental.current_rent.take() {
+            None => env::panic_str("Token is already held by the market"),
+            Some(rent) => rent,
+        };
+        near_assert!(
+            previous_owner_id == rent.renter_id,
+            "Only the renter can return a rented token"
+        );
+
+        if rent.collateral > 0 {
+            transfer_funds(
+                &rental.currency,
+                rent.renter_id.clone(),
+                rent.collateral,
+            );
+        }
+        self.rentals.insert(&rental.token_key(), &rental);
+
+        env::log_str(
+            &events::NftRentalReturnData {
+                nft_contract_id: rental.nft_contract_id,
+                nft_token_id: rental.nft_token_id,
+                renter_id: rent.renter_id,
+            }
+            .serialize_event(),
+        );
+    }
+
+    // -------------------------------- renting --------------------------------
+    /// Rent an escrowed NFT with native NEAR tokens for the given number of
+    /// days. The rent takes place according to the following rules:
+    ///
+    /// - Purchases must not be paused.
+    /// - The renter must not be banned and must not be the owner.
+    /// - The NFT must be rented for NEAR, not an FT, and must not currently be
+    ///   rented out.
+    /// - `days` must be between 1 and the maximum duration of the rental.
+    /// - The attached deposit must cover the price per day for all rented
+    ///   days plus the collateral. Any excess is counted as rent.
+    ///
+    /// The NFT is transferred to the renter, and `nft_resolve_rent_near` pays
+    /// the owner, or refunds the renter if the transfer failed.
+    #[payable]
+    pub fn rent(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        days: u64,
+    ) -> Promise {
+        self.assert_not_paused(PauseKind::Purchases);
+        let token_key = format!("{}<$>{}", nft_contract_id, token_id);
+        let rental = self.get_rental_internal(&token_key);
+
+        // NFT must be rented for NEAR
+        if let Currency::FtContract(ft_contract) = &rental.currency {
+            env::panic_str(&format!(
+                "This NFT is not rented for NEAR, you must instead use `ft_transfer_call` on `{}`",
+                ft_contract
+            ))
+        }
+
+        let rental = self.start_rent(
+            rental,
+            env::predecessor_account_id(),
+            env::attached_deposit(),
+            days,
+        );
+        self.transfer_to_renter(rental).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(NFT_RESOLVE_RENT_GAS)
+                .nft_resolve_rent_near(token_key),
+        )
+    }
+
+    /// Rent an escrowed NFT with FTs. This is called from `ft_on_transfer` if
+    /// the message can be parsed as `RentWithFtMessage`, and follows the same
+    /// rules as `rent`.
+    pub(crate) fn rent_ft(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: RentWithFtMessage,
+    ) -> PromiseOrValue<U128> {
+        let ft_contract_id = env::predecessor_account_id();
+        self.assert_not_banned(&ft_contract_id);
+
+        let token_key = format!("{}<$>{}", msg.nft_contract_id, msg.token_id);
+        let rental = self.get_rental_internal(&token_key);
+        // NFT needs to be rented for the transferred FT
+        near_assert!(
+            rental.currency.get_ft_contract_id().as_ref()
+                == Some(&ft_contract_id),
+            "This NFT cannot be rented with FTs from {}",
+            ft_contract_id
+        );
+
+        let rental = self.start_rent(rental, sender_id, amount.0, msg.days);
+        PromiseOrValue::Promise(
+            self.transfer_to_renter(rental).then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(NFT_RESOLVE_RENT_GAS)
+                    .nft_resolve_rent_ft(token_key),
+            ),
+        )
+    }
+
+    /// Resolves the transfer of a rented NFT paid for with NEAR. Pays the owner
+    /// on success, and refunds the renter otherwise.
+    #[private]
+    pub fn nft_resolve_rent_near(&mut self, token_key: String) {
+        if let Some((renter_id, refund)) = self.resolve_rent(&token_key) {
+            Promise::new(renter_id).transfer(refund);
+        }
+    }
+
+    /// Resolves the transfer of a rented NFT paid for with FTs. Pays the owner
+    /// on success, and returns the full amount to the FT contract for refunding
+    /// otherwise.
+    #[private]
+    pub fn nft_resolve_rent_ft(&mut self, token_key: String) -> U128 {
+        match self.resolve_rent(&token_key) {
+            Some((_, refund)) => refund.into(),
+            None => 0.into(),
+        }
+    }
+
+    /// Validates a rent request and stores it in the rental.
+    fn start_rent(
+        &mut self,
+        mut rental: Rental,
+        renter_id: AccountId,
+        amount: Balance,
+        days: u64,
+    ) -> Rental {
+        self.assert_not_banned(&renter_id);
+        near_assert!(
+            renter_id != rental.owner_id,
+            "Cannot rent your own token"
+        );
+        // There must be no other rent in progress right now
+        if rental.current_rent.is_some() {
+            env::panic_str(ERR_RENT_IN_PROGRESS);
+        }
+        near_assert!(
+            days > 0 && days <= rental.max_days,
+            "Token can be rented for 1 to {} days",
+            rental.max_days
+        );
+        // Amount needs to cover rent and collateral
+        let required =
+            rental.price_per_day * days as Balance + rental.collateral;
+        near_assert!(
+            amount >= required,
+            "Renting for {} days requires {}",
+            days,
+            required
+        );
+
+        rental.current_rent = Some(Rent {
+            renter_id,
+            payment: amount - rental.collateral,
+            collateral: rental.collateral,
+            expires_at: env::block_timestamp() + days * NANOS_PER_DAY,
+        });
+        self.rentals.insert(&rental.token_key(), &rental);
+        rental
+    }
+
+    /// Transfers an escrowed NFT to the renter of the current rent.
+    fn transfer_to_renter(&self, rental: Rental) -> Promise {
+        let renter_id = rental.current_rent.unwrap().renter_id;
+        ext_nft_escrow::ext(rental.nft_contract_id)
+            .with_attached_deposit(1)
+            .with_static_gas(NFT_TRANSFER_GAS)
+            .nft_transfer(renter_id, rental.nft_token_id, None, None)
+    }
+
+    /// Processes the result of transferring an NFT to a renter. On success,
+    /// the owner is paid the rent minus the fallback cut. On failure, the rent
+    /// is removed and the renter and amount to refund are returned.
+    fn resolve_rent(
+        &mut self,
+        token_key: &String,
+    ) -> Option<(AccountId, Balance)> {
+        let mut rental = self.get_rental_internal(token_key);
+
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            let rent = rental.current_rent.as_ref().unwrap();
+            let market_amount =
+                self.fallback_cut as u128 * rent.payment / 10_000;
+            transfer_funds(
+                &rental.currency,
+                rental.owner_id.clone(),
+                rent.payment - market_amount,
+            );
+            env::log_str(
+                &events::NftRentData {
+                    nft_contract_id: rental.nft_contract_id.clone(),
+                    nft_token_id: rental.nft_token_id.clone(),
+                    renter_id: rent.renter_id.clone(),
+                    currency: rental.currency.to_string(),
+                    payment: rent.payment.into(),
+                    collateral: rent.collateral.into(),
+                    expires_at: rent.expires_at.into(),
+                }
+                .serialize_event(),
+            );
+            return None;
+        }
+
+        let rent = rental.current_rent.take().unwrap();
+        self.rentals.insert(token_key, &rental);
+        Some((rent.renter_id, rent.payment + rent.collateral))
+    }
+
+    // --------------------------------- views ---------------------------------
+    /// Show a rental.
+    pub fn get_rental(
+        &self,
+        nft_contract_id: AccountId,
+        token_id: String,
+    ) -> Option<RentalJson> {
+        self.rentals
+            .get(&format!("{}<$>{}", nft_contract_id, token_id))
+            .map(Into::into)
+    }
+
+    /// Show all rentals with pagination.
+    pub fn get_rentals(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<RentalJson> {
+        self.rentals
+            .values()
+            .skip(from_index.unwrap_or(U64(0)).0 as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(Into::into)
+            .collect()
+    }
+
+    /// Removes the record of a claimed rental and refunds its storage to the
+    /// owner that paid for it.
+    fn clear_claimed_rental(&mut self, token_key: &String) {
+        let initial_storage_usage = env::storage_usage();
+        if let Some(claimed) = self.claimed_rentals.remove(token_key) {
+            let released =
+                initial_storage_usage.saturating_sub(env::storage_usage());
+            let refund = env::storage_byte_cost() * released as Balance;
+            Promise::new(claimed.owner_id)
+                .transfer(refund.min(self.listing_storage_deposit));
+        }
+    }
+
+    fn get_rental_internal(&self, token_key: &String) -> Rental {
+        match self.rentals.get(token_key) {
+            None => env::panic_str(ERR_RENTAL_NOT_FOUND),
+            Some(rental) => rental,
+        }
+    }
+}
+
+/// Emits the event for a listed rental.
+fn log_rental_list(rental: &Rental) {
+    env::log_str(
+        &events::NftRentalListData {
+            nft_contract_id: rental.nft_contract_id.clone(),
+            nft_token_id: rental.nft_token_id.clone(),
+            owner_id: rental.owner_id.clone(),
+            currency: rental.currency.to_string(),
+            price_per_day: rental.price_per_day.into(),
+            max_days: rental.max_days,
+            collateral: rental.collateral.into(),
+        }
+        .serialize_event(),
+    );
+}
+
+/// Transfers NEAR or FTs held by the market.
+fn transfer_funds(
+    currency: &Currency,
+    receiver_id: AccountId,
+    amount: Balance,
+) {
+    match currency {
+        Currency::Near => {
+            Promise::new(receiver_id).transfer(amount);
+        }
+        Currency::FtContract(ft_contract_id) => {
+            ft_transfer(ft_contract_id.clone(), receiver_id, amount);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mb_sdk::near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    const PRICE_PER_DAY: Balance = ONE_NEAR;
+    const COLLATERAL: Balance = 5 * ONE_NEAR;
+
+    fn token_key() -> String {
+        "nft.near<$>1".to_string()
+    }
+
+    /// Escrows token `1` of `nft.near` for rent by `owner.near`.
+    fn setup_rental() -> Market {
+        let mut market = setup_market();
+        testing_env!(context("lender.near")
+            .attached_deposit(TEN_MILLINEAR)
+            .build());
+        market.deposit_storage();
+
+        testing_env!(context("nft.near").build());
+        market.nft_on_transfer(
+            account("lender.near"),
+            account("lender.near"),
+            "1".to_string(),
+            format!(
+                r#"{{"price_per_day":"{}","max_days":3,"collateral":"{}"}}"#,
+                PRICE_PER_DAY, COLLATERAL
+            ),
+        );
+        market
+    }
+
+    fn rent(market: &mut Market, renter_id: &str, days: u64, deposit: Balance) {
+        testing_env!(context(renter_id).attached_deposit(deposit).build());
+        market.rent(account("nft.near"), "1".to_string(), days);
+    }
+
+    #[test]
+    fn escrowed_token_is_listed_for_rent() {
+        let market = setup_rental();
+        let rental = market
+            .get_rental(account("nft.near"), "1".to_string())
+            .unwrap();
+        assert_eq!(rental.owner_id, account("lender.near"));
+        assert_eq!(rental.price_per_day.0, PRICE_PER_DAY);
+        assert_eq!(rental.max_days, 3);
+        assert_eq!(rental.collateral.0, COLLATERAL);
+        assert!(rental.current_rent.is_none());
+        assert_eq!(market.get_listings_count(&account("lender.near")).0, 1);
+        assert_eq!(market.get_rentals(None, None).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Storage for rental not covered")]
+    fn escrow_requires_storage_deposit() {
+        let mut market = setup_market();
+        testing_env!(context("nft.near").build());
+        market.nft_on_transfer(
+            account("lender.near"),
+            account("lender.near"),
+            "1".to_string(),
+            r#"{"price_per_day":"1","max_days":3,"collateral":"1"}"#
+                .to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Rentals require a collateral")]
+    fn rental_requires_a_collateral() {
+        let mut market = setup_market();
+        testing_env!(context("lender.near")
+            .attached_deposit(TEN_MILLINEAR)
+            .build());
+        market.deposit_storage();
+        testing_env!(context("nft.near").build());
+        market.nft_on_transfer(
+            account("lender.near"),
+            account("lender.near"),
+            "1".to_string(),
+            r#"{"price_per_day":"1","max_days":3,"collateral":"0"}"#
+                .to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Price must be at most 100")]
+    fn rental_price_must_be_within_the_price_bounds() {
+        let mut market = setup_market();
+        testing_env!(context("owner.near").attached_deposit(1).build());
+        market.set_price_bounds(None, U128(1), Some(U128(100)));
+        testing_env!(context("lender.near")
+            .attached_deposit(TEN_MILLINEAR)
+            .build());
+        market.deposit_storage();
+        testing_env!(context("nft.near").build());
+        market.nft_on_transfer(
+            account("lender.near"),
+            account("lender.near"),
+            "1".to_string(),
+            r#"{"price_per_day":"101","max_days":3,"collateral":"1"}"#
+                .to_string(),
+        );
+    }
+
+    #[test]
+    fn rent_for_days() {
+        let mut market = setup_rental();
+        rent(
+            &mut market,
+            "renter.near",
+            2,
+            2 * PRICE_PER_DAY + COLLATERAL,
+        );
+
+        let rent = market.rentals.get(&token_key()).unwrap().current_rent;
+        let rent = rent.unwrap();
+        assert_eq!(rent.renter_id, account("renter.near"));
+        assert_eq!(rent.payment, 2 * PRICE_PER_DAY);
+        assert_eq!(rent.collateral, COLLATERAL);
+        assert_eq!(rent.expires_at, 1_000 * SECOND + 2 * NANOS_PER_DAY);
+    }
+
+    #[test]
+    #[should_panic(expected = "Renting for 2 days requires")]
+    fn rent_must_cover_rent_and_collateral() {
+        let mut market = setup_rental();
+        rent(&mut market, "renter.near", 2, 2 * PRICE_PER_DAY);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token can be rented for 1 to 3 days")]
+    fn rent_cannot_exceed_max_days() {
+        let mut market = setup_rental();
+        rent(
+            &mut market,
+            "renter.near",
+            4,
+            4 * PRICE_PER_DAY + COLLATERAL,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot rent your own token")]
+    fn owner_cannot_rent() {
+        let mut market = setup_rental();
+        rent(&mut market, "lender.near", 1, PRICE_PER_DAY + COLLATERAL);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot modify rental while it is rented out")]
+    fn rented_token_cannot_be_rented_again() {
+        let mut market = setup_rental();
+        rent(&mut market, "renter.near", 1, PRICE_PER_DAY + COLLATERAL);
+        rent(&mut market, "other.near", 1, PRICE_PER_DAY + COLLATERAL);
+    }
+
+    #[test]
+    fn failed_transfer_ends_the_rent() {
+        let mut market = setup_rental();
+        rent(&mut market, "renter.near", 1, PRICE_PER_DAY + COLLATERAL);
+
+        set_promise_result(PromiseResult::Failed);
+        market.nft_resolve_rent_near(token_key());
+        let rental = market.rentals.get(&token_key()).unwrap();
+        assert!(rental.current_rent.is_none());
+    }
+
+    #[test]
+    fn renter_returns_the_token() {
+        let mut market = setup_rental();
+        rent(&mut market, "renter.near", 1, PRICE_PER_DAY + COLLATERAL);
+
+        testing_env!(context("nft.near").build());
+        let result = market.nft_on_transfer(
+            account("renter.near"),
+            account("renter.near"),
+            "1".to_string(),
+            String::new(),
+        );
+        assert!(matches!(result, PromiseOrValue::Value(false)));
+        let rental = market.rentals.get(&token_key()).unwrap();
+        assert!(rental.current_rent.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the renter can return a rented token")]
+    fn only_the_renter_returns_the_token() {
+        let mut market = setup_rental();
+        rent(&mut market, "renter.near", 1, PRICE_PER_DAY + COLLATERAL);
+
+        testing_env!(context("nft.near").build());
+        market.nft_on_transfer(
+            account("other.near"),
+            account("other.near"),
+            "1".to_string(),
+            String::new(),
+        );
+    }
+
+    #[test]
+    fn collateral_is_claimed_after_expiry() {
+        let mut market = setup_rental();
+        rent(&mut market, "renter.near", 1, PRICE_PER_DAY + COLLATERAL);
+
+        testing_env!(context("anyone.near")
+            .block_timestamp(1_000 * SECOND + NANOS_PER_DAY + 1)
+            .build());
+        market.claim_rental_collateral(account("nft.near"), "1".to_string());
+        assert!(market
+            .get_rental(account("nft.near"), "1".to_string())
+            .is_none());
+        assert_eq!(market.get_listings_count(&account("lender.near")).0, 0);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The collateral of this rental has been claimed, it cannot be returned"
+    )]
+    fn claimed_rental_cannot_be_returned() {
+        let mut market = setup_rental();
+        rent(&mut market, "renter.near", 1, PRICE_PER_DAY + COLLATERAL);
+        testing_env!(context("anyone.near")
+            .block_timestamp(1_000 * SECOND + NANOS_PER_DAY + 1)
+            .build());
+        market.claim_rental_collateral(account("nft.near"), "1".to_string());
+        let claimed = market.claimed_rentals.get(&token_key()).unwrap();
+        assert_eq!(claimed.renter_id, account("renter.near"));
+        assert_eq!(claimed.owner_id, account("lender.near"));
+
+        testing_env!(context("nft.near").build());
+        market.nft_on_transfer(
+            account("renter.near"),
+            account("renter.near"),
+            "1".to_string(),
+            String::new(),
+        );
+    }
+
+    #[test]
+    fn relisting_clears_the_claimed_rental() {
+        let mut market = setup_rental();
+        rent(&mut market, "renter.near", 1, PRICE_PER_DAY + COLLATERAL);
+        testing_env!(context("anyone.near")
+            .block_timestamp(1_000 * SECOND + NANOS_PER_DAY + 1)
+            .build());
+        market.claim_rental_collateral(account("nft.near"), "1".to_string());
+
+        // The renter sold the token, and its new owner escrows it again
+        testing_env!(context("buyer.near")
+            .attached_deposit(TEN_MILLINEAR)
+            .build());
+        market.deposit_storage();
+        testing_env!(context("nft.near").build());
+        market.nft_on_transfer(
+            account("buyer.near"),
+            account("buyer.near"),
+            "1".to_string(),
+            format!(
+                r#"{{"price_per_day":"{}","max_days":3,"collateral":"{}"}}"#,
+                PRICE_PER_DAY, COLLATERAL
+            ),
+        );
+        assert!(market.claimed_rentals.get(&token_key()).is_none());
+        assert_eq!(
+            market
+                .get_rental(account("nft.near"), "1".to_string())
+                .unwrap()
+                .owner_id,
+            account("buyer.near")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Rent does not expire before timestamp")]
+    fn collateral_cannot_be_claimed_before_expiry() {
+        let mut market = setup_rental();
+        rent(&mut market, "renter.near", 1, PRICE_PER_DAY + COLLATERAL);
+        market.claim_rental_collateral(account("nft.near"), "1".to_string());
+    }
+
+    #[test]
+    fn owner_unlists_rental() {
+        let mut market = setup_rental();
+        testing_env!(context("lender.near")
+            .attached_deposit(1)
+            .block_timestamp(1_001 * SECOND)
+            .build());
+        let rental = market
+            .get_rental(account("nft.near"), "1".to_string())
+            .unwrap();
+        market.unlist_rental(account("nft.near"), "1".to_string());
+        assert!(market
+            .get_rental(account("nft.near"), "1".to_string())
+            .is_none());
+        // The deposit is only refunded once the NFT has been transferred
+        assert_eq!(market.get_listings_count(&account("lender.near")).0, 1);
+
+        set_promise_result(PromiseResult::Successful(vec![]));
+        market.nft_resolve_unlist_rental(rental);
+        assert_eq!(market.get_listings_count(&account("lender.near")).0, 0);
+        assert_eq!(market.get_storage_deposit(&account("lender.near")).0, 0);
+    }
+
+    #[test]
+    fn failed_unlisting_restores_the_rental() {
+        let mut market = setup_rental();
+        testing_env!(context("lender.near")
+            .attached_deposit(1)
+            .block_timestamp(1_001 * SECOND)
+            .build());
+        let rental = market
+            .get_rental(account("nft.near"), "1".to_string())
+            .unwrap();
+        market.unlist_rental(account("nft.near"), "1".to_string());
+
+        set_promise_result(PromiseResult::Failed);
+        market.nft_resolve_unlist_rental(rental);
+        let rental = market
+            .get_rental(account("nft.near"), "1".to_string())
+            .unwrap();
+        assert_eq!(rental.owner_id, account("lender.near"));
+        assert_eq!(rental.price_per_day.0, PRICE_PER_DAY);
+        assert_eq!(rental.collateral.0, COLLATERAL);
+        assert_eq!(rental.currency, "near");
+        assert_eq!(rental.created_at.0, 1_000 * SECOND);
+        assert_eq!(market.get_listings_count(&account("lender.near")).0, 1);
+        assert_eq!(
+            market.get_storage_deposit(&account("lender.near")).0,
+            TEN_MILLINEAR
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot modify rental while it is rented out")]
+    fn rented_token_cannot_be_unlisted() {
+        let mut market = setup_rental();
+        rent(&mut market, "renter.near", 1, PRICE_PER_DAY + COLLATERAL);
+        testing_env!(context("lender.near")
+            .attached_deposit(1)
+            .block_timestamp(1_001 * SECOND)
+            .build());
+        market.unlist_rental(account("nft.near"), "1".to_string());
+    }
+}
@@ -0,0 +1,436 @@
+//! This module keeps an on-chain record of sales, such that price histories
+//! and collection statistics can be queried without an indexer. For each token
+//! the last `SALE_HISTORY_LEN` sales are kept, and each collection aggregates
+//! volume, sale count, last sale price and all-time high per currency.
+//!
+//! Records are only written by `nft_resolve_payout_{near,ft}` after the payout
+//! has been validated, so failed sales never show up here.
+//! Their storage is kept back from the listing deposit of the seller, or taken
+//! from the seller's storage deposit for signed orders. For trait offers, it
+//! is kept back from the storage deposit of the offer.
+//!
+//! The token history also serves as wash-trading protection: Offers are
+//! rejected if the same token has changed hands between the seller and the
+//! buyer within the last `wash_trade_cooldown_seconds`, and sales between
+//! accounts that have already traded the token are flagged as suspicious.
+
+use mb_sdk::{
+    near_assert,
+    near_sdk::{
+        self,
+        env,
+        json_types::U64,
+        AccountId,
+        Balance,
+        Timestamp,
+    },
+};
+
+use crate::{
+    data::*,
+    Market,
+    MarketExt,
+};
+
+#[near_sdk::near_bindgen]
+impl Market {
+    /// Show the most recent sales of a token, newest first.
+    pub fn get_token_sales(
+        &self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<SaleRecordJson> {
+        let token_key = format!("{}<$>{}", nft_contract_id, token_id);
+        self.sales_by_token
+            .get(&token_key)
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+            .skip(from_index.unwrap_or(U64(0)).0 as usize)
+            .take(limit.unwrap_or(SALE_HISTORY_LEN as u64) as usize)
+            .map(Into::into)
+            .collect()
+    }
+
+    /// Show the aggregated sales of a collection.
+    pub fn get_collection_stats(
+        &self,
+        nft_contract_id: AccountId,
+    ) -> Option<CollectionStatsJson> {
+        self.collection_stats
+            .get(&nft_contract_id)
+            .map(|stats| stats.into_json(nft_contract_id))
+    }
+
+    /// Show the aggregated sales of all collections that have been traded on
+    /// this market.
+    pub fn get_collections_stats(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<CollectionStatsJson> {
+        self.collection_stats
+            .iter()
+            .skip(from_index.unwrap_or(U64(0)).0 as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(nft_contract_id, stats)| stats.into_json(nft_contract_id))
+            .collect()
+    }
+
+    /// Records a settled sale in the token history and collection statistics.
+    /// Returns the cost of the storage that has been used for it.
+    pub(crate) fn record_sale(
+        &mut self,
+        listing: &Listing,
+        offer: &Offer,
+    ) -> Balance {
+        let initial_storage_usage = env::storage_usage();
+        let token_key = listing.token_key();
+        let mut sales = self.sales_by_token.get(&token_key).unwrap_or_default();
+        if sales.len() >= SALE_HISTORY_LEN {
+            sales.remove(0);
+        }
+        sales.push(SaleRecord {
+            seller_id: listing.nft_owner_id.clone(),
+            offerer_id: offer.offerer_id.clone(),
+            receiver_id: offer.receiver_id.clone(),
+            price: offer.amount,
+            currency: listing.currency.clone(),
+            sold_at: env::block_timestamp(),
+        });
+        self.sales_by_token.insert(&token_key, &sales);
+
+        let mut stats = self
+            .collection_stats
+            .get(&listing.nft_contract_id)
+            .unwrap_or_default();
+        stats.add_sale(&listing.currency, offer.amount);
+        self.collection_stats
+            .insert(&listing.nft_contract_id, &stats);
+
+        let used = env::storage_usage().saturating_sub(initial_storage_usage);
+        env::storage_byte_cost() * used as Balance
+    }
+
+    // ---------------------------- wash trading -------------------------------
+    /// Panics if an offer looks like a wash trade:
+    ///
+    /// - The buyer (offerer or NFT receiver) is the seller.
+    /// - The affiliate is the buyer or the seller.
+    /// - The token has changed hands between the seller and the buyer within
+    ///   the last `wash_trade_cooldown_seconds`.
+    pub(crate) fn assert_not_wash_trade(
+        &self,
+        listing: &Listing,
+        offer: &Offer,
+    ) {
+        let seller_id = &listing.nft_owner_id;
+        near_assert!(
+            &offer.offerer_id != seller_id && &offer.receiver_id != seller_id,
+            "Cannot buy your own listing"
+        );
+        if let Some(referrer_id) = offer.referrer_id.as_ref() {
+            near_assert!(
+                referrer_id != seller_id
+                    && referrer_id != &offer.offerer_id
+                    && referrer_id != &offer.receiver_id,
+                "The affiliate cannot be the buyer or seller"
+            );
+        }
+
+        let cooldown_start = env::block_timestamp()
+            .saturating_sub(self.wash_trade_cooldown_seconds * 1_000_000_000);
+        near_assert!(
+            !self.has_traded_since(listing, offer, cooldown_start),
+            "This token has been traded between the same accounts within the last {} seconds",
+            self.wash_trade_cooldown_seconds
+        );
+    }
+
+    /// Whether the seller and buyer of an offer have traded the token before,
+    /// as far as the sale history reaches back. Such sales are flagged as
+    /// suspicious in the sale event.
+    pub(crate) fn is_repeat_trade(
+        &self,
+        listing: &Listing,
+        offer: &Offer,
+    ) -> bool {
+        self.has_traded_since(listing, offer, 0)
+    }
+
+    /// Whether the token has changed hands between the seller and either the
+    /// offerer or the receiver of an offer since the given timestamp.
+    fn has_traded_since(
+        &self,
+        listing: &Listing,
+        offer: &Offer,
+        since: Timestamp,
+    ) -> bool {
+        let seller_id = &listing.nft_owner_id;
+        let is_buyer = |account: &AccountId| {
+            account == &offer.offerer_id || account == &offer.receiver_id
+        };
+        self.sales_by_token
+            .get(&listing.token_key())
+            .unwrap_or_default()
+            .iter()
+            .filter(|sale| sale.sold_at >= since)
+            .any(|sale| {
+                let involves_seller = &sale.seller_id == seller_id
+                    || &sale.offerer_id == seller_id
+                    || &sale.receiver_id == seller_id;
+                let involves_buyer = is_buyer(&sale.seller_id)
+                    || is_buyer(&sale.offerer_id)
+                    || is_buyer(&sale.receiver_id);
+                involves_seller && involves_buyer
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mb_sdk::near_sdk::{
+        testing_env,
+        Balance,
+        PromiseResult,
+    };
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn sale(market: &mut Market, listing: &Listing, amount: Balance) {
+        let offer = Offer {
+            amount,
+            ..offer("buyer.near", "buyer.near", None)
+        };
+        market.record_sale(listing, &offer);
+    }
+
+    fn token_sale_prices(market: &Market, from_index: u64) -> Vec<u128> {
+        market
+            .get_token_sales(
+                account("nft.near"),
+                "1".to_string(),
+                Some(U64(from_index)),
+                None,
+            )
+            .into_iter()
+            .map(|sale| sale.price.0)
+            .collect()
+    }
+
+    #[test]
+    fn token_sales_are_listed_newest_first() {
+        let mut market = setup_market();
+        sale(&mut market, &listing("seller.near"), 1);
+        sale(&mut market, &listing("buyer.near"), 2);
+
+        assert_eq!(token_sale_prices(&market, 0), vec![2, 1]);
+        assert_eq!(token_sale_prices(&market, 1), vec![1]);
+        let sales = market.get_token_sales(
+            account("nft.near"),
+            "1".to_string(),
+            None,
+            None,
+        );
+        assert_eq!(sales[0].seller_id, account("buyer.near"));
+    }
+
+    #[test]
+    fn token_history_keeps_the_latest_sales() {
+        let mut market = setup_market();
+        for price in 0..=SALE_HISTORY_LEN as u128 {
+            sale(&mut market, &listing("seller.near"), price);
+        }
+
+        let prices = token_sale_prices(&market, 0);
+        assert_eq!(prices.len(), SALE_HISTORY_LEN);
+        assert_eq!(prices.first(), Some(&(SALE_HISTORY_LEN as u128)));
+        assert_eq!(prices.last(), Some(&1));
+    }
+
+    #[test]
+    fn collection_stats_are_aggregated_per_currency() {
+        let mut market = setup_market();
+        let ft_listing = Listing {
+            currency: Currency::FtContract(account("usdc.near")),
+            ..listing("seller.near")
+        };
+        sale(&mut market, &listing("seller.near"), 1);
+        sale(&mut market, &listing("seller.near"), 3);
+        sale(&mut market, &ft_listing, 5);
+        sale(&mut market, &listing("seller.near"), 2);
+
+        let stats = market.get_collection_stats(account("nft.near")).unwrap();
+        assert_eq!(stats.sale_count.0, 4);
+        assert_eq!(stats.last_sale_at.0, 1_000 * SECOND);
+        let near = stats
+            .currencies
+            .iter()
+            .find(|stats| stats.currency == Currency::Near.to_string())
+            .unwrap();
+        assert_eq!(near.volume.0, 6);
+        assert_eq!(near.sale_count.0, 3);
+        assert_eq!(near.last_sale_price.0, 2);
+        assert_eq!(near.all_time_high.0, 3);
+        let ft = stats
+            .currencies
+            .iter()
+            .find(|stats| stats.currency == ft_listing.currency.to_string())
+            .unwrap();
+        assert_eq!(ft.volume.0, 5);
+
+        assert!(market.get_collection_stats(account("other.near")).is_none());
+        assert_eq!(market.get_collections_stats(None, None).len(), 1);
+    }
+
+    #[test]
+    fn settled_sales_are_recorded() {
+        let mut market = setup_market();
+        let token_key = insert_listing(
+            &mut market,
+            &Listing {
+                current_offer: Some(offer("buyer.near", "buyer.near", None)),
+                ..listing("seller.near")
+            },
+        );
+
+        set_promise_result(PromiseResult::Successful(
+            br#"{"payout":{"seller.near":"900000000000000000000000"}}"#
+                .to_vec(),
+        ));
+        market.nft_resolve_payout_near(token_key);
+
+        assert_eq!(token_sale_prices(&market, 0), vec![ONE_NEAR]);
+        assert!(market.get_collection_stats(account("nft.near")).is_some());
+    }
+
+    #[test]
+    fn failed_sales_are_not_recorded() {
+        let mut market = setup_market();
+        let token_key = insert_listing(
+            &mut market,
+            &Listing {
+                current_offer: Some(offer("buyer.near", "buyer.near", None)),
+                ..listing("seller.near")
+            },
+        );
+
+        set_promise_result(PromiseResult::Failed);
+        market.nft_resolve_payout_near(token_key);
+
+        assert!(token_sale_prices(&market, 0).is_empty());
+        assert!(market.get_collection_stats(account("nft.near")).is_none());
+    }
+
+    #[test]
+    fn offer_between_strangers_is_not_a_wash_trade() {
+        let market = setup_market();
+        market.assert_not_wash_trade(
+            &listing("seller.near"),
+            &offer("buyer.near", "friend.near", Some("affiliate.near")),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot buy your own listing")]
+    fn seller_cannot_buy_own_listing() {
+        let mut market = setup_market();
+        insert_listing(&mut market, &listing("seller.near"));
+        testing_env!(context("seller.near").attached_deposit(ONE_NEAR).build());
+        market.buy(account("nft.near"), "1".to_string(), None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot buy your own listing")]
+    fn seller_cannot_receive_own_listing() {
+        let market = setup_market();
+        market.assert_not_wash_trade(
+            &listing("seller.near"),
+            &offer("buyer.near", "seller.near", None),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The affiliate cannot be the buyer or seller")]
+    fn affiliate_cannot_be_the_buyer() {
+        let market = setup_market();
+        market.assert_not_wash_trade(
+            &listing("seller.near"),
+            &offer("buyer.near", "friend.near", Some("friend.near")),
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "This token has been traded between the same accounts within the last 86400 seconds"
+    )]
+    fn token_cannot_be_traded_back_within_cooldown() {
+        let mut market = setup_market();
+        market.record_sale(
+            &listing("seller.near"),
+            &offer("buyer.near", "buyer.near", None),
+        );
+
+        testing_env!(context("market.near")
+            .block_timestamp((1_000 + WASH_TRADE_COOLDOWN_SECONDS) * SECOND)
+            .build());
+        market.assert_not_wash_trade(
+            &listing("buyer.near"),
+            &offer("seller.near", "seller.near", None),
+        );
+    }
+
+    #[test]
+    fn token_can_be_traded_back_after_cooldown() {
+        let mut market = setup_market();
+        market.record_sale(
+            &listing("seller.near"),
+            &offer("buyer.near", "friend.near", None),
+        );
+
+        // The sale is only flagged as a repeat trade
+        testing_env!(context("market.near")
+            .block_timestamp((1_001 + WASH_TRADE_COOLDOWN_SECONDS) * SECOND)
+            .build());
+        let listing = listing("friend.near");
+        let offer = offer("other.near", "seller.near", None);
+        market.assert_not_wash_trade(&listing, &offer);
+        assert!(market.is_repeat_trade(&listing, &offer));
+    }
+
+    #[test]
+    fn cooldown_can_be_shortened() {
+        let mut market = setup_market();
+        market.record_sale(
+            &listing("seller.near"),
+            &offer("buyer.near", "buyer.near", None),
+        );
+
+        testing_env!(context("owner.near").attached_deposit(1).build());
+        market.set_wash_trade_cooldown_seconds(U64(0));
+        testing_env!(context("market.near")
+            .block_timestamp(1_001 * SECOND)
+            .build());
+        market.assert_not_wash_trade(
+            &listing("buyer.near"),
+            &offer("seller.near", "seller.near", None),
+        );
+    }
+
+    #[test]
+    fn trades_with_other_accounts_are_not_repeat_trades() {
+        let mut market = setup_market();
+        market.record_sale(
+            &listing("seller.near"),
+            &offer("buyer.near", "buyer.near", None),
+        );
+
+        let listing = listing("buyer.near");
+        let offer = offer("other.near", "other.near", None);
+        market.assert_not_wash_trade(&listing, &offer);
+        assert!(!market.is_repeat_trade(&listing, &offer));
+    }
+}
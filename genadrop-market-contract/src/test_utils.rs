@@ -0,0 +1,107 @@
+//! Fixtures shared by the unit tests of the market.
+
+use mb_sdk::near_sdk::{
+    json_types::U64,
+    test_utils::VMContextBuilder,
+    testing_env,
+    AccountId,
+    Balance,
+    Gas,
+    PromiseResult,
+    RuntimeFeesConfig,
+    Timestamp,
+    VMConfig,
+};
+
+use crate::{
+    data::*,
+    Market,
+};
+
+/// One second in nanoseconds, the unit of block timestamps.
+pub const SECOND: Timestamp = 1_000_000_000;
+/// One NEAR in yoctoNEAR.
+pub const ONE_NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+/// Gas available to `ft_on_transfer`: NEP-141 contracts reserve 30 TGas of
+/// the maximum 300 TGas for `ft_transfer_call` and `ft_resolve_transfer`.
+pub const FT_ON_TRANSFER_GAS: Gas = Gas(270_000_000_000_000);
+
+pub fn account(account_id: &str) -> AccountId {
+    account_id.parse().unwrap()
+}
+
+/// Context for a call by `predecessor_id` to the market, which is deployed
+/// at `market.near`. The block timestamp is at 1000 seconds.
+pub fn context(predecessor_id: &str) -> VMContextBuilder {
+    let mut builder = VMContextBuilder::new();
+    builder
+        .current_account_id(account("market.near"))
+        .predecessor_account_id(account(predecessor_id))
+        .block_timestamp(1_000 * SECOND);
+    builder
+}
+
+/// Market owned by `owner.near` with cuts of 2.5% and no listing lock.
+pub fn setup_market() -> Market {
+    testing_env!(context("owner.near").build());
+    Market::init(account("owner.near"), 250, 250, U64(0))
+}
+
+/// Listing of token `1` on `nft.near` for one NEAR.
+pub fn listing(seller_id: &str) -> Listing {
+    Listing {
+        nft_token_id: "1".to_string(),
+        nft_approval_id: 0,
+        nft_owner_id: account(seller_id),
+        nft_contract_id: account("nft.near"),
+        price: ONE_NEAR,
+        currency: Currency::Near,
+        created_at: 0,
+        current_offer: None,
+        order_nonce: None,
+        trait_offer_id: None,
+        gate: None,
+    }
+}
+
+/// Offer of one NEAR.
+pub fn offer(
+    offerer_id: &str,
+    receiver_id: &str,
+    referrer_id: Option<&str>,
+) -> Offer {
+    Offer {
+        offerer_id: account(offerer_id),
+        receiver_id: account(receiver_id),
+        amount: ONE_NEAR,
+        referrer_id: referrer_id.map(account),
+        referral_cut: None,
+    }
+}
+
+/// Sets up a callback of the market that receives the given result of the
+/// promise it has been waiting for.
+pub fn set_promise_result(result: PromiseResult) {
+    testing_env!(
+        context("market.near").build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![result],
+    );
+}
+
+/// Stores a listing together with the storage deposit of the seller that
+/// backs it and returns its token key.
+pub fn insert_listing(market: &mut Market, listing: &Listing) -> String {
+    let token_key = listing.token_key();
+    let seller_id = &listing.nft_owner_id;
+    let deposit =
+        market.storage_deposit_by(seller_id) + market.listing_storage_deposit;
+    market
+        .storage_deposits_by_account
+        .insert(seller_id, &deposit);
+    market.increase_listings_count(seller_id, 1);
+    market.listings.insert(&token_key, listing);
+    token_key
+}
@@ -0,0 +1,632 @@
+//! This module implements escrowed offers on traits rather than on specific
+//! tokens. A buyer calls `make_trait_offer` with NEAR attached, specifying an
+//! NFT contract and a set of traits, e.g. `background = gold`. Any holder of a
+//! token on that contract can then fill the offer:
+//!
+//! - Approve the market for the token on the NFT contract without a `msg`
+//! - Call `accept_trait_offer` with the token ID and approval ID
+//!
+//! The market then queries `nft_token` on the NFT contract and checks that the
+//! seller owns the token, that the approval ID is current, and that the
+//! `extra` field of the token metadata contains all requested traits. Only
+//! then the offer is settled through the same `nft_transfer_payout` and
+//! `nft_resolve_payout_near` path as regular listings. Should the checks fail,
+//! the offer remains available.
+//!
+//! While an offer is being settled, it occupies a listing slot for the token,
+//! such that no other offer can execute on it concurrently, and the offer
+//! itself cannot be filled by another seller or be cancelled.
+
+use std::collections::HashMap;
+
+use mb_sdk::{
+    near_assert,
+    near_sdk::{
+        self,
+        env,
+        json_types::{
+            U128,
+            U64,
+        },
+        serde_json::{
+            self,
+            Value,
+        },
+        AccountId,
+        Balance,
+        Promise,
+        PromiseOrValue,
+        PromiseResult,
+    },
+    utils::assert_predecessor,
+};
+
+use crate::{
+    data::*,
+    events,
+    ext::{
+        ext_nft_view,
+        NftToken,
+    },
+    Market,
+    MarketExt,
+};
+
+#[near_sdk::near_bindgen]
+impl Market {
+    /// Escrow NEAR for any token on `nft_contract_id` that has all of the
+    /// given traits. The attached deposit must cover the price and the
+    /// required storage, any excess is refunded. Returns the ID of the offer.
+    ///
+    /// - Offers must not be paused.
+    /// - The offerer, the receiver and the NFT contract must not be banned.
+    /// - The price must be within the price bounds for NEAR.
+    /// - The NFT is transferred to `receiver_id` if given, otherwise to the
+    ///   offerer. Refunds always go back to the offerer.
+    #[payable]
+    pub fn make_trait_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        traits: HashMap<String, String>,
+        price: U128,
+        receiver_id: Option<AccountId>,
+    ) -> U64 {
+        self.assert_not_paused(PauseKind::Offers);
+        let offerer_id = env::predecessor_account_id();
+        let receiver_id = receiver_id.unwrap_or_else(|| offerer_id.clone());
+
+        // No involved party must be banned from using the market
+        self.assert_not_banned(&offerer_id);
+        self.assert_not_banned(&receiver_id);
+        self.assert_not_banned(&nft_contract_id);
+        near_assert!(!traits.is_empty(), "Trait offers require a trait");
+        near_assert!(price.0 > 0, "Trait offers require a price");
+        self.assert_valid_price(&Currency::Near, price.0);
+
+        let offer_id = self.next_trait_offer_id;
+        self.next_trait_offer_id += 1;
+        let mut offer = TraitOffer {
+            nft_contract_id,
+            offerer_id,
+            receiver_id,
+            traits,
+            price: price.0,
+            storage_deposit: 0,
+            created_at: env::block_timestamp(),
+            in_progress: false,
+        };
+
+        // The storage deposit has a fixed size, so it can be set afterwards
+        let initial_storage_usage = env::storage_usage();
+        self.trait_offers.insert(&offer_id, &offer);
+        let used = env::storage_usage() - initial_storage_usage;
+        offer.storage_deposit = env::storage_byte_cost() * used as Balance;
+        self.trait_offers.insert(&offer_id, &offer);
+
+        let required = offer.price + offer.storage_deposit;
+        let deposit = env::attached_deposit();
+        near_assert!(
+            deposit >= required,
+            "Must attach {} yoctoNEAR to cover price and storage",
+            required
+        );
+        if deposit > required {
+            Promise::new(offer.offerer_id.clone()).transfer(deposit - required);
+        }
+
+        env::log_str(
+            &events::NftTraitOfferData {
+                offer_id: offer_id.into(),
+                nft_contract_id: offer.nft_contract_id,
+                offerer_id: offer.offerer_id,
+                receiver_id: offer.receiver_id,
+                traits: offer.traits,
+                price,
+            }
+            .serialize_event(),
+        );
+
+        offer_id.into()
+    }
+
+    /// Cancel a trait offer and refund the escrowed price and storage
+    /// deposit. Only the offerer can call this, and not while the offer is
+    /// being settled.
+    #[payable]
+    pub fn cancel_trait_offer(&mut self, offer_id: U64) {
+        near_sdk::assert_one_yocto();
+        let offer = self.get_trait_offer_internal(offer_id.0);
+        assert_predecessor(&offer.offerer_id);
+        near_assert!(!offer.in_progress, "Trait offer is being settled");
+
+        self.trait_offers.remove(&offer_id.0);
+        Promise::new(offer.offerer_id)
+            .transfer(offer.price + offer.storage_deposit);
+
+        env::log_str(
+            &events::NftTraitOfferCancelData { offer_id }.serialize_event(),
+        );
+    }
+
+    /// Fill a trait offer by selling a token to the offerer. The market must
+    /// have been approved for the token beforehand with `approval_id`. The
+    /// same rules as for `buy` apply, and additionally:
+    ///
+    /// - The offer must not be in settlement already.
+    /// - The token must not be listed on the market at the same time.
+    ///
+    /// Before the transfer, `nft_token` is queried on the NFT contract to
+    /// check ownership, approval, and traits of the token. Should that fail,
+    /// the offer remains available.
+    pub fn accept_trait_offer(
+        &mut self,
+        offer_id: U64,
+        token_id: String,
+        approval_id: u64,
+    ) -> Promise {
+        self.assert_not_paused(PauseKind::Purchases);
+        let seller_id = env::predecessor_account_id();
+        let mut offer = self.get_trait_offer_internal(offer_id.0);
+        near_assert!(!offer.in_progress, "Trait offer is being settled");
+
+        // No involved party must be banned from using the market
+        self.assert_not_banned(&seller_id);
+        self.assert_not_banned(&offer.offerer_id);
+        self.assert_not_banned(&offer.receiver_id);
+        self.assert_not_banned(&offer.nft_contract_id);
+        near_assert!(
+            token_id.len() <= 128,
+            "Cannot process token IDs with more than 128 bytes"
+        );
+
+        // The token must not be listed or in settlement already
+        let listing = Listing::from_trait_offer(
+            offer_id.0,
+            &offer,
+            token_id,
+            approval_id,
+            seller_id,
+        );
+        let token_key = listing.token_key();
+        near_assert!(
+            self.get_listing_internal(&token_key).is_none(),
+            "Token is listed on the market, unlist it first"
+        );
+        self.assert_not_wash_trade(
+            &listing,
+            listing.current_offer.as_ref().unwrap(),
+        );
+
+        offer.in_progress = true;
+        self.trait_offers.insert(&offer_id.0, &offer);
+        self.log_make_offer(
+            &listing,
+            listing.current_offer.as_ref().unwrap(),
+            offer_id.0,
+        );
+        self.listings.insert(&token_key, &listing);
+
+        ext_nft_view::ext(listing.nft_contract_id)
+            .with_static_gas(NFT_TOKEN_GAS)
+            .with_unused_gas_weight(0)
+            .nft_token(listing.nft_token_id)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(NFT_RESOLVE_TRAIT_OFFER_GAS)
+                    .nft_resolve_trait_offer(token_key),
+            )
+    }
+
+    /// Resolves the ownership, approval and trait check of a trait offer. If
+    /// the check fails, the offer becomes available again, otherwise the
+    /// token is transferred and paid out as for any other listing.
+    #[private]
+    pub fn nft_resolve_trait_offer(
+        &mut self,
+        token_key: String,
+    ) -> PromiseOrValue<()> {
+        let listing = self.get_listing_internal(&token_key).unwrap();
+        let offer_id = listing.trait_offer_id.unwrap();
+        let offer = self.get_trait_offer_internal(offer_id);
+
+        if !trait_offer_token_is_valid(&listing, &offer.traits) {
+            self.listings.remove(&token_key);
+            self.release_trait_offer(offer_id);
+            env::log_str(
+                &events::NftTraitOfferRejectData {
+                    offer_id: offer_id.into(),
+                    nft_contract_id: listing.nft_contract_id,
+                    nft_token_id: listing.nft_token_id,
+                }
+                .serialize_event(),
+            );
+            return PromiseOrValue::Value(());
+        }
+
+        PromiseOrValue::Promise(self.execute_transfer(
+            listing,
+            offer.receiver_id,
+            offer.price,
+        ))
+    }
+
+    /// Show a trait offer.
+    pub fn get_trait_offer(&self, offer_id: U64) -> Option<TraitOfferJson> {
+        self.trait_offers
+            .get(&offer_id.0)
+            .map(|offer| offer.into_json(offer_id.0))
+    }
+
+    /// Show trait offers, optionally only those on a single NFT contract.
+    pub fn get_trait_offers(
+        &self,
+        nft_contract_id: Option<AccountId>,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<TraitOfferJson> {
+        self.trait_offers
+            .iter()
+            .filter(|(_, offer)| {
+                !matches!(
+                    nft_contract_id.as_ref(),
+                    Some(id) if id != &offer.nft_contract_id
+                )
+            })
+            .skip(from_index.unwrap_or(U64(0)).0 as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(offer_id, offer)| offer.into_json(offer_id))
+            .collect()
+    }
+
+    /// Removes a trait offer after its settlement, refunding the storage
+    /// deposit. The escrowed price must have been handled by the caller.
+    pub(crate) fn remove_trait_offer(
+        &mut self,
+        offer_id: u64,
+        storage_fee: Balance,
+    ) {
+        if let Some(offer) = self.trait_offers.remove(&offer_id) {
+            let refund = offer.storage_deposit.saturating_sub(storage_fee);
+            if refund > 0 {
+                Promise::new(offer.offerer_id).transfer(refund);
+            }
+        }
+    }
+
+    /// Makes a trait offer available again after a settlement that did not
+    /// go through.
+    pub(crate) fn release_trait_offer(&mut self, offer_id: u64) {
+        if let Some(mut offer) = self.trait_offers.get(&offer_id) {
+            offer.in_progress = false;
+            self.trait_offers.insert(&offer_id, &offer);
+        }
+    }
+
+    fn get_trait_offer_internal(&self, offer_id: u64) -> TraitOffer {
+        match self.trait_offers.get(&offer_id) {
+            None => env::panic_str(ERR_TRAIT_OFFER_NOT_FOUND),
+            Some(offer) => offer,
+        }
+    }
+}
+
+/// Checks the result of the `nft_token` call that precedes settlement of a
+/// trait offer: The seller must own the token, the market must be approved
+/// with the given approval ID, and the token must have all requested traits.
+fn trait_offer_token_is_valid(
+    listing: &Listing,
+    traits: &HashMap<String, String>,
+) -> bool {
+    let token = match env::promise_result(0) {
+        PromiseResult::Successful(value) => {
+            serde_json::from_slice::<Option<NftToken>>(&value)
+                .ok()
+                .flatten()
+        }
+        _ => None,
+    };
+
+    match token {
+        None => false,
+        Some(token) => {
+            let extra = token.metadata.and_then(|metadata| metadata.extra);
+            token.owner_id == listing.nft_owner_id
+                && token.approved_account_ids.get(&env::current_account_id())
+                    == Some(&listing.nft_approval_id)
+                && extra.is_some_and(|extra| traits_match(&extra, traits))
+        }
+    }
+}
+
+/// Checks whether the `extra` field of a token's metadata contains all of the
+/// given traits. Both flat JSON objects (`{"background": "gold"}`) and
+/// attribute lists (`{"attributes": [{"trait_type": "background", "value":
+/// "gold"}]}`) are supported.
+fn traits_match(extra: &str, traits: &HashMap<String, String>) -> bool {
+    let token_traits = match serde_json::from_str::<Value>(extra) {
+        Ok(Value::Array(attributes)) => collect_attributes(&attributes),
+        Ok(Value::Object(object)) => match object.get("attributes") {
+            Some(Value::Array(attributes)) => collect_attributes(attributes),
+            _ => object
+                .iter()
+                .map(|(key, value)| (key.clone(), trait_value(value)))
+                .collect(),
+        },
+        _ => return false,
+    };
+
+    traits
+        .iter()
+        .all(|(key, value)| token_traits.get(key) == Some(value))
+}
+
+/// Collects `trait_type`/`value` pairs from an attribute list.
+fn collect_attributes(attributes: &[Value]) -> HashMap<String, String> {
+    attributes
+        .iter()
+        .filter_map(|attribute| {
+            Some((
+                trait_value(attribute.get("trait_type")?),
+                trait_value(attribute.get("value")?),
+            ))
+        })
+        .collect()
+}
+
+/// Strings are compared without quotes, anything else by its JSON.
+fn trait_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mb_sdk::near_sdk::{
+        test_utils::get_logs,
+        testing_env,
+    };
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn traits(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn make_offer(market: &mut Market) -> U64 {
+        testing_env!(context("buyer.near")
+            .attached_deposit(2 * ONE_NEAR)
+            .build());
+        market.make_trait_offer(
+            account("nft.near"),
+            traits(&[("background", "gold")]),
+            U128(ONE_NEAR),
+            None,
+        )
+    }
+
+    fn accept_offer(market: &mut Market, offer_id: U64) -> String {
+        testing_env!(context("seller.near").build());
+        market.accept_trait_offer(offer_id, "1".to_string(), 0);
+        "nft.near<$>1".to_string()
+    }
+
+    /// Result of `nft_token` for token `1`, owned by `seller.near` and
+    /// approved for the market with ID 0.
+    fn nft_token(owner_id: &str, extra: &str) -> PromiseResult {
+        let token = serde_json::json!({
+            "token_id": "1",
+            "owner_id": owner_id,
+            "approved_account_ids": {"market.near": 0},
+            "metadata": {"extra": extra},
+        });
+        PromiseResult::Successful(token.to_string().into_bytes())
+    }
+
+    #[test]
+    fn offer_escrows_price_and_storage() {
+        let mut market = setup_market();
+        let offer_id = make_offer(&mut market);
+
+        let offer = market.get_trait_offer(offer_id).unwrap();
+        assert_eq!(offer.price, U128(ONE_NEAR));
+        assert_eq!(offer.receiver_id, account("buyer.near"));
+        assert!(!offer.in_progress);
+        let storage_deposit = market
+            .trait_offers
+            .get(&offer_id.0)
+            .unwrap()
+            .storage_deposit;
+        assert!(storage_deposit > 0);
+        assert!(get_logs()[0].contains(r#""event":"nft_trait_offer""#));
+        assert_eq!(market.get_trait_offers(None, None, None).len(), 1);
+        assert!(market
+            .get_trait_offers(Some(account("other.near")), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn offer_requires_price_and_storage() {
+        let mut market = setup_market();
+        testing_env!(context("buyer.near").attached_deposit(ONE_NEAR).build());
+        market.make_trait_offer(
+            account("nft.near"),
+            traits(&[("background", "gold")]),
+            U128(ONE_NEAR),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Trait offers require a trait")]
+    fn offer_requires_a_trait() {
+        let mut market = setup_market();
+        testing_env!(context("buyer.near").attached_deposit(ONE_NEAR).build());
+        market.make_trait_offer(
+            account("nft.near"),
+            traits(&[]),
+            U128(ONE_NEAR),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Price must be at least 2")]
+    fn offer_price_must_be_within_the_price_bounds() {
+        let mut market = setup_market();
+        testing_env!(context("owner.near").attached_deposit(1).build());
+        market.set_price_bounds(None, U128(2 * ONE_NEAR), None);
+        make_offer(&mut market);
+    }
+
+    #[test]
+    fn offerer_cancels_an_offer() {
+        let mut market = setup_market();
+        let offer_id = make_offer(&mut market);
+
+        testing_env!(context("buyer.near").attached_deposit(1).build());
+        market.cancel_trait_offer(offer_id);
+        assert!(market.get_trait_offer(offer_id).is_none());
+        assert!(get_logs()[0].contains(r#""event":"nft_trait_offer_cancel""#));
+    }
+
+    #[test]
+    #[should_panic]
+    fn stranger_cannot_cancel_an_offer() {
+        let mut market = setup_market();
+        let offer_id = make_offer(&mut market);
+
+        testing_env!(context("stranger.near").attached_deposit(1).build());
+        market.cancel_trait_offer(offer_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Trait offer is being settled")]
+    fn offer_in_settlement_cannot_be_cancelled() {
+        let mut market = setup_market();
+        let offer_id = make_offer(&mut market);
+        accept_offer(&mut market, offer_id);
+
+        testing_env!(context("buyer.near").attached_deposit(1).build());
+        market.cancel_trait_offer(offer_id);
+    }
+
+    #[test]
+    fn accepting_locks_the_offer() {
+        let mut market = setup_market();
+        let offer_id = make_offer(&mut market);
+        let token_key = accept_offer(&mut market, offer_id);
+
+        assert!(market.get_trait_offer(offer_id).unwrap().in_progress);
+        let listing = market.listings.get(&token_key).unwrap();
+        assert_eq!(listing.trait_offer_id, Some(offer_id.0));
+        assert_eq!(listing.nft_owner_id, account("seller.near"));
+        assert_eq!(listing.price, ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is listed on the market")]
+    fn listed_token_cannot_be_sold_to_an_offer() {
+        let mut market = setup_market();
+        insert_listing(&mut market, &listing("seller.near"));
+        let offer_id = make_offer(&mut market);
+        accept_offer(&mut market, offer_id);
+    }
+
+    #[test]
+    fn matching_token_is_transferred() {
+        let mut market = setup_market();
+        let offer_id = make_offer(&mut market);
+        let token_key = accept_offer(&mut market, offer_id);
+
+        set_promise_result(nft_token(
+            "seller.near",
+            r#"{"background":"gold"}"#,
+        ));
+        let result = market.nft_resolve_trait_offer(token_key.clone());
+        assert!(matches!(result, PromiseOrValue::Promise(_)));
+        assert!(market.listings.get(&token_key).is_some());
+        assert!(market.get_trait_offer(offer_id).unwrap().in_progress);
+    }
+
+    #[test]
+    fn token_without_the_traits_releases_the_offer() {
+        let mut market = setup_market();
+        let offer_id = make_offer(&mut market);
+        let token_key = accept_offer(&mut market, offer_id);
+
+        set_promise_result(nft_token("seller.near", r#"{"background":"red"}"#));
+        market.nft_resolve_trait_offer(token_key.clone());
+        assert!(market.listings.get(&token_key).is_none());
+        assert!(!market.get_trait_offer(offer_id).unwrap().in_progress);
+        assert!(get_logs()[0].contains(r#""event":"nft_trait_offer_reject""#));
+    }
+
+    #[test]
+    fn token_of_another_owner_releases_the_offer() {
+        let mut market = setup_market();
+        let offer_id = make_offer(&mut market);
+        let token_key = accept_offer(&mut market, offer_id);
+
+        set_promise_result(nft_token("owner.near", r#"{"background":"gold"}"#));
+        market.nft_resolve_trait_offer(token_key.clone());
+        assert!(market.listings.get(&token_key).is_none());
+        assert!(!market.get_trait_offer(offer_id).unwrap().in_progress);
+    }
+
+    #[test]
+    fn traits_match_flat_object() {
+        let extra = r#"{"background": "gold", "eyes": "laser"}"#;
+        assert!(traits_match(extra, &traits(&[("background", "gold")])));
+        assert!(traits_match(
+            extra,
+            &traits(&[("background", "gold"), ("eyes", "laser")])
+        ));
+        assert!(!traits_match(extra, &traits(&[("background", "silver")])));
+        assert!(!traits_match(extra, &traits(&[("hat", "gold")])));
+    }
+
+    #[test]
+    fn traits_match_attribute_list() {
+        let attributes = r#"[
+            {"trait_type": "background", "value": "gold"},
+            {"trait_type": "level", "value": 5}
+        ]"#;
+        let extra = format!(r#"{{"attributes": {}}}"#, attributes);
+        for extra in &[attributes, extra.as_str()] {
+            assert!(traits_match(
+                extra,
+                &traits(&[("background", "gold"), ("level", "5")])
+            ));
+            assert!(!traits_match(extra, &traits(&[("level", "6")])));
+            assert!(!traits_match(extra, &traits(&[("trait_type", "level")])));
+        }
+    }
+
+    #[test]
+    fn traits_match_non_string_values() {
+        let extra = r#"{"level": 5, "shiny": true, "name": "5"}"#;
+        assert!(traits_match(
+            extra,
+            &traits(&[("level", "5"), ("shiny", "true"), ("name", "5")])
+        ));
+    }
+
+    #[test]
+    fn traits_match_all_of_no_traits() {
+        assert!(traits_match(r#"{"background": "gold"}"#, &traits(&[])));
+    }
+
+    #[test]
+    fn traits_do_not_match_invalid_extra() {
+        assert!(!traits_match("background=gold", &traits(&[])));
+        assert!(!traits_match(r#""gold""#, &traits(&[])));
+        assert!(!traits_match("", &traits(&[("background", "gold")])));
+    }
+}
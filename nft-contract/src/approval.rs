@@ -54,6 +54,11 @@ impl NonFungibleTokenCore for Contract {
             "Predecessor must be the token owner."
         );
 
+        //soulbound tokens can't be approved since they can't be transferred
+        assert!(!token.soulbound, "Soulbound tokens cannot be approved");
+        //make sure that the token is within its validity window if the validity is enforced
+        self.internal_assert_valid(&token_id, &token);
+
         //get the next approval ID if we need a new approval
         let approval_id: u64 = token.next_approval_id;
 
@@ -79,7 +84,7 @@ impl NonFungibleTokenCore for Contract {
         self.tokens_by_id.insert(&token_id, &token);
 
         //refund any excess storage attached by the user. If the user didn't attach enough, panic. 
-        refund_deposit(storage_used);
+        self.refund_deposit(storage_used);
 
         //if some message was passed into the function, we initiate a cross contract call on the
         //account we're giving access to. 
@@ -143,7 +148,7 @@ impl NonFungibleTokenCore for Contract {
             .is_some()
         {
             //refund the funds released by removing the approved_account_id to the caller of the function
-            refund_approved_account_ids_iter(predecessor_account_id, [account_id].iter());
+            self.refund_approved_account_ids_iter(predecessor_account_id, [account_id].iter());
 
             //insert the token back into the tokens_by_id collection with the account_id removed from the approval list
             self.tokens_by_id.insert(&token_id, &token);
@@ -165,7 +170,7 @@ impl NonFungibleTokenCore for Contract {
         //only revoke if the approved account IDs for the token is not empty
         if !token.approved_account_ids.is_empty() {
             //refund the approved account IDs to the caller of the function
-            refund_approved_account_ids(predecessor_account_id, &token.approved_account_ids);
+            self.refund_approved_account_ids(predecessor_account_id, &token.approved_account_ids);
             //clear the approved account IDs
             token.approved_account_ids.clear();
             //insert the token back into the tokens_by_id collection with the approved account IDs cleared
@@ -0,0 +1,158 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    //burns a token. Only the owner or an approved account can burn it. The freed storage is refunded to the owner.
+    #[payable]
+    pub fn nft_burn(&mut self, token_id: TokenId) {
+        //assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be redirected to the NEAR wallet.
+        assert_one_yocto();
+        //get the account that wants to burn the token
+        let sender_id = env::predecessor_account_id();
+
+        //get the token object by passing in the token_id
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+
+        //if the sender doesn't equal the owner, we check if the sender is in the approval list
+        if sender_id != token.owner_id && !token.approved_account_ids.contains_key(&sender_id) {
+            env::panic_str("Unauthorized");
+        }
+
+        // Default the authorized ID to be None for the logs.
+        let mut authorized_id = None;
+        //if the token was burned by an approved account, set the authorized ID equal to the sender
+        if sender_id != token.owner_id {
+            authorized_id = Some(sender_id.to_string());
+        }
+
+        //burn the token
+        self.internal_burn(&token_id, token, authorized_id, None);
+    }
+}
+
+impl Contract {
+    //burns a token without checking who is allowed to do so. The freed storage is refunded to the owner.
+    pub(crate) fn internal_burn(
+        &mut self,
+        token_id: &TokenId,
+        token: Token,
+        //the account that burned the token on behalf of the owner, for the logs
+        authorized_id: Option<String>,
+        memo: Option<String>,
+    ) {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //remove the token from the owner's and creator's sets, the token struct, the metadata and its metadata settings
+        self.internal_remove_token_from_owner(&token.owner_id, token_id);
+        self.internal_remove_token_from_creator(&token.creator_id, token_id);
+        self.tokens_by_id.remove(token_id);
+        self.token_metadata_by_id.remove(token_id);
+        self.metadata_updaters.remove(token_id);
+        self.frozen_tokens.remove(token_id);
+        self.total_supply -= 1;
+        //tokens of a series share its metadata, so we only remove them from the series
+        if let Some(series_id) = &token.series_id {
+            self.internal_remove_token_from_series(series_id, token_id);
+        }
+
+        //calculate the storage that was freed up
+        let released_storage_in_bytes = initial_storage_usage - env::storage_usage();
+
+        //the approvals are part of the freed storage, but are refunded separately
+        let approval_storage_in_bytes: u64 = token
+            .approved_account_ids
+            .keys()
+            .map(bytes_for_approved_account_id)
+            .sum();
+        let token_storage_in_bytes = released_storage_in_bytes.saturating_sub(approval_storage_in_bytes);
+
+        //we refund the owner for releasing the storage used up by the token and the approved account IDs
+        self.refund_approved_account_ids(token.owner_id.clone(), &token.approved_account_ids);
+        self.internal_release_storage(
+            token.owner_id.clone(),
+            Balance::from(token_storage_in_bytes) * env::storage_byte_cost(),
+        );
+
+        // Construct the burn log as per the events standard.
+        let nft_burn_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftBurn(vec![NftBurnLog {
+                // Owner of the token.
+                owner_id: token.owner_id.to_string(),
+                // The optional authorized account ID to burn the token on behalf of the owner.
+                authorized_id,
+                // Vector of token IDs that were burned.
+                token_ids: vec![token_id.to_string()],
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_burn_log.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn owner_burns_a_token() {
+        let mut contract = setup_contract();
+        mint(&mut contract, "1", "holder.near");
+        mint(&mut contract, "2", "holder.near");
+
+        testing_env!(get_context("holder.near").attached_deposit(1).build());
+        contract.nft_burn("1".to_string());
+        assert!(contract.nft_token("1".to_string()).is_none());
+        assert!(contract.token_metadata_by_id.get(&"1".to_string()).is_none());
+        assert_eq!(contract.nft_supply_for_owner(account("holder.near")), U128(1));
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep171","version":"nft-1.0.0","event":"nft_burn","data":[{"owner_id":"holder.near","token_ids":["1"]}]}"#]
+        );
+    }
+
+    #[test]
+    fn approved_account_burns_a_token() {
+        let mut contract = setup_contract();
+        mint(&mut contract, "1", "holder.near");
+        let mut token = contract.tokens_by_id.get(&"1".to_string()).unwrap();
+        token.approved_account_ids.insert(account("market.near"), 0);
+        contract.tokens_by_id.insert(&"1".to_string(), &token);
+
+        testing_env!(get_context("market.near").attached_deposit(1).build());
+        contract.nft_burn("1".to_string());
+        assert!(contract.nft_token("1".to_string()).is_none());
+        assert!(get_logs()[0].contains(r#""authorized_id":"market.near""#));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn stranger_cannot_burn_a_token() {
+        let mut contract = setup_contract();
+        mint(&mut contract, "1", "holder.near");
+
+        testing_env!(get_context("stranger.near").attached_deposit(1).build());
+        contract.nft_burn("1".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn burn_requires_one_yocto() {
+        let mut contract = setup_contract();
+        mint(&mut contract, "1", "holder.near");
+
+        testing_env!(get_context("holder.near").build());
+        contract.nft_burn("1".to_string());
+    }
+}
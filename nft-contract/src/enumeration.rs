@@ -4,8 +4,8 @@ use crate::*;
 impl Contract {
     //Query for the total supply of NFTs on the contract
     pub fn nft_total_supply(&self) -> U128 {
-        //return the length of the token metadata by ID
-        U128(self.token_metadata_by_id.len() as u128)
+        //return the running count of tokens, which includes the tokens minted from series
+        U128(self.total_supply as u128)
     }
 
     //Query for nft tokens on the contract regardless of the owner using pagination
@@ -13,10 +13,8 @@ impl Contract {
         //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
         let start = u128::from(from_index.unwrap_or(U128(0)));
 
-        //iterate through each token using an iterator
-        self.token_metadata_by_id.keys()
-            //skip to the index we specified in the start variable
-            .skip(start as usize) 
+        //iterate through the token IDs from the index we specified in the start variable, followed by the tokens minted from series
+        self.internal_token_ids_from(start as u64)
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
             .take(limit.unwrap_or(50) as usize) 
             //we'll map the token IDs which are strings into Json Tokens
@@ -73,4 +71,137 @@ impl Contract {
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
+
+    //get the total supply of NFTs created by a given account
+    pub fn nft_supply_for_creator(
+        &self,
+        account_id: AccountId,
+    ) -> U128 {
+        //get the set of tokens for the passed in creator
+        let tokens_for_creator_set = self.tokens_per_creator.get(&account_id);
+
+        //if there is some set of tokens, we'll return the length as a U128
+        if let Some(tokens_for_creator_set) = tokens_for_creator_set {
+            U128(tokens_for_creator_set.len() as u128)
+        } else {
+            //if there isn't a set of tokens for the passed in account ID, we'll return 0
+            U128(0)
+        }
+    }
+
+    //Query for all the tokens created by an account
+    pub fn nft_tokens_for_creator(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        //get the set of tokens for the passed in creator
+        let tokens_for_creator_set = self.tokens_per_creator.get(&account_id);
+        //if there is some set of tokens, we'll set the tokens variable equal to that set
+        let tokens = if let Some(tokens_for_creator_set) = tokens_for_creator_set {
+            tokens_for_creator_set
+        } else {
+            //if there is no set of tokens, we'll simply return an empty vector. 
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        //iterate through the keys vector
+        tokens.iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize) 
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize) 
+            //we'll map the token IDs which are strings into Json Tokens
+            .map(|token_id| self.nft_token(token_id.clone()).unwrap())
+            //since we turned the keys into an iterator, we need to turn it back into a vector to return
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    fn token_ids(tokens: Vec<JsonToken>) -> Vec<TokenId> {
+        tokens.into_iter().map(|token| token.token_id).collect()
+    }
+
+    #[test]
+    fn mint_records_the_creator_and_minter() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("owner.near").block_timestamp(3_000_000_000).build());
+        contract.nft_mint("1".to_string(), token_metadata(), account("holder.near"), None, None);
+
+        let token = contract.nft_token("1".to_string()).unwrap();
+        assert_eq!(token.creator_id, account("owner.near"));
+        assert_eq!(token.minter_id, account("owner.near"));
+        assert_eq!(token.minted_at, 3_000);
+    }
+
+    #[test]
+    fn tokens_are_listed_per_creator() {
+        let mut contract = setup_contract();
+        mint(&mut contract, "1", "alice.near");
+        mint(&mut contract, "2", "bob.near");
+        mint(&mut contract, "3", "bob.near");
+
+        assert_eq!(contract.nft_supply_for_creator(account("owner.near")), U128(3));
+        assert_eq!(contract.nft_supply_for_creator(account("alice.near")), U128(0));
+        assert_eq!(
+            token_ids(contract.nft_tokens_for_creator(account("owner.near"), Some(U128(1)), Some(1))),
+            vec!["2".to_string()]
+        );
+        assert!(contract.nft_tokens_for_creator(account("alice.near"), None, None).is_empty());
+    }
+
+    #[test]
+    fn series_copies_belong_to_the_series_creator() {
+        let mut contract = setup_contract();
+        open_minting(&mut contract);
+        testing_env!(get_context("creator.near").build());
+        contract.nft_create_series("s".to_string(), token_metadata(), None, None, Some(U128(1_000)), None);
+        testing_env!(get_context("buyer.near").build());
+        let token_id = contract.nft_mint_series("s".to_string(), account("buyer.near"));
+
+        let token = contract.nft_token(token_id.clone()).unwrap();
+        assert_eq!(token.creator_id, account("creator.near"));
+        assert_eq!(token.minter_id, account("buyer.near"));
+        assert_eq!(token_ids(contract.nft_tokens_for_creator(account("creator.near"), None, None)), vec![token_id]);
+    }
+
+    #[test]
+    fn burned_tokens_leave_the_creator_index() {
+        let mut contract = setup_contract();
+        mint(&mut contract, "1", "holder.near");
+        mint(&mut contract, "2", "holder.near");
+
+        testing_env!(get_context("holder.near").attached_deposit(1).build());
+        contract.nft_burn("1".to_string());
+        contract.nft_burn("2".to_string());
+        assert_eq!(contract.nft_supply_for_creator(account("owner.near")), U128(0));
+        assert!(contract.tokens_per_creator.get(&account("owner.near")).is_none());
+    }
+
+    #[test]
+    fn total_supply_counts_mints_copies_and_burns() {
+        let mut contract = setup_contract();
+        mint(&mut contract, "1", "holder.near");
+        mint(&mut contract, "2", "holder.near");
+        open_minting(&mut contract);
+        testing_env!(get_context("creator.near").build());
+        contract.nft_create_series("s".to_string(), token_metadata(), None, None, None, None);
+        contract.nft_mint_series("s".to_string(), account("holder.near"));
+        assert_eq!(contract.nft_total_supply(), U128(3));
+
+        testing_env!(get_context("holder.near").attached_deposit(1).build());
+        contract.nft_burn("1".to_string());
+        contract.nft_burn("s:1".to_string());
+        assert_eq!(contract.nft_total_supply(), U128(1));
+    }
 }
@@ -1,9 +1,11 @@
+use std::collections::HashMap;
 use std::fmt;
 
 use near_sdk::serde::{Deserialize, Serialize};
 
 /// Enum that represents the data type of the EventLog.
-/// The enum can either be an NftMint or an NftTransfer.
+/// The enum can either be an NftMint, an NftTransfer or an NftBurn from the NEP-171 standard,
+/// or one of the Genadrop specific events such as a MintPolicyUpdate.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "event", content = "data")]
 #[serde(rename_all = "snake_case")]
@@ -12,6 +14,17 @@ use near_sdk::serde::{Deserialize, Serialize};
 pub enum EventLogVariant {
     NftMint(Vec<NftMintLog>),
     NftTransfer(Vec<NftTransferLog>),
+    NftBurn(Vec<NftBurnLog>),
+    NftMetadataUpdate(Vec<NftMetadataUpdateLog>),
+    ContractMetadataUpdate(Vec<ContractMetadataUpdateLog>),
+    NftMetadataFreeze(Vec<NftMetadataFreezeLog>),
+    OwnershipTransferPropose(Vec<OwnershipTransferLog>),
+    OwnershipTransfer(Vec<OwnershipTransferLog>),
+    DefaultRoyaltyUpdate(Vec<DefaultRoyaltyUpdateLog>),
+    RoyaltyUpdate(Vec<RoyaltyUpdateLog>),
+    MintPolicyUpdate(Vec<MintPolicyUpdateLog>),
+    MinterAdd(Vec<MinterLog>),
+    MinterRemove(Vec<MinterLog>),
 }
 
 /// Interface to capture data about an event
@@ -78,6 +91,136 @@ pub struct NftTransferLog {
     pub memo: Option<String>,
 }
 
+/// An event log to capture token burning
+///
+/// Arguments
+/// * `owner_id`: owner of tokens to burn
+/// * `authorized_id`: approved account to burn, if applicable
+/// * `token_ids`: ["1","2"]
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftBurnLog {
+    pub owner_id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<String>,
+
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture token metadata updates (NEP-171 v1.1)
+///
+/// Arguments
+/// * `token_ids`: ["1","2"]
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMetadataUpdateLog {
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture contract metadata updates (NEP-171 v1.1)
+///
+/// Arguments
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractMetadataUpdateLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture the metadata of tokens being permanently frozen
+///
+/// Arguments
+/// * `token_ids`: ["1","2"]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMetadataFreezeLog {
+    pub token_ids: Vec<String>,
+}
+
+/// An event log to capture a proposed or completed transfer of the contract ownership
+///
+/// Arguments
+/// * `old_owner_id`: "owner.near"
+/// * `new_owner_id`: "new_owner.near", or none if a proposal was cancelled
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnershipTransferLog {
+    pub old_owner_id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_owner_id: Option<String>,
+}
+
+/// An event log to capture changes of the default royalty of the collection
+///
+/// Arguments
+/// * `royalty`: {"artist.near": 500}
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DefaultRoyaltyUpdateLog {
+    pub royalty: HashMap<String, u32>,
+}
+
+/// An event log to capture a creator redirecting or reducing their royalty on a token
+///
+/// Arguments
+/// * `token_ids`: ["1"]
+/// * `old_receiver_id`: "creator.near"
+/// * `new_receiver_id`: "new_receiver.near"
+/// * `old_amount`: royalty in basis points before the update
+/// * `new_amount`: royalty in basis points after the update
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoyaltyUpdateLog {
+    pub token_ids: Vec<String>,
+    pub old_receiver_id: String,
+    pub new_receiver_id: String,
+    pub old_amount: u32,
+    pub new_amount: u32,
+}
+
+/// An event log to capture changes of the minting policy
+///
+/// Arguments
+/// * `mode`: "owner_only", "allowlist" or "public"
+/// * `price`: price of a public mint in yoctoNEAR
+/// * `treasury_id`: account receiving the price of public mints
+/// * `max_supply`: optional cap on the total supply
+/// * `per_wallet_limit`: optional number of public mints per account
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintPolicyUpdateLog {
+    pub mode: String,
+    pub price: String,
+    pub treasury_id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_supply: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_wallet_limit: Option<u64>,
+}
+
+/// An event log to capture accounts being added to or removed from the minter allowlist
+///
+/// Arguments
+/// * `minter_id`: "minter.near"
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MinterLog {
+    pub minter_id: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,4 +278,77 @@ mod tests {
         };
         assert_eq!(expected, log.to_string());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn nep_format_burn() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_burn","data":[{"owner_id":"user1.near","authorized_id":"market.near","token_ids":["token"]}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftBurn(vec![NftBurnLog {
+                owner_id: "user1.near".to_string(),
+                authorized_id: Some("market.near".to_string()),
+                token_ids: vec!["token".to_string()],
+                memo: None,
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn format_mint_policy_update() {
+        let expected = r#"EVENT_JSON:{"standard":"genadrop_nft","version":"1.0.0","event":"mint_policy_update","data":[{"mode":"public","price":"1000","treasury_id":"treasury.near","max_supply":10000}]}"#;
+        let log = EventLog {
+            standard: "genadrop_nft".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::MintPolicyUpdate(vec![MintPolicyUpdateLog {
+                mode: "public".to_string(),
+                price: "1000".to_string(),
+                treasury_id: "treasury.near".to_string(),
+                max_supply: Some(10000),
+                per_wallet_limit: None,
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_metadata_update() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.1.0","event":"nft_metadata_update","data":[{"token_ids":["1","2"],"memo":"reveal"}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.1.0".to_string(),
+            event: EventLogVariant::NftMetadataUpdate(vec![NftMetadataUpdateLog {
+                token_ids: vec!["1".to_string(), "2".to_string()],
+                memo: Some("reveal".to_string()),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn format_metadata_freeze() {
+        let expected = r#"EVENT_JSON:{"standard":"genadrop_nft","version":"1.0.0","event":"nft_metadata_freeze","data":[{"token_ids":["1"]}]}"#;
+        let log = EventLog {
+            standard: "genadrop_nft".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftMetadataFreeze(vec![NftMetadataFreezeLog {
+                token_ids: vec!["1".to_string()],
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_contract_metadata_update() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.1.0","event":"contract_metadata_update","data":[{}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.1.0".to_string(),
+            event: EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog {
+                memo: None,
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+}
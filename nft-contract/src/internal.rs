@@ -7,35 +7,21 @@ pub(crate) fn royalty_to_payout(royalty_percentage: u32, amount_to_pay: Balance)
     U128(royalty_percentage as u128 * amount_to_pay / 10_000u128)
 }
 
+//make sure that a royalty map can be paid out: at most 6 receivers and at most 100% in total
+pub(crate) fn assert_valid_royalty(royalty: &HashMap<AccountId, u32>) {
+    //make sure that the length of the perpetual royalties is below 7 since we won't have enough GAS to pay out that many people
+    assert!(royalty.len() < 7, "Cannot add more than 6 perpetual royalty amounts");
+    //make sure that the royalties don't add up to more than 100% (10000 basis points)
+    let total: u64 = royalty.values().map(|amount| u64::from(*amount)).sum();
+    assert!(total <= 10000, "Perpetual royalties cannot exceed 10000 basis points in total");
+}
+
 //calculate how many bytes the account ID is taking up
 pub(crate) fn bytes_for_approved_account_id(account_id: &AccountId) -> u64 {
     // The extra 4 bytes are coming from Borsh serialization to store the length of the string.
     account_id.as_str().len() as u64 + 4 + size_of::<u64>() as u64
 }
 
-//refund the storage taken up by passed in approved account IDs and send the funds to the passed in account ID. 
-pub(crate) fn refund_approved_account_ids_iter<'a, I>(
-    account_id: AccountId,
-    approved_account_ids: I, //the approved account IDs must be passed in as an iterator
-) -> Promise
-where
-    I: Iterator<Item = &'a AccountId>,
-{
-    //get the storage total by going through and summing all the bytes for each approved account IDs
-    let storage_released: u64 = approved_account_ids.map(bytes_for_approved_account_id).sum();
-    //transfer the account the storage that is released
-    Promise::new(account_id).transfer(Balance::from(storage_released) * env::storage_byte_cost())
-}
-
-//refund a map of approved account IDs and send the funds to the passed in account ID
-pub(crate) fn refund_approved_account_ids(
-    account_id: AccountId,
-    approved_account_ids: &HashMap<AccountId, u64>,
-) -> Promise {
-    //call the refund_approved_account_ids_iter with the approved account IDs as keys
-    refund_approved_account_ids_iter(account_id, approved_account_ids.keys())
-}
-
 //used to generate a unique prefix in our storage collections (this is to avoid data collisions)
 pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     //get the default hash
@@ -45,6 +31,15 @@ pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     hash
 }
 
+//used to generate a unique prefix for collections keyed by a string, such as a series ID
+pub(crate) fn hash_string(value: &str) -> CryptoHash {
+    //get the default hash
+    let mut hash = CryptoHash::default();
+    //we hash the string and return it
+    hash.copy_from_slice(&env::sha256(value.as_bytes()));
+    hash
+}
+
 //used to make sure the user attached exactly 1 yoctoNEAR
 pub(crate) fn assert_one_yocto() {
     assert_eq!(
@@ -62,30 +57,105 @@ pub(crate) fn assert_at_least_one_yocto() {
     )
 }
 
-//refund the initial deposit based on the amount of storage that was used up
-pub(crate) fn refund_deposit(storage_used: u64) {
-    //get how much it would cost to store the information
-    let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
-    //get the attached deposit
-    let attached_deposit = env::attached_deposit();
-
-    //make sure that the attached deposit is greater than or equal to the required cost
-    assert!(
-        required_cost <= attached_deposit,
-        "Must attach {} yoctoNEAR to cover storage",
-        required_cost,
-    );
+//verify an ed25519 signature with the host function of the runtime, which near_sdk::env doesn't wrap yet
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn ed25519_verify(signature: &[u8; 64], message: &[u8], public_key: &[u8; 32]) -> bool {
+    unsafe {
+        near_sdk::sys::ed25519_verify(
+            signature.len() as u64,
+            signature.as_ptr() as u64,
+            message.len() as u64,
+            message.as_ptr() as u64,
+            public_key.len() as u64,
+            public_key.as_ptr() as u64,
+        ) == 1
+    }
+}
 
-    //get the refund amount from the attached deposit - required cost
-    let refund = attached_deposit - required_cost;
+//the mocked blockchain of the unit tests doesn't implement the host function, so we verify with ed25519-dalek instead
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn ed25519_verify(signature: &[u8; 64], message: &[u8], public_key: &[u8; 32]) -> bool {
+    use ed25519_dalek::{PublicKey, Signature, Verifier};
 
-    //if the refund is greater than 1 yocto NEAR, we refund the predecessor that amount
-    if refund > 1 {
-        Promise::new(env::predecessor_account_id()).transfer(refund);
+    match (PublicKey::from_bytes(public_key), Signature::from_bytes(signature)) {
+        (Ok(public_key), Ok(signature)) => public_key.verify(message, &signature).is_ok(),
+        _ => false,
     }
 }
 
 impl Contract {
+    //refund the storage taken up by passed in approved account IDs and give the funds to the passed in account ID.
+    pub(crate) fn refund_approved_account_ids_iter<'a, I>(
+        &mut self,
+        account_id: AccountId,
+        approved_account_ids: I, //the approved account IDs must be passed in as an iterator
+    ) where
+        I: Iterator<Item = &'a AccountId>,
+    {
+        //get the storage total by going through and summing all the bytes for each approved account IDs
+        let storage_released: u64 = approved_account_ids.map(bytes_for_approved_account_id).sum();
+        //give the account the storage that is released
+        self.internal_release_storage(account_id, Balance::from(storage_released) * env::storage_byte_cost());
+    }
+
+    //refund a map of approved account IDs and give the funds to the passed in account ID
+    pub(crate) fn refund_approved_account_ids(
+        &mut self,
+        account_id: AccountId,
+        approved_account_ids: &HashMap<AccountId, u64>,
+    ) {
+        //call the refund_approved_account_ids_iter with the approved account IDs as keys
+        self.refund_approved_account_ids_iter(account_id, approved_account_ids.keys())
+    }
+
+    //charge the predecessor for the amount of storage that was used up, and refund the rest of the attached deposit
+    pub(crate) fn refund_deposit(&mut self, storage_used: u64) {
+        //get how much it would cost to store the information
+        let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
+        //pay from the prepaid storage balance first and the attached deposit for the rest
+        self.internal_charge_storage(required_cost, 0);
+    }
+
+    //charge the predecessor for the amount of storage that was used up and a price that is paid to the receiver
+    pub(crate) fn refund_deposit_with_price(&mut self, storage_used: u64, price: Balance, price_receiver_id: AccountId) {
+        //get how much it would cost to store the information
+        let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
+        //pay the storage from the prepaid storage balance first, and the price and the rest of the storage from the attached deposit
+        self.internal_charge_storage(required_cost, price);
+
+        //pay the price to the receiver
+        if price > 0 {
+            Promise::new(price_receiver_id).transfer(price);
+        }
+    }
+
+    //charge the predecessor for the storage used since the initial storage usage, or give back the storage that was released
+    //to the account that paid for it
+    pub(crate) fn refund_storage_change(&mut self, initial_storage_usage: u64, payer_id: AccountId) {
+        let final_storage_usage = env::storage_usage();
+        if final_storage_usage > initial_storage_usage {
+            //charge the predecessor for the storage and refund any excess deposit
+            self.refund_deposit(final_storage_usage - initial_storage_usage);
+        } else {
+            //give back the released storage and refund the attached deposit
+            let released_storage_in_bytes = initial_storage_usage - final_storage_usage;
+            self.internal_release_storage(
+                payer_id,
+                Balance::from(released_storage_in_bytes) * env::storage_byte_cost(),
+            );
+            self.refund_deposit(0);
+        }
+    }
+
+    //used to make sure that only the contract owner can call a method
+    pub(crate) fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the contract owner can call this method",
+        )
+    }
+
     //add a token to the set of tokens an owner has
     pub(crate) fn internal_add_token_to_owner(
         &mut self,
@@ -137,6 +207,48 @@ impl Contract {
         }
     }
 
+    //add a token to the set of tokens a creator has created
+    pub(crate) fn internal_add_token_to_creator(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &TokenId,
+    ) {
+        //get the set of tokens for the given account
+        let mut tokens_set = self.tokens_per_creator.get(account_id).unwrap_or_else(|| {
+            //if the account hasn't created any tokens, we create a new unordered set
+            UnorderedSet::new(
+                StorageKey::TokensPerCreatorInner {
+                    //we get a new unique prefix for the collection
+                    account_id_hash: hash_account_id(account_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+
+        //we insert the token ID into the set and the set for the given account ID
+        tokens_set.insert(token_id);
+        self.tokens_per_creator.insert(account_id, &tokens_set);
+    }
+
+    //remove a token from the set of tokens a creator has created
+    pub(crate) fn internal_remove_token_from_creator(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &TokenId,
+    ) {
+        if let Some(mut tokens_set) = self.tokens_per_creator.get(account_id) {
+            tokens_set.remove(token_id);
+
+            //if the token set is now empty, we remove the creator from the tokens_per_creator collection
+            if tokens_set.is_empty() {
+                self.tokens_per_creator.remove(account_id);
+            } else {
+                self.tokens_per_creator.insert(account_id, &tokens_set);
+            }
+        }
+    }
+
     //transfers the NFT to the receiver_id (internal method and can't be called directly via CLI).
     pub(crate) fn internal_transfer(
         &mut self,
@@ -150,6 +262,11 @@ impl Contract {
         //get the token object by passing in the token_id
         let token = self.tokens_by_id.get(token_id).expect("No token");
 
+        //soulbound tokens can only be moved by the contract owner through a recovery transfer
+        assert!(!token.soulbound, "Soulbound tokens cannot be transferred");
+        //make sure that the token is within its validity window if the validity is enforced
+        self.internal_assert_valid(token_id, &token);
+
         //if the sender doesn't equal the owner, we check if the sender is in the approval list
 		if sender_id != &token.owner_id {
 			//if the token's approved account IDs doesn't contain the sender, we panic
@@ -175,6 +292,27 @@ impl Contract {
 			}
 		}
 
+        // Default the authorized ID to be None for the logs.
+        let mut authorized_id = None;
+        //if the approval ID was provided, set the authorized ID equal to the sender
+        if approval_id.is_some() {
+            authorized_id = Some(sender_id.to_string());
+        }
+
+        //move the token to the receiver
+        self.internal_move_token(token, receiver_id, token_id, authorized_id, memo)
+    }
+
+    //moves the token to the receiver_id without checking who is allowed to do so, and returns the previous token object
+    pub(crate) fn internal_move_token(
+        &mut self,
+        token: Token,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
+        //the account that moved the token on behalf of the owner, for the logs
+        authorized_id: Option<String>,
+        memo: Option<String>,
+    ) -> Token {
         //we make sure that the sender isn't sending the token to themselves
         assert_ne!(
             &token.owner_id, receiver_id,
@@ -194,6 +332,14 @@ impl Contract {
             next_approval_id: token.next_approval_id,
             //we copy over the royalties from the previous token
             royalty: token.royalty.clone(),
+            //we copy over the series from the previous token
+            series_id: token.series_id.clone(),
+            //we copy over the creator, minter and mint time from the previous token
+            creator_id: token.creator_id.clone(),
+            minter_id: token.minter_id.clone(),
+            minted_at: token.minted_at,
+            //we copy over the soulbound flag from the previous token
+            soulbound: token.soulbound,
         };
         //insert that new token into the tokens_by_id, replacing the old entry 
         self.tokens_by_id.insert(token_id, &new_token);
@@ -203,13 +349,6 @@ impl Contract {
             env::log_str(&format!("Memo: {}", memo).to_string());
         }
 
-        // Default the authorized ID to be None for the logs.
-        let mut authorized_id = None;
-        //if the approval ID was provided, set the authorized ID equal to the sender
-        if approval_id.is_some() {
-            authorized_id = Some(sender_id.to_string());
-        }
-
         // Construct the transfer log as per the events standard.
         let nft_transfer_log: EventLog = EventLog {
             // Standard name ("nep171").
@@ -237,4 +376,53 @@ impl Contract {
         //return the previous token object that was transferred.
         token
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn valid_royalty_of_exactly_10000() {
+        assert_valid_royalty(&royalty(&[("artist.near", 6000), ("gallery.near", 4000)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Perpetual royalties cannot exceed 10000 basis points in total")]
+    fn invalid_royalty_above_10000() {
+        assert_valid_royalty(&royalty(&[("artist.near", 6000), ("gallery.near", 4001)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Perpetual royalties cannot exceed 10000 basis points in total")]
+    fn invalid_royalty_that_overflows_u32() {
+        assert_valid_royalty(&royalty(&[("artist.near", u32::MAX), ("gallery.near", 1)]));
+    }
+
+    #[test]
+    fn valid_royalty_with_6_receivers() {
+        assert_valid_royalty(&royalty(&[
+            ("a.near", 100),
+            ("b.near", 100),
+            ("c.near", 100),
+            ("d.near", 100),
+            ("e.near", 100),
+            ("f.near", 100),
+        ]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot add more than 6 perpetual royalty amounts")]
+    fn invalid_royalty_with_7_receivers() {
+        assert_valid_royalty(&royalty(&[
+            ("a.near", 100),
+            ("b.near", 100),
+            ("c.near", 100),
+            ("d.near", 100),
+            ("e.near", 100),
+            ("f.near", 100),
+            ("g.near", 100),
+        ]));
+    }
+}
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedMap, UnorderedSet};
 use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
     env, near_bindgen, AccountId, Balance, CryptoHash, PanicOnDefault, Promise, PromiseOrValue,
+    PublicKey,
 };
 
 use crate::internal::*;
@@ -14,20 +15,43 @@ pub use crate::nft_core::*;
 pub use crate::approval::*;
 pub use crate::royalty::*;
 pub use crate::events::*;
+pub use crate::series::*;
+pub use crate::mint_policy::*;
+pub use crate::voucher::*;
+pub use crate::metadata_update::*;
+pub use crate::validity::*;
+pub use crate::storage::*;
 
 mod internal;
 mod approval; 
+mod burn;
 mod enumeration; 
 mod metadata; 
 mod mint; 
 mod nft_core; 
 mod royalty; 
 mod events;
+mod series;
+mod mint_policy;
+mod voucher;
+mod metadata_update;
+mod owner;
+mod soulbound;
+mod validity;
+mod storage;
+mod migrate;
+#[cfg(test)]
+mod test_utils;
 
 /// This spec can be treated like a version of the standard.
 pub const NFT_METADATA_SPEC: &str = "nft-1.0.0";
 /// This is the name of the NFT standard we're using
 pub const NFT_STANDARD_NAME: &str = "nep171";
+/// This is the version of the NFT standard that introduced the metadata update events
+pub const NFT_METADATA_UPDATE_VERSION: &str = "1.1.0";
+/// This is the name and version used for events that are not part of the NFT standard
+pub const GENADROP_STANDARD_NAME: &str = "genadrop_nft";
+pub const GENADROP_EVENT_VERSION: &str = "1.0.0";
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -35,9 +59,15 @@ pub struct Contract {
     //contract owner
     pub owner_id: AccountId,
 
+    //the account that was proposed as the new owner and has to accept the ownership
+    pub pending_owner_id: Option<AccountId>,
+
     //keeps track of all the token IDs for a given account
     pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
 
+    //keeps track of all the token IDs created by a given account
+    pub tokens_per_creator: LookupMap<AccountId, UnorderedSet<TokenId>>,
+
     //keeps track of the token struct for a given token ID
     pub tokens_by_id: LookupMap<TokenId, Token>,
 
@@ -46,6 +76,54 @@ pub struct Contract {
 
     //keeps track of the metadata for the contract
     pub metadata: LazyOption<NFTContractMetadata>,
+
+    //keeps track of the series (editions) for a given series ID
+    pub series_by_id: UnorderedMap<SeriesId, Series>,
+
+    //keeps track of all the token IDs minted from a given series
+    pub tokens_per_type: LookupMap<SeriesId, UnorderedSet<TokenId>>,
+
+    //keeps track of the series that can no longer be minted from
+    pub token_types_locked: UnorderedSet<SeriesId>,
+
+    //the number of tokens that currently exist, including the copies of series
+    pub total_supply: u64,
+
+    //the policy that decides who can mint and at which price
+    pub mint_policy: MintPolicy,
+
+    //keeps track of the accounts that are allowed to mint
+    pub minters: UnorderedSet<AccountId>,
+
+    //keeps track of the number of public mints for a given account
+    pub mints_per_account: LookupMap<AccountId, u64>,
+
+    //the ed25519 key used by the owner to sign mint vouchers
+    pub voucher_public_key: Option<PublicKey>,
+
+    //keeps track of the token IDs whose vouchers have been redeemed
+    pub redeemed_vouchers: LookupSet<TokenId>,
+
+    //keeps track of the tokens whose metadata can be updated by their owner instead of their creator
+    pub metadata_updaters: LookupMap<TokenId, MetadataUpdater>,
+
+    //keeps track of the tokens whose metadata can no longer be updated
+    pub frozen_tokens: LookupSet<TokenId>,
+
+    //whether the metadata of all tokens has been frozen
+    pub token_metadata_frozen: bool,
+
+    //the royalty applied to tokens that are minted without perpetual royalties
+    pub default_royalty: HashMap<AccountId, u32>,
+
+    //the account that receives the smallest royalty shares of payouts with too many receivers
+    pub payout_collector_id: Option<AccountId>,
+
+    //how the validity window (starts_at and expires_at) of tokens is enforced
+    pub validity_mode: ValidityMode,
+
+    //keeps track of the prepaid storage balance (NEP-145) for a given account
+    pub storage_deposits: LookupMap<AccountId, Balance>,
 }
 
 /// Helper structure for keys of the persistent collections.
@@ -59,6 +137,15 @@ pub enum StorageKey {
     TokensPerType,
     TokensPerTypeInner { token_type_hash: CryptoHash },
     TokenTypesLocked,
+    SeriesById,
+    Minters,
+    MintsPerAccount,
+    RedeemedVouchers,
+    MetadataUpdaters,
+    FrozenTokens,
+    TokensPerCreator,
+    TokensPerCreatorInner { account_id_hash: CryptoHash },
+    StorageDeposits,
 }
 
 #[near_bindgen]
@@ -96,16 +183,43 @@ impl Contract {
         let this = Self {
             //Storage keys are simply the prefixes used for the collections. This helps avoid data collision
             tokens_per_owner: LookupMap::new(StorageKey::TokensPerOwner.try_to_vec().unwrap()),
+            tokens_per_creator: LookupMap::new(StorageKey::TokensPerCreator.try_to_vec().unwrap()),
             tokens_by_id: LookupMap::new(StorageKey::TokensById.try_to_vec().unwrap()),
             token_metadata_by_id: UnorderedMap::new(
                 StorageKey::TokenMetadataById.try_to_vec().unwrap(),
             ),
             //set the owner_id field equal to the passed in owner_id. 
-            owner_id,
+            owner_id: owner_id.clone(),
+            pending_owner_id: None,
             metadata: LazyOption::new(
                 StorageKey::NFTContractMetadata.try_to_vec().unwrap(),
                 Some(&metadata),
             ),
+            series_by_id: UnorderedMap::new(StorageKey::SeriesById.try_to_vec().unwrap()),
+            tokens_per_type: LookupMap::new(StorageKey::TokensPerType.try_to_vec().unwrap()),
+            token_types_locked: UnorderedSet::new(
+                StorageKey::TokenTypesLocked.try_to_vec().unwrap(),
+            ),
+            total_supply: 0,
+            //only the owner can mint until the policy is changed
+            mint_policy: MintPolicy {
+                mode: MintMode::OwnerOnly,
+                price: U128(0),
+                treasury_id: owner_id,
+                max_supply: None,
+                per_wallet_limit: None,
+            },
+            minters: UnorderedSet::new(StorageKey::Minters.try_to_vec().unwrap()),
+            mints_per_account: LookupMap::new(StorageKey::MintsPerAccount.try_to_vec().unwrap()),
+            voucher_public_key: None,
+            redeemed_vouchers: LookupSet::new(StorageKey::RedeemedVouchers.try_to_vec().unwrap()),
+            metadata_updaters: LookupMap::new(StorageKey::MetadataUpdaters.try_to_vec().unwrap()),
+            frozen_tokens: LookupSet::new(StorageKey::FrozenTokens.try_to_vec().unwrap()),
+            token_metadata_frozen: false,
+            default_royalty: HashMap::new(),
+            payout_collector_id: None,
+            validity_mode: ValidityMode::Disabled,
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits.try_to_vec().unwrap()),
         };
 
         //return the Contract object
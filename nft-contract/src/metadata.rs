@@ -46,6 +46,16 @@ pub struct Token {
     pub next_approval_id: u64,
     //keep track of the royalty percentages for the token in a hash map
     pub royalty: HashMap<AccountId, u32>,
+    //the series the token was minted from. Such tokens share the metadata of their series
+    pub series_id: Option<SeriesId>,
+    //the account that created the token
+    pub creator_id: AccountId,
+    //the account that minted the token
+    pub minter_id: AccountId,
+    //when the token was minted, Unix epoch in milliseconds
+    pub minted_at: u64,
+    //soulbound tokens can't be transferred or approved, except for a recovery transfer by the contract owner
+    pub soulbound: bool,
 }
 
 //The Json token is what will be returned from view calls. 
@@ -62,6 +72,14 @@ pub struct JsonToken {
     pub approved_account_ids: HashMap<AccountId, u64>,
     //keep track of the royalty percentages for the token in a hash map
     pub royalty: HashMap<AccountId, u32>,
+    //whether the token is soulbound (non-transferable)
+    pub soulbound: bool,
+    //the account that created the token
+    pub creator_id: AccountId,
+    //the account that minted the token
+    pub minter_id: AccountId,
+    //when the token was minted, Unix epoch in milliseconds
+    pub minted_at: u64,
 }
 
 pub trait NonFungibleTokenMetadata {
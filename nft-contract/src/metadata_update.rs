@@ -0,0 +1,327 @@
+use crate::*;
+
+//who is allowed to update the metadata of a token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataUpdater {
+    //the account that created the token (default)
+    Creator,
+    //the current owner of the token
+    Owner,
+}
+
+#[near_bindgen]
+impl Contract {
+    //update the metadata of a token. Only the authorized updater can call this, as long as the metadata isn't frozen.
+    #[payable]
+    pub fn nft_update_metadata(&mut self, token_id: TokenId, metadata: TokenMetadata, memo: Option<String>) {
+        //assert that the user attached at least 1 yoctoNEAR for security reasons and to pay for storage
+        assert_at_least_one_yocto();
+
+        //get the token and make sure that the caller can update its metadata
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        self.internal_assert_metadata_updater(&token_id, &token);
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //the time the token was issued can't change and the time of the update is set automatically
+        let old_metadata = self.token_metadata_by_id.get(&token_id).unwrap();
+        let mut metadata = metadata;
+        metadata.issued_at = old_metadata.issued_at;
+        metadata.updated_at = Some(env::block_timestamp() / 1_000_000);
+        self.token_metadata_by_id.insert(&token_id, &metadata);
+
+        self.internal_log_metadata_update(vec![token_id], memo);
+
+        //if the metadata grew, the caller pays for the storage. Otherwise, the creator who paid for the minted metadata gets back the released storage
+        self.refund_storage_change(initial_storage_usage, token.creator_id);
+    }
+
+    //change who can update the metadata of a token. Only the current authorized updater can call this.
+    #[payable]
+    pub fn nft_set_metadata_updater(&mut self, token_id: TokenId, updater: MetadataUpdater) {
+        //assert that the user attached at least 1 yoctoNEAR for security reasons and to pay for storage
+        assert_at_least_one_yocto();
+
+        //get the token and make sure that the caller can update its metadata
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        self.internal_assert_metadata_updater(&token_id, &token);
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //only tokens that aren't updated by their creator are stored
+        match updater {
+            MetadataUpdater::Creator => self.metadata_updaters.remove(&token_id),
+            MetadataUpdater::Owner => self.metadata_updaters.insert(&token_id, &updater),
+        };
+
+        //the caller pays for the storage of the setting, and the creator who set it gets it back when going back to the creator
+        self.refund_storage_change(initial_storage_usage, token.creator_id);
+    }
+
+    //permanently freeze the metadata of a token. Only the authorized updater can call this.
+    #[payable]
+    pub fn nft_freeze_metadata(&mut self, token_id: TokenId) {
+        //assert that the user attached at least 1 yoctoNEAR for security reasons and to pay for storage
+        assert_at_least_one_yocto();
+
+        //get the token and make sure that the caller can update its metadata
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        self.internal_assert_metadata_updater(&token_id, &token);
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        self.frozen_tokens.insert(&token_id);
+
+        // Construct the metadata freeze log. The metadata itself didn't change.
+        let nft_metadata_freeze_log: EventLog = EventLog {
+            // Standard name ("genadrop_nft").
+            standard: GENADROP_STANDARD_NAME.to_string(),
+            // Version of the events ("1.0.0").
+            version: GENADROP_EVENT_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftMetadataFreeze(vec![NftMetadataFreezeLog {
+                // Vector of token IDs whose metadata was frozen.
+                token_ids: vec![token_id],
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_metadata_freeze_log.to_string());
+
+        //the caller pays for the storage of the frozen flag
+        self.refund_storage_change(initial_storage_usage, token.creator_id);
+    }
+
+    //permanently freeze the metadata of all tokens on the contract. Only the owner can call this.
+    #[payable]
+    pub fn freeze_all_token_metadata(&mut self) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(!self.token_metadata_frozen, "Token metadata is already frozen");
+        self.token_metadata_frozen = true;
+        self.internal_log_contract_metadata_update(Some("token metadata frozen".to_string()));
+    }
+
+    //get who can update the metadata of a token
+    pub fn nft_metadata_updater(&self, token_id: TokenId) -> MetadataUpdater {
+        self.metadata_updaters
+            .get(&token_id)
+            .unwrap_or(MetadataUpdater::Creator)
+    }
+
+    //check whether the metadata of a token is frozen, either for the token itself or for the whole contract
+    pub fn nft_is_metadata_frozen(&self, token_id: TokenId) -> bool {
+        self.token_metadata_frozen || self.frozen_tokens.contains(&token_id)
+    }
+}
+
+impl Contract {
+    //make sure that the caller is allowed to update the metadata of a token and that it isn't frozen
+    pub(crate) fn internal_assert_metadata_updater(&self, token_id: &TokenId, token: &Token) {
+        //tokens of a series share its metadata, which can't be updated per token
+        assert!(
+            token.series_id.is_none(),
+            "Tokens of a series share the metadata of the series"
+        );
+        assert!(
+            !self.nft_is_metadata_frozen(token_id.clone()),
+            "Token metadata is frozen"
+        );
+
+        //get the account that is allowed to update the metadata
+        let updater_id = match self.nft_metadata_updater(token_id.clone()) {
+            MetadataUpdater::Creator => &token.creator_id,
+            MetadataUpdater::Owner => &token.owner_id,
+        };
+        assert_eq!(
+            &env::predecessor_account_id(),
+            updater_id,
+            "Only the authorized updater can change the metadata"
+        );
+    }
+
+    //log an update of the metadata of some tokens
+    pub(crate) fn internal_log_metadata_update(&self, token_ids: Vec<TokenId>, memo: Option<String>) {
+        // Construct the metadata update log as per the events standard.
+        let nft_metadata_update_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard that introduced the event ("1.1.0").
+            version: NFT_METADATA_UPDATE_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftMetadataUpdate(vec![NftMetadataUpdateLog {
+                // Vector of token IDs whose metadata was updated.
+                token_ids,
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_metadata_update_log.to_string());
+    }
+
+    //log an update of the contract metadata
+    pub(crate) fn internal_log_contract_metadata_update(&self, memo: Option<String>) {
+        // Construct the contract metadata update log as per the events standard.
+        let contract_metadata_update_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard that introduced the event ("1.1.0").
+            version: NFT_METADATA_UPDATE_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog {
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&contract_metadata_update_log.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+
+    fn titled(title: &str) -> TokenMetadata {
+        let mut metadata = token_metadata();
+        metadata.title = Some(title.to_string());
+        metadata
+    }
+
+    //token "1" created by owner.near and held by holder.near, issued at 5 ms
+    fn setup_token() -> Contract {
+        let mut contract = setup_contract();
+        let mut metadata = token_metadata();
+        metadata.issued_at = Some(5);
+        contract.nft_mint("1".to_string(), metadata, account("holder.near"), None, None);
+        contract
+    }
+
+    fn update(contract: &mut Contract, caller: &str, title: &str) {
+        testing_env!(get_context(caller).block_timestamp(7_000_000).build());
+        contract.nft_update_metadata("1".to_string(), titled(title), None);
+    }
+
+    #[test]
+    fn creator_updates_the_metadata() {
+        let mut contract = setup_token();
+        update(&mut contract, "owner.near", "Updated");
+
+        let metadata = contract.nft_token("1".to_string()).unwrap().metadata;
+        assert_eq!(metadata.title, Some("Updated".to_string()));
+        assert_eq!(metadata.issued_at, Some(5));
+        assert_eq!(metadata.updated_at, Some(7));
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep171","version":"1.1.0","event":"nft_metadata_update","data":[{"token_ids":["1"]}]}"#]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the authorized updater can change the metadata")]
+    fn holder_cannot_update_by_default() {
+        let mut contract = setup_token();
+        update(&mut contract, "holder.near", "Updated");
+    }
+
+    #[test]
+    fn creator_hands_updates_to_the_holder() {
+        let mut contract = setup_token();
+        testing_env!(get_context("owner.near").build());
+        contract.nft_set_metadata_updater("1".to_string(), MetadataUpdater::Owner);
+        assert!(contract.nft_metadata_updater("1".to_string()) == MetadataUpdater::Owner);
+
+        update(&mut contract, "holder.near", "Updated");
+        assert_eq!(
+            contract.nft_token("1".to_string()).unwrap().metadata.title,
+            Some("Updated".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Token metadata is frozen")]
+    fn frozen_token_cannot_be_updated() {
+        let mut contract = setup_token();
+        testing_env!(get_context("owner.near").build());
+        contract.nft_freeze_metadata("1".to_string());
+        assert!(contract.nft_is_metadata_frozen("1".to_string()));
+        update(&mut contract, "owner.near", "Updated");
+    }
+
+    #[test]
+    #[should_panic(expected = "Token metadata is frozen")]
+    fn frozen_contract_cannot_be_updated() {
+        let mut contract = setup_token();
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.freeze_all_token_metadata();
+        assert!(get_logs()[0].contains(r#""event":"contract_metadata_update""#));
+        update(&mut contract, "owner.near", "Updated");
+    }
+
+    #[test]
+    #[should_panic(expected = "Tokens of a series share the metadata of the series")]
+    fn series_copies_cannot_be_updated() {
+        let mut contract = setup_contract();
+        contract.nft_create_series("s".to_string(), token_metadata(), None, None, None, None);
+        contract.nft_mint_series("s".to_string(), account("holder.near"));
+
+        testing_env!(get_context("owner.near").build());
+        contract.nft_update_metadata("s:1".to_string(), titled("Updated"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn growing_metadata_must_be_paid() {
+        let mut contract = setup_token();
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.nft_update_metadata("1".to_string(), titled("A much longer title"), None);
+    }
+
+    #[test]
+    fn released_storage_goes_to_the_creator() {
+        let mut contract = setup_token();
+        update(&mut contract, "owner.near", "A much longer title");
+        testing_env!(get_context("owner.near").build());
+        contract.nft_set_metadata_updater("1".to_string(), MetadataUpdater::Owner);
+        contract.storage_deposit(None, None);
+        testing_env!(get_context("holder.near").build());
+        contract.storage_deposit(None, None);
+        let creator_balance = contract.storage_balance_of(account("owner.near")).unwrap().total.0;
+        let holder_balance = contract.storage_balance_of(account("holder.near")).unwrap().total.0;
+
+        update(&mut contract, "holder.near", "Short");
+        assert!(contract.storage_balance_of(account("owner.near")).unwrap().total.0 > creator_balance);
+        assert_eq!(contract.storage_balance_of(account("holder.near")).unwrap().total.0, holder_balance);
+    }
+
+    #[test]
+    fn freezing_is_logged_separately() {
+        let mut contract = setup_token();
+        testing_env!(get_context("owner.near").build());
+        contract.nft_freeze_metadata("1".to_string());
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"genadrop_nft","version":"1.0.0","event":"nft_metadata_freeze","data":[{"token_ids":["1"]}]}"#]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover storage")]
+    fn metadata_updater_storage_must_be_paid() {
+        let mut contract = setup_token();
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.nft_set_metadata_updater("1".to_string(), MetadataUpdater::Owner);
+    }
+}
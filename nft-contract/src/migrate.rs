@@ -0,0 +1,125 @@
+use crate::*;
+
+//the contract as it was stored before series, minting policies, vouchers, metadata updates, royalties, soulbound tokens,
+//validity windows, creators and storage balances were introduced
+#[derive(BorshDeserialize)]
+pub struct OldContract {
+    pub owner_id: AccountId,
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub tokens_by_id: LookupMap<TokenId, OldToken>,
+    pub token_metadata_by_id: UnorderedMap<TokenId, TokenMetadata>,
+    pub metadata: LazyOption<NFTContractMetadata>,
+}
+
+//the token as it was stored before the upgrade
+#[derive(BorshDeserialize)]
+pub struct OldToken {
+    pub owner_id: AccountId,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    pub next_approval_id: u64,
+    pub royalty: HashMap<AccountId, u32>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        upgrades the contract state to the current layout. This has to be called right after deploying the new code,
+        ideally in the same batch transaction, and can only be called by the contract account itself.
+        The tokens are upgraded afterwards with migrate_tokens.
+    */
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: OldContract = env::state_read().expect("No state");
+        //every existing token has its own metadata
+        let total_supply = old.token_metadata_by_id.len();
+
+        Self {
+            //the existing collections keep their storage prefixes
+            owner_id: old.owner_id.clone(),
+            pending_owner_id: None,
+            tokens_per_owner: old.tokens_per_owner,
+            tokens_per_creator: LookupMap::new(StorageKey::TokensPerCreator.try_to_vec().unwrap()),
+            tokens_by_id: LookupMap::new(StorageKey::TokensById.try_to_vec().unwrap()),
+            token_metadata_by_id: old.token_metadata_by_id,
+            metadata: old.metadata,
+            series_by_id: UnorderedMap::new(StorageKey::SeriesById.try_to_vec().unwrap()),
+            tokens_per_type: LookupMap::new(StorageKey::TokensPerType.try_to_vec().unwrap()),
+            token_types_locked: UnorderedSet::new(
+                StorageKey::TokenTypesLocked.try_to_vec().unwrap(),
+            ),
+            total_supply,
+            //only the owner can mint until the policy is changed
+            mint_policy: MintPolicy {
+                mode: MintMode::OwnerOnly,
+                price: U128(0),
+                treasury_id: old.owner_id,
+                max_supply: None,
+                per_wallet_limit: None,
+            },
+            minters: UnorderedSet::new(StorageKey::Minters.try_to_vec().unwrap()),
+            mints_per_account: LookupMap::new(StorageKey::MintsPerAccount.try_to_vec().unwrap()),
+            voucher_public_key: None,
+            redeemed_vouchers: LookupSet::new(StorageKey::RedeemedVouchers.try_to_vec().unwrap()),
+            metadata_updaters: LookupMap::new(StorageKey::MetadataUpdaters.try_to_vec().unwrap()),
+            frozen_tokens: LookupSet::new(StorageKey::FrozenTokens.try_to_vec().unwrap()),
+            token_metadata_frozen: false,
+            default_royalty: HashMap::new(),
+            payout_collector_id: None,
+            validity_mode: ValidityMode::Disabled,
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits.try_to_vec().unwrap()),
+        }
+    }
+
+    /*
+        upgrades the tokens that were minted before the upgrade, going through the token metadata using pagination.
+        Tokens that have already been upgraded are skipped, so the owner can simply call this until every index has been covered.
+        Old tokens don't know who created them, so they are attributed to the contract owner, and their mint time is taken from issued_at.
+        Returns the number of upgraded tokens.
+    */
+    #[payable]
+    pub fn migrate_tokens(&mut self, from_index: Option<U128>, limit: Option<u64>) -> u64 {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+        let token_ids: Vec<TokenId> = self
+            .token_metadata_by_id
+            .keys()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect();
+
+        let tokens_by_id_prefix = StorageKey::TokensById.try_to_vec().unwrap();
+        let mut migrated = 0;
+        for token_id in token_ids {
+            //the token is read as raw bytes, since it can't be deserialized as a Token if it hasn't been upgraded yet
+            let key = [tokens_by_id_prefix.clone(), token_id.try_to_vec().unwrap()].concat();
+            let old_token = match env::storage_read(&key).map(|bytes| OldToken::try_from_slice(&bytes)) {
+                Some(Ok(old_token)) => old_token,
+                //the token has already been upgraded
+                _ => continue,
+            };
+
+            let metadata = self.token_metadata_by_id.get(&token_id).unwrap();
+            let token = Token {
+                owner_id: old_token.owner_id,
+                approved_account_ids: old_token.approved_account_ids,
+                next_approval_id: old_token.next_approval_id,
+                royalty: old_token.royalty,
+                series_id: None,
+                creator_id: self.owner_id.clone(),
+                minter_id: self.owner_id.clone(),
+                minted_at: metadata.issued_at.unwrap_or(0),
+                soulbound: false,
+            };
+            self.tokens_by_id.insert(&token_id, &token);
+            self.internal_add_token_to_creator(&token.creator_id, &token_id);
+            migrated += 1;
+        }
+
+        migrated
+    }
+}
@@ -1,5 +1,8 @@
 use crate::*;
 
+//a single token to mint in a batch: the token ID, metadata, receiver and optional perpetual royalties
+pub type BatchMintItem = (TokenId, TokenMetadata, AccountId, Option<HashMap<AccountId, u32>>);
+
 #[near_bindgen]
 impl Contract {
     #[payable]
@@ -10,25 +13,134 @@ impl Contract {
         receiver_id: AccountId,
         //we add an optional parameter for perpetual royalties
         perpetual_royalties: Option<HashMap<AccountId, u32>>,
+        //an optional flag to make the token soulbound (non-transferable)
+        soulbound: Option<bool>,
     ) {
         //measure the initial storage being used on the contract
         let initial_storage_usage = env::storage_usage();
 
-        // create a royalty map to store in the token
-        let mut royalty = HashMap::new();
+        //make sure that the minting policy allows the caller to mint and get the price they have to pay
+        let price = self.internal_authorize_mint(1);
+
+        //the memo of the mint log is the reference of the metadata
+        let memo = metadata.reference.clone();
+
+        //mint the token. The caller is its creator
+        let token = self.internal_mint(
+            &token_id,
+            metadata,
+            receiver_id,
+            perpetual_royalties,
+            env::predecessor_account_id(),
+            soulbound.unwrap_or(false),
+        );
+
+        // Construct the mint log as per the events standard.
+        let nft_mint_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftMint(vec![NftMintLog {
+                // Owner of the token.
+                owner_id: token.owner_id.to_string(),
+                // Vector of token IDs that were minted.
+                token_ids: vec![token_id.to_string()],
+                // An optional memo to include.
+                memo,
+            }]),
+        };
 
-        // if perpetual royalties were passed into the function: 
-        if let Some(perpetual_royalties) = perpetual_royalties {
-            //make sure that the length of the perpetual royalties is below 7 since we won't have enough GAS to pay out that many people
-            assert!(perpetual_royalties.len() < 7, "Cannot add more than 6 perpetual royalty amounts");
+        // Log the serialized json.
+        env::log_str(&nft_mint_log.to_string());
 
-            //iterate through the perpetual royalties and insert the account and amount in the royalty map
-            for (account, amount) in perpetual_royalties {
-                royalty.insert(account, amount);
+        //calculate the required storage which was the used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        //pay the treasury and refund any excess deposit. Panic if they didn't attach enough to cover the price and storage.
+        self.refund_deposit_with_price(required_storage_in_bytes, price, self.mint_policy.treasury_id.clone());
+    }
+
+    //mint many tokens at once. The storage is measured once for the whole batch and one mint log is emitted per receiver.
+    //If soulbound is set, all tokens of the batch are soulbound.
+    #[payable]
+    pub fn nft_batch_mint(&mut self, tokens: Vec<BatchMintItem>, soulbound: Option<bool>) {
+        assert!(!tokens.is_empty(), "Nothing to mint");
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //make sure that the minting policy allows the caller to mint the whole batch and get the price they have to pay
+        let price = self.internal_authorize_mint(tokens.len() as u64);
+
+        //the mint logs, one per receiver with all of the token IDs they received
+        let mut nft_mint_logs: Vec<NftMintLog> = Vec::new();
+
+        for (token_id, metadata, receiver_id, perpetual_royalties) in tokens {
+            //mint the token. The caller is its creator
+            let token = self.internal_mint(
+                &token_id,
+                metadata,
+                receiver_id,
+                perpetual_royalties,
+                env::predecessor_account_id(),
+                soulbound.unwrap_or(false),
+            );
+
+            //add the token ID to the log of the receiver, or create a new log if it is the first token for them
+            let owner_id = token.owner_id.to_string();
+            match nft_mint_logs.iter_mut().find(|log| log.owner_id == owner_id) {
+                Some(log) => log.token_ids.push(token_id),
+                None => nft_mint_logs.push(NftMintLog {
+                    owner_id,
+                    token_ids: vec![token_id],
+                    memo: None,
+                }),
             }
         }
 
-        //specify the token struct that contains the owner ID 
+        // Construct the mint log as per the events standard.
+        let nft_mint_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftMint(nft_mint_logs),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_mint_log.to_string());
+
+        //calculate the required storage which was the used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        //pay the treasury and refund any excess deposit. Panic if they didn't attach enough to cover the price and storage.
+        self.refund_deposit_with_price(required_storage_in_bytes, price, self.mint_policy.treasury_id.clone());
+    }
+}
+
+impl Contract {
+    //mint a single token with its own metadata and return it. The caller takes care of the logs and the storage.
+    pub(crate) fn internal_mint(
+        &mut self,
+        token_id: &TokenId,
+        metadata: TokenMetadata,
+        receiver_id: AccountId,
+        perpetual_royalties: Option<HashMap<AccountId, u32>>,
+        creator_id: AccountId,
+        soulbound: bool,
+    ) -> Token {
+        //token IDs containing the separator are reserved for the copies of series ("{series_id}:{n}")
+        assert!(!token_id.contains(':'), "Token ID cannot contain ':'");
+
+        //use the perpetual royalties passed into the function, or the default royalty of the collection
+        let royalty = perpetual_royalties.unwrap_or_else(|| self.default_royalty.clone());
+        //make sure that the royalties can be paid out
+        assert_valid_royalty(&royalty);
+
+        //specify the token struct that contains the owner ID
         let token = Token {
             //set the owner ID equal to the receiver ID passed into the function
             owner_id: receiver_id,
@@ -38,44 +150,106 @@ impl Contract {
             next_approval_id: 0,
             //the map of perpetual royalties for the token (The owner will get 100% - total perpetual royalties)
             royalty,
+            //the token has its own metadata and is not part of a series
+            series_id: None,
+            //the account that created the token
+            creator_id,
+            //the account that minted the token and when
+            minter_id: env::predecessor_account_id(),
+            minted_at: env::block_timestamp() / 1_000_000,
+            //whether the token is soulbound (non-transferable)
+            soulbound,
         };
 
         //insert the token ID and token struct and make sure that the token doesn't exist
         assert!(
-            self.tokens_by_id.insert(&token_id, &token).is_none(),
+            self.tokens_by_id.insert(token_id, &token).is_none(),
             "Token already exists"
         );
 
         //insert the token ID and metadata
-        self.token_metadata_by_id.insert(&token_id, &metadata);
+        self.token_metadata_by_id.insert(token_id, &metadata);
+        self.total_supply += 1;
 
-        //call the internal method for adding the token to the owner
-        self.internal_add_token_to_owner(&token.owner_id, &token_id);
+        //call the internal methods for adding the token to the owner and the creator
+        self.internal_add_token_to_owner(&token.owner_id, token_id);
+        self.internal_add_token_to_creator(&token.creator_id, token_id);
 
-        // Construct the mint log as per the events standard.
-        let nft_mint_log: EventLog = EventLog {
-            // Standard name ("nep171").
-            standard: NFT_STANDARD_NAME.to_string(),
-            // Version of the standard ("nft-1.0.0").
-            version: NFT_METADATA_SPEC.to_string(),
-            // The data related with the event stored in a vector.
-            event: EventLogVariant::NftMint(vec![NftMintLog {
-                // Owner of the token.
-                owner_id: token.owner_id.to_string(),
-                // Vector of token IDs that were minted.
-                token_ids: vec![token_id.to_string()],
-                // An optional memo to include.
-                memo: metadata.reference,
-            }]),
-        };
+        token
+    }
+}
 
-        // Log the serialized json.
-        env::log_str(&nft_mint_log.to_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
 
-        //calculate the required storage which was the used - initial
-        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+    fn item(token_id: &str, receiver_id: &str) -> BatchMintItem {
+        (token_id.to_string(), token_metadata(), account(receiver_id), None)
+    }
+
+    #[test]
+    fn batch_mint_logs_once_per_receiver() {
+        let mut contract = setup_contract();
+        contract.nft_batch_mint(vec![
+            item("1", "alice.near"),
+            item("2", "bob.near"),
+            item("3", "alice.near"),
+        ], None);
+
+        assert_eq!(contract.nft_total_supply(), U128(3));
+        assert_eq!(contract.nft_supply_for_owner(account("alice.near")), U128(2));
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep171","version":"nft-1.0.0","event":"nft_mint","data":[{"owner_id":"alice.near","token_ids":["1","3"]},{"owner_id":"bob.near","token_ids":["2"]}]}"#]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Nothing to mint")]
+    fn batch_mint_requires_tokens() {
+        let mut contract = setup_contract();
+        contract.nft_batch_mint(vec![], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token already exists")]
+    fn batch_mint_rejects_duplicates() {
+        let mut contract = setup_contract();
+        contract.nft_batch_mint(vec![item("1", "alice.near"), item("1", "bob.near")], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint would exceed the per-wallet limit")]
+    fn batch_mint_counts_every_token() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.set_mint_policy(MintPolicy {
+            mode: MintMode::Public,
+            price: U128(0),
+            treasury_id: account("owner.near"),
+            max_supply: None,
+            per_wallet_limit: Some(1),
+        });
+
+        testing_env!(get_context("buyer.near").build());
+        contract.nft_batch_mint(vec![item("1", "buyer.near"), item("2", "buyer.near")], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover storage")]
+    fn batch_mint_requires_storage() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.nft_batch_mint(vec![item("1", "alice.near")], None);
+    }
 
-        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
-        refund_deposit(required_storage_in_bytes);
+    #[test]
+    #[should_panic(expected = "Token ID cannot contain ':'")]
+    fn series_token_ids_are_reserved() {
+        let mut contract = setup_contract();
+        mint(&mut contract, "s:1", "alice.near");
     }
-}
\ No newline at end of file
+}
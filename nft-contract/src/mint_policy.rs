@@ -0,0 +1,302 @@
+use crate::*;
+
+//who is allowed to call nft_mint. The owner can always mint.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum MintMode {
+    //only the contract owner can mint
+    OwnerOnly,
+    //the owner and the accounts on the minter allowlist can mint
+    Allowlist,
+    //anyone can mint by paying the price. Allowlisted minters mint for free
+    Public,
+}
+
+impl MintMode {
+    //the name of the mode as used in the events
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MintMode::OwnerOnly => "owner_only",
+            MintMode::Allowlist => "allowlist",
+            MintMode::Public => "public",
+        }
+    }
+}
+
+//the policy that decides who can mint and at which price
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintPolicy {
+    //who is allowed to mint
+    pub mode: MintMode,
+    //the price in yoctoNEAR that has to be paid for each public mint (on top of storage)
+    pub price: U128,
+    //the account receiving the price of public mints
+    pub treasury_id: AccountId,
+    //the maximum number of tokens that can exist on the contract (unlimited if None)
+    pub max_supply: Option<u64>,
+    //the maximum number of public mints per account (unlimited if None)
+    pub per_wallet_limit: Option<u64>,
+}
+
+#[near_bindgen]
+impl Contract {
+    //change the minting policy. Only the owner can call this.
+    #[payable]
+    pub fn set_mint_policy(&mut self, policy: MintPolicy) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+
+        // Construct the mint policy log.
+        let mint_policy_log: EventLog = EventLog {
+            // Standard name ("genadrop_nft").
+            standard: GENADROP_STANDARD_NAME.to_string(),
+            // Version of the events ("1.0.0").
+            version: GENADROP_EVENT_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::MintPolicyUpdate(vec![MintPolicyUpdateLog {
+                mode: policy.mode.as_str().to_string(),
+                price: policy.price.0.to_string(),
+                treasury_id: policy.treasury_id.to_string(),
+                max_supply: policy.max_supply,
+                per_wallet_limit: policy.per_wallet_limit,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&mint_policy_log.to_string());
+
+        self.mint_policy = policy;
+    }
+
+    //add an account to the minter allowlist. Only the owner can call this.
+    #[payable]
+    pub fn add_minter(&mut self, account_id: AccountId) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+        if self.minters.insert(&account_id) {
+            self.internal_log_minter(EventLogVariant::MinterAdd(vec![MinterLog {
+                minter_id: account_id.to_string(),
+            }]));
+        }
+    }
+
+    //remove an account from the minter allowlist. Only the owner can call this.
+    #[payable]
+    pub fn remove_minter(&mut self, account_id: AccountId) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+        if self.minters.remove(&account_id) {
+            self.internal_log_minter(EventLogVariant::MinterRemove(vec![MinterLog {
+                minter_id: account_id.to_string(),
+            }]));
+        }
+    }
+
+    //get the current minting policy
+    pub fn get_mint_policy(&self) -> MintPolicy {
+        self.mint_policy.clone()
+    }
+
+    //Query for the accounts on the minter allowlist using pagination
+    pub fn get_minters(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.minters
+            .iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+
+    //get the number of public mints an account has made
+    pub fn get_mints_for_account(&self, account_id: AccountId) -> u64 {
+        self.mints_per_account.get(&account_id).unwrap_or(0)
+    }
+}
+
+impl Contract {
+    //make sure that the predecessor can mint the given number of tokens and return the price they have to pay
+    pub(crate) fn internal_authorize_mint(&mut self, count: u64) -> Balance {
+        let minter_id = env::predecessor_account_id();
+
+        //the supply cap applies to everyone
+        self.internal_assert_supply_cap(count);
+
+        //the owner can always mint for free
+        if minter_id == self.owner_id {
+            return 0;
+        }
+
+        match self.mint_policy.mode {
+            MintMode::OwnerOnly => env::panic_str("Only the contract owner can mint"),
+            MintMode::Allowlist => {
+                assert!(self.minters.contains(&minter_id), "Account is not allowed to mint");
+                0
+            }
+            MintMode::Public => {
+                //allowlisted minters don't pay and are not limited
+                if self.minters.contains(&minter_id) {
+                    return 0;
+                }
+
+                //keep track of the public mints of the account and make sure they don't exceed the limit
+                let minted = self.mints_per_account.get(&minter_id).unwrap_or(0) + count;
+                if let Some(per_wallet_limit) = self.mint_policy.per_wallet_limit {
+                    assert!(minted <= per_wallet_limit, "Mint would exceed the per-wallet limit");
+                }
+                self.mints_per_account.insert(&minter_id, &minted);
+
+                self.mint_policy.price.0 * Balance::from(count)
+            }
+        }
+    }
+
+    //make sure that minting the given number of tokens doesn't exceed the max supply
+    pub(crate) fn internal_assert_supply_cap(&self, count: u64) {
+        if let Some(max_supply) = self.mint_policy.max_supply {
+            assert!(
+                self.total_supply + count <= max_supply,
+                "Mint would exceed the max supply"
+            );
+        }
+    }
+
+    //log a change of the minter allowlist
+    fn internal_log_minter(&self, event: EventLogVariant) {
+        // Construct the minter log.
+        let minter_log: EventLog = EventLog {
+            // Standard name ("genadrop_nft").
+            standard: GENADROP_STANDARD_NAME.to_string(),
+            // Version of the events ("1.0.0").
+            version: GENADROP_EVENT_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event,
+        };
+
+        // Log the serialized json.
+        env::log_str(&minter_log.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+
+    fn set_policy(contract: &mut Contract, mode: MintMode, max_supply: Option<u64>, per_wallet_limit: Option<u64>) {
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.set_mint_policy(MintPolicy {
+            mode,
+            price: U128(ONE_NEAR / 2),
+            treasury_id: account("treasury.near"),
+            max_supply,
+            per_wallet_limit,
+        });
+    }
+
+    fn add_minter(contract: &mut Contract, minter: &str) {
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.add_minter(account(minter));
+    }
+
+    //mint token `token_id` called by `minter` with one NEAR attached
+    fn mint_as(contract: &mut Contract, minter: &str, token_id: &str) {
+        testing_env!(get_context(minter).build());
+        contract.nft_mint(token_id.to_string(), token_metadata(), account(minter), None, None);
+    }
+
+    #[test]
+    fn owner_mints_by_default() {
+        let mut contract = setup_contract();
+        assert!(contract.get_mint_policy().mode == MintMode::OwnerOnly);
+        mint_as(&mut contract, "owner.near", "1");
+        assert_eq!(contract.nft_total_supply(), U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract owner can mint")]
+    fn stranger_cannot_mint_by_default() {
+        let mut contract = setup_contract();
+        mint_as(&mut contract, "stranger.near", "1");
+    }
+
+    #[test]
+    fn allowlisted_minter_mints() {
+        let mut contract = setup_contract();
+        set_policy(&mut contract, MintMode::Allowlist, None, None);
+        add_minter(&mut contract, "minter.near");
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"genadrop_nft","version":"1.0.0","event":"minter_add","data":[{"minter_id":"minter.near"}]}"#]
+        );
+        assert_eq!(contract.get_minters(None, None), vec![account("minter.near")]);
+
+        mint_as(&mut contract, "minter.near", "1");
+        assert_eq!(contract.get_mints_for_account(account("minter.near")), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account is not allowed to mint")]
+    fn removed_minter_cannot_mint() {
+        let mut contract = setup_contract();
+        set_policy(&mut contract, MintMode::Allowlist, None, None);
+        add_minter(&mut contract, "minter.near");
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.remove_minter(account("minter.near"));
+        assert!(contract.get_minters(None, None).is_empty());
+
+        mint_as(&mut contract, "minter.near", "1");
+    }
+
+    #[test]
+    fn anyone_mints_publicly() {
+        let mut contract = setup_contract();
+        set_policy(&mut contract, MintMode::Public, None, Some(2));
+        mint_as(&mut contract, "buyer.near", "1");
+        mint_as(&mut contract, "buyer.near", "2");
+        assert_eq!(contract.get_mints_for_account(account("buyer.near")), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint would exceed the per-wallet limit")]
+    fn public_mints_are_limited_per_wallet() {
+        let mut contract = setup_contract();
+        set_policy(&mut contract, MintMode::Public, None, Some(1));
+        mint_as(&mut contract, "buyer.near", "1");
+        mint_as(&mut contract, "buyer.near", "2");
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover price and storage")]
+    fn public_mints_require_the_price() {
+        let mut contract = setup_contract();
+        set_policy(&mut contract, MintMode::Public, None, None);
+        testing_env!(get_context("buyer.near").attached_deposit(ONE_NEAR / 2).build());
+        contract.nft_mint("1".to_string(), token_metadata(), account("buyer.near"), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint would exceed the max supply")]
+    fn max_supply_applies_to_the_owner() {
+        let mut contract = setup_contract();
+        set_policy(&mut contract, MintMode::OwnerOnly, Some(1), None);
+        mint_as(&mut contract, "owner.near", "1");
+        mint_as(&mut contract, "owner.near", "2");
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract owner can call this method")]
+    fn stranger_cannot_change_the_policy() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("stranger.near").attached_deposit(1).build());
+        contract.add_minter(account("stranger.near"));
+    }
+}
@@ -93,7 +93,7 @@ impl NonFungibleTokenCore for Contract {
         );
 
         //we refund the owner for releasing the storage used up by the approved account IDs
-        refund_approved_account_ids(
+        self.refund_approved_account_ids(
             previous_token.owner_id.clone(),
             &previous_token.approved_account_ids,
         );
@@ -162,8 +162,8 @@ impl NonFungibleTokenCore for Contract {
     fn nft_token(&self, token_id: TokenId) -> Option<JsonToken> {
         //if there is some token ID in the tokens_by_id collection
         if let Some(token) = self.tokens_by_id.get(&token_id) {
-            //we'll get the metadata for that token
-            let metadata = self.token_metadata_by_id.get(&token_id).unwrap();
+            //we'll get the metadata for that token (or its series)
+            let metadata = self.internal_token_metadata(&token_id, &token);
             //we return the JsonToken (wrapped by Some since we return an option)
             Some(JsonToken {
                 token_id,
@@ -171,6 +171,10 @@ impl NonFungibleTokenCore for Contract {
                 metadata,
                 approved_account_ids: token.approved_account_ids,
                 royalty: token.royalty,
+                soulbound: token.soulbound,
+                creator_id: token.creator_id,
+                minter_id: token.minter_id,
+                minted_at: token.minted_at,
             })
         } else { //if there wasn't a token ID in the tokens_by_id collection, we return None
             None
@@ -207,7 +211,7 @@ impl NonFungibleTokenResolver for Contract {
                         revert the original transfer and thus we can just return true since nothing went wrong.
                     */
                     //we refund the owner for releasing the storage used up by the approved account IDs
-                    refund_approved_account_ids(owner_id, &approved_account_ids);
+                    self.refund_approved_account_ids(owner_id, &approved_account_ids);
                     return true;
                 }
             }
@@ -217,7 +221,7 @@ impl NonFungibleTokenResolver for Contract {
         let mut token = if let Some(token) = self.tokens_by_id.get(&token_id) {
             if token.owner_id != receiver_id {
                 //we refund the owner for releasing the storage used up by the approved account IDs
-                refund_approved_account_ids(owner_id, &approved_account_ids);
+                self.refund_approved_account_ids(owner_id, &approved_account_ids);
                 // The token is not owner by the receiver anymore. Can't return it.
                 return true;
             }
@@ -225,7 +229,7 @@ impl NonFungibleTokenResolver for Contract {
         //if there isn't a token object, it was burned and so we return true
         } else {
             //we refund the owner for releasing the storage used up by the approved account IDs
-            refund_approved_account_ids(owner_id, &approved_account_ids);
+            self.refund_approved_account_ids(owner_id, &approved_account_ids);
             return true;
         };
 
@@ -238,7 +242,7 @@ impl NonFungibleTokenResolver for Contract {
         token.owner_id = owner_id.clone();
 
         //we refund the receiver any approved account IDs that they may have set on the token
-        refund_approved_account_ids(receiver_id.clone(), &token.approved_account_ids);
+        self.refund_approved_account_ids(receiver_id.clone(), &token.approved_account_ids);
         //reset the approved account IDs to what they were before the transfer
         token.approved_account_ids = approved_account_ids;
 
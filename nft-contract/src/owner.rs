@@ -0,0 +1,218 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    //propose a new owner for the contract, who has to accept the ownership. Passing None cancels a proposal.
+    #[payable]
+    pub fn propose_owner(&mut self, new_owner_id: Option<AccountId>) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+        self.pending_owner_id = new_owner_id;
+
+        let ownership_transfer_log = OwnershipTransferLog {
+            old_owner_id: self.owner_id.to_string(),
+            new_owner_id: self.pending_owner_id.as_ref().map(|account_id| account_id.to_string()),
+        };
+        self.internal_log_ownership_transfer(EventLogVariant::OwnershipTransferPropose(vec![ownership_transfer_log]));
+    }
+
+    //accept the ownership of the contract. Only the proposed owner can call this.
+    #[payable]
+    pub fn accept_ownership(&mut self) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        let new_owner_id = env::predecessor_account_id();
+        assert_eq!(
+            self.pending_owner_id.as_ref(),
+            Some(&new_owner_id),
+            "Only the proposed owner can accept the ownership"
+        );
+
+        //switch the owner and clear the proposal
+        let old_owner_id = std::mem::replace(&mut self.owner_id, new_owner_id);
+        self.pending_owner_id = None;
+
+        let ownership_transfer_log = OwnershipTransferLog {
+            old_owner_id: old_owner_id.to_string(),
+            new_owner_id: Some(self.owner_id.to_string()),
+        };
+        self.internal_log_ownership_transfer(EventLogVariant::OwnershipTransfer(vec![ownership_transfer_log]));
+    }
+
+    //get the account that was proposed as the new owner
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner_id.clone()
+    }
+
+    //replace the metadata of the contract. Only the owner can call this.
+    #[payable]
+    pub fn set_contract_metadata(&mut self, metadata: NFTContractMetadata) {
+        //assert that the user attached at least 1 yoctoNEAR for security reasons and to pay for storage
+        assert_at_least_one_yocto();
+        self.assert_owner();
+        assert_valid_contract_metadata(&metadata);
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        self.metadata.set(&metadata);
+        self.internal_log_contract_metadata_update(None);
+
+        //if the metadata grew, the owner pays for the storage. Otherwise, they get back the released storage
+        self.refund_storage_change(initial_storage_usage, self.owner_id.clone());
+    }
+}
+
+impl Contract {
+    //log a proposed or completed ownership transfer
+    fn internal_log_ownership_transfer(&self, event: EventLogVariant) {
+        // Construct the ownership transfer log.
+        let ownership_transfer_log: EventLog = EventLog {
+            // Standard name ("genadrop_nft").
+            standard: GENADROP_STANDARD_NAME.to_string(),
+            // Version of the events ("1.0.0").
+            version: GENADROP_EVENT_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event,
+        };
+
+        // Log the serialized json.
+        env::log_str(&ownership_transfer_log.to_string());
+    }
+}
+
+//make sure that the contract metadata follows the NEP-177 standard
+fn assert_valid_contract_metadata(metadata: &NFTContractMetadata) {
+    assert_eq!(metadata.spec, NFT_METADATA_SPEC, "Unsupported metadata spec");
+    assert!(!metadata.name.is_empty(), "Name cannot be empty");
+    assert!(!metadata.symbol.is_empty(), "Symbol cannot be empty");
+    if let Some(icon) = &metadata.icon {
+        assert!(icon.starts_with("data:"), "Icon must be a data URL");
+    }
+
+    //the reference hash is required if and only if there is a reference, and has to be a sha256 hash
+    assert_eq!(
+        metadata.reference.is_some(),
+        metadata.reference_hash.is_some(),
+        "Reference and reference hash must be provided together"
+    );
+    if let Some(reference_hash) = &metadata.reference_hash {
+        assert_eq!(reference_hash.0.len(), 32, "Reference hash must be 32 bytes");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+
+    fn propose(contract: &mut Contract, caller: &str, new_owner_id: Option<&str>) {
+        testing_env!(get_context(caller).attached_deposit(1).build());
+        contract.propose_owner(new_owner_id.map(account));
+    }
+
+    fn accept(contract: &mut Contract, caller: &str) {
+        testing_env!(get_context(caller).attached_deposit(1).build());
+        contract.accept_ownership();
+    }
+
+    fn contract_metadata() -> NFTContractMetadata {
+        NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: "Genadrop".to_string(),
+            symbol: "GND".to_string(),
+            icon: Some("data:image/svg+xml,<svg/>".to_string()),
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    #[test]
+    fn proposed_owner_accepts_the_ownership() {
+        let mut contract = setup_contract();
+        propose(&mut contract, "owner.near", Some("new.near"));
+        assert_eq!(contract.get_pending_owner(), Some(account("new.near")));
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"genadrop_nft","version":"1.0.0","event":"ownership_transfer_propose","data":[{"old_owner_id":"owner.near","new_owner_id":"new.near"}]}"#]
+        );
+
+        accept(&mut contract, "new.near");
+        assert_eq!(contract.owner_id, account("new.near"));
+        assert_eq!(contract.get_pending_owner(), None);
+        assert!(get_logs()[0].contains(r#""event":"ownership_transfer""#));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the proposed owner can accept the ownership")]
+    fn stranger_cannot_accept_the_ownership() {
+        let mut contract = setup_contract();
+        propose(&mut contract, "owner.near", Some("new.near"));
+        accept(&mut contract, "stranger.near");
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the proposed owner can accept the ownership")]
+    fn cancelled_proposal_cannot_be_accepted() {
+        let mut contract = setup_contract();
+        propose(&mut contract, "owner.near", Some("new.near"));
+        propose(&mut contract, "owner.near", None);
+        accept(&mut contract, "new.near");
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract owner can call this method")]
+    fn stranger_cannot_propose_an_owner() {
+        let mut contract = setup_contract();
+        propose(&mut contract, "stranger.near", Some("stranger.near"));
+    }
+
+    #[test]
+    fn owner_sets_the_contract_metadata() {
+        let mut contract = setup_contract();
+        contract.set_contract_metadata(contract_metadata());
+        assert_eq!(contract.nft_metadata().symbol, "GND");
+        assert!(get_logs()[0].contains(r#""event":"contract_metadata_update""#));
+    }
+
+    #[test]
+    #[should_panic(expected = "Icon must be a data URL")]
+    fn icon_must_be_a_data_url() {
+        let mut contract = setup_contract();
+        let mut metadata = contract_metadata();
+        metadata.icon = Some("https://example.com/icon.png".to_string());
+        contract.set_contract_metadata(metadata);
+    }
+
+    #[test]
+    #[should_panic(expected = "Reference and reference hash must be provided together")]
+    fn reference_requires_a_hash() {
+        let mut contract = setup_contract();
+        let mut metadata = contract_metadata();
+        metadata.reference = Some("https://example.com/reference.json".to_string());
+        contract.set_contract_metadata(metadata);
+    }
+
+    #[test]
+    #[should_panic(expected = "Reference hash must be 32 bytes")]
+    fn reference_hash_must_be_a_sha256_hash() {
+        let mut contract = setup_contract();
+        let mut metadata = contract_metadata();
+        metadata.reference = Some("https://example.com/reference.json".to_string());
+        metadata.reference_hash = Some(vec![0; 31].into());
+        contract.set_contract_metadata(metadata);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported metadata spec")]
+    fn spec_must_be_supported() {
+        let mut contract = setup_contract();
+        let mut metadata = contract_metadata();
+        metadata.spec = "nft-2.0.0".to_string();
+        contract.set_contract_metadata(metadata);
+    }
+}
@@ -23,40 +23,11 @@ impl NonFungibleTokenCore for Contract {
     fn nft_payout(&self, token_id: TokenId, balance: U128, max_len_payout: u32) -> Payout {
         //get the token object
 		let token = self.tokens_by_id.get(&token_id).expect("No token");
+        //make sure that the token is within its validity window if the validity is enforced
+        self.internal_assert_valid(&token_id, &token);
 
-        //get the owner of the token
-        let owner_id = token.owner_id;
-        //keep track of the total perpetual royalties
-        let mut total_perpetual = 0;
-        //get the u128 version of the passed in balance (which was U128 before)
-        let balance_u128 = u128::from(balance);
-		//keep track of the payout object to send back
-        let mut payout_object = Payout {
-            payout: HashMap::new()
-        };
-        //get the royalty object from token
-		let royalty = token.royalty;
-
-        //make sure we're not paying out to too many people (GAS limits this)
-		assert!(royalty.len() as u32 <= max_len_payout, "Market cannot payout to that many receivers");
-
-        //go through each key and value in the royalty object
-		for (k, v) in royalty.iter() {
-            //get the key
-			let key = k.clone();
-            //only insert into the payout if the key isn't the token owner (we add their payout at the end)
-			if key != owner_id {
-                //
-				payout_object.payout.insert(key, royalty_to_payout(*v, balance_u128));
-				total_perpetual += *v;
-			}
-		}
-
-		// payout to previous owner who gets 100% - total perpetual royalties
-		payout_object.payout.insert(owner_id, royalty_to_payout(10000 - total_perpetual, balance_u128));
-
-        //return the payout object
-		payout_object
+        //calculate the payout for the owner and the royalty receivers
+        self.internal_payout(token.owner_id, &token.royalty, balance.into(), max_len_payout)
 	}
 
     //transfers the token to the receiver ID and returns the payout object that should be payed given the passed in balance. 
@@ -84,43 +55,402 @@ impl NonFungibleTokenCore for Contract {
         );
 
         //refund the previous token owner for the storage used up by the previous approved account IDs
-        refund_approved_account_ids(
+        self.refund_approved_account_ids(
             previous_token.owner_id.clone(),
             &previous_token.approved_account_ids,
         );
 
-        //get the owner of the token
-        let owner_id = previous_token.owner_id;
-        //keep track of the total perpetual royalties
-        let mut total_perpetual = 0;
-        //get the u128 version of the passed in balance (which was U128 before)
-        let balance_u128 = u128::from(balance);
-		//keep track of the payout object to send back
+        //calculate the payout for the previous owner and the royalty receivers
+        self.internal_payout(previous_token.owner_id, &previous_token.royalty, balance.into(), max_len_payout)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //set the royalty applied to tokens that are minted without perpetual royalties. Only the owner can call this.
+    #[payable]
+    pub fn set_default_royalty(&mut self, royalty: HashMap<AccountId, u32>) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+        //make sure that the royalties can be paid out
+        assert_valid_royalty(&royalty);
+
+        self.internal_log_royalty(EventLogVariant::DefaultRoyaltyUpdate(vec![DefaultRoyaltyUpdateLog {
+            royalty: royalty
+                .iter()
+                .map(|(account_id, amount)| (account_id.to_string(), *amount))
+                .collect(),
+        }]));
+
+        self.default_royalty = royalty;
+    }
+
+    //get the royalty applied to tokens that are minted without perpetual royalties
+    pub fn get_default_royalty(&self) -> HashMap<AccountId, u32> {
+        self.default_royalty.clone()
+    }
+
+    //set the account that receives the smallest royalty shares when a payout has more receivers than the
+    //marketplace allows. If None, these shares are merged into the owner's payout. Only the owner can call this.
+    #[payable]
+    pub fn set_payout_collector(&mut self, account_id: Option<AccountId>) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+        self.payout_collector_id = account_id;
+    }
+
+    //get the account that receives the smallest royalty shares of payouts with too many receivers
+    pub fn get_payout_collector(&self) -> Option<AccountId> {
+        self.payout_collector_id.clone()
+    }
+
+    //redirect the creator's royalty on a token to another account and/or reduce it. Only the creator of the token can
+    //call this, and only for the royalty entry of their own account. The amount can never be increased.
+    #[payable]
+    pub fn nft_update_royalty(&mut self, token_id: TokenId, receiver_id: AccountId, amount: u32) {
+        //assert that the user attached at least 1 yoctoNEAR for security reasons and to pay for storage
+        assert_at_least_one_yocto();
+        let creator_id = env::predecessor_account_id();
+
+        //get the token and make sure that the caller is its creator
+        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+        assert_eq!(token.creator_id, creator_id, "Only the creator can update their royalty");
+
+        //get the current royalty of the creator and make sure that it isn't increased
+        let old_amount = token.royalty.get(&creator_id).copied().expect("Creator has no royalty on this token");
+        assert!(amount <= old_amount, "Royalty can only be reduced");
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //move the royalty to the receiver, adding to their royalty if they already have one. A royalty of 0 is dropped
+        token.royalty.remove(&creator_id);
+        if amount > 0 {
+            *token.royalty.entry(receiver_id.clone()).or_insert(0) += amount;
+        }
+        //make sure that the royalties can still be paid out
+        assert_valid_royalty(&token.royalty);
+        self.tokens_by_id.insert(&token_id, &token);
+
+        self.internal_log_royalty(EventLogVariant::RoyaltyUpdate(vec![RoyaltyUpdateLog {
+            token_ids: vec![token_id],
+            old_receiver_id: creator_id.to_string(),
+            new_receiver_id: receiver_id.to_string(),
+            old_amount,
+            new_amount: amount,
+        }]));
+
+        //if the royalty map grew, the creator pays for the storage. Otherwise, they get back the released storage
+        self.refund_storage_change(initial_storage_usage, creator_id);
+    }
+}
+
+impl Contract {
+    //calculate a payout that sums up exactly to the balance and has at most max_len_payout receivers. The royalty
+    //receivers are paid first, largest shares first, and the owner gets the remainder including any rounding dust.
+    //If there are too many receivers, the smallest shares are merged into the owner's payout or the payout collector.
+    pub(crate) fn internal_payout(
+        &self,
+        owner_id: AccountId,
+        royalty: &HashMap<AccountId, u32>,
+        balance: Balance,
+        max_len_payout: u32,
+    ) -> Payout {
+        //the owner always receives a payout
+        let max_len_payout = max_len_payout as usize;
+        assert!(max_len_payout >= 1, "Payout must have at least one receiver");
+
+        //get the royalty shares of everyone except the owner, largest first and then by account ID for determinism
+        let mut shares: Vec<(AccountId, Balance)> = royalty
+            .iter()
+            .filter(|(account_id, _)| **account_id != owner_id)
+            .map(|(account_id, amount)| (account_id.clone(), royalty_to_payout(*amount, balance).0))
+            .collect();
+        shares.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        //if there are too many receivers, the collector takes one of the receiver slots (unless it is the owner)
+        let collector_id = match &self.payout_collector_id {
+            Some(collector_id)
+                if shares.len() + 1 > max_len_payout && max_len_payout >= 2 && *collector_id != owner_id =>
+            {
+                Some(collector_id.clone())
+            }
+            _ => None,
+        };
+        let royalty_slots = max_len_payout - 1 - collector_id.iter().count();
+
+        //keep track of the payout object to send back and how much has been paid out so far
         let mut payout_object = Payout {
             payout: HashMap::new()
         };
-        //get the royalty object from token
-		let royalty = previous_token.royalty;
-
-        //make sure we're not paying out to too many people (GAS limits this)
-		assert!(royalty.len() as u32 <= max_len_payout, "Market cannot payout to that many receivers");
-
-        //go through each key and value in the royalty object
-		for (k, v) in royalty.iter() {
-            //get the key
-			let key = k.clone();
-            //only insert into the payout if the key isn't the token owner (we add their payout at the end)
-			if key != owner_id {
-                //
-				payout_object.payout.insert(key, royalty_to_payout(*v, balance_u128));
-				total_perpetual += *v;
-			}
-		}
-
-		// payout to previous owner who gets 100% - total perpetual royalties
-		payout_object.payout.insert(owner_id, royalty_to_payout(10000 - total_perpetual, balance_u128));
+        let mut paid: Balance = 0;
+        let mut merged: Balance = 0;
+
+        for (index, (account_id, amount)) in shares.into_iter().enumerate() {
+            //never pay out more than the balance, even for tokens minted before royalties were validated
+            let amount = amount.min(balance - paid);
+            if index < royalty_slots && Some(&account_id) != collector_id.as_ref() {
+                payout_object.payout.insert(account_id, U128(amount));
+            } else {
+                merged += amount;
+            }
+            paid += amount;
+        }
+
+        //the collector receives the merged shares, otherwise they stay with the owner
+        if let Some(collector_id) = collector_id {
+            payout_object.payout.insert(collector_id, U128(merged));
+            merged = 0;
+        }
+
+        //the owner gets the remainder of the balance, so that the payout sums up exactly to the balance
+        payout_object.payout.insert(owner_id, U128(balance - paid + merged));
 
         //return the payout object
-		payout_object
+        payout_object
+    }
+
+    //log a change of the royalties
+    fn internal_log_royalty(&self, event: EventLogVariant) {
+        // Construct the royalty log.
+        let royalty_log: EventLog = EventLog {
+            // Standard name ("genadrop_nft").
+            standard: GENADROP_STANDARD_NAME.to_string(),
+            // Version of the events ("1.0.0").
+            version: GENADROP_EVENT_VERSION.to_string(),
+            // The data related with the event stored in a vector.
+            event,
+        };
+
+        // Log the serialized json.
+        env::log_str(&royalty_log.to_string());
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+
+    //create a contract with a token "1" that was created by creator.near and is held by holder.near
+    fn setup_token(royalty: HashMap<AccountId, u32>) -> Contract {
+        let mut contract = setup_contract();
+        contract.internal_mint(
+            &"1".to_string(),
+            token_metadata(),
+            account("holder.near"),
+            Some(royalty),
+            account("creator.near"),
+            false,
+        );
+        contract
+    }
+
+    #[test]
+    fn update_royalty_redirects_to_existing_receiver() {
+        let mut contract = setup_token(royalty(&[("creator.near", 4000), ("gallery.near", 6000)]));
+        testing_env!(get_context("creator.near").build());
+        contract.nft_update_royalty("1".to_string(), account("gallery.near"), 4000);
+
+        //the royalties are merged and total exactly 10000
+        let token = contract.tokens_by_id.get(&"1".to_string()).unwrap();
+        assert_eq!(token.royalty, royalty(&[("gallery.near", 10000)]));
+        assert!(get_logs()[0].contains(r#""event":"royalty_update""#));
+    }
+
+    #[test]
+    fn update_royalty_drops_zero_amount() {
+        let mut contract = setup_token(royalty(&[("creator.near", 4000), ("gallery.near", 6000)]));
+        testing_env!(get_context("creator.near").build());
+        contract.nft_update_royalty("1".to_string(), account("new.near"), 0);
+
+        let token = contract.tokens_by_id.get(&"1".to_string()).unwrap();
+        assert_eq!(token.royalty, royalty(&[("gallery.near", 6000)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalty can only be reduced")]
+    fn update_royalty_cannot_increase() {
+        let mut contract = setup_token(royalty(&[("creator.near", 4000)]));
+        testing_env!(get_context("creator.near").build());
+        contract.nft_update_royalty("1".to_string(), account("creator.near"), 4001);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the creator can update their royalty")]
+    fn update_royalty_only_by_creator() {
+        let mut contract = setup_token(royalty(&[("creator.near", 4000)]));
+        testing_env!(get_context("holder.near").build());
+        contract.nft_update_royalty("1".to_string(), account("holder.near"), 4000);
+    }
+
+    #[test]
+    fn default_royalty_applies_without_perpetual_royalties() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.set_default_royalty(royalty(&[("owner.near", 500)]));
+        assert_eq!(contract.get_default_royalty(), royalty(&[("owner.near", 500)]));
+
+        mint(&mut contract, "1", "holder.near");
+        testing_env!(get_context("owner.near").build());
+        contract.nft_mint(
+            "2".to_string(),
+            token_metadata(),
+            account("holder.near"),
+            Some(royalty(&[("artist.near", 1000)])),
+            None,
+        );
+        assert_eq!(contract.tokens_by_id.get(&"1".to_string()).unwrap().royalty, royalty(&[("owner.near", 500)]));
+        assert_eq!(contract.tokens_by_id.get(&"2".to_string()).unwrap().royalty, royalty(&[("artist.near", 1000)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Perpetual royalties cannot exceed 10000 basis points in total")]
+    fn default_royalty_is_validated() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.set_default_royalty(royalty(&[("owner.near", 10001)]));
+    }
+
+    //the total amount of a payout
+    fn total(payout: &Payout) -> Balance {
+        payout.payout.values().map(|amount| amount.0).sum()
+    }
+
+    //get the payout of an account, panicking if it doesn't receive one
+    fn paid(payout: &Payout, account_id: &str) -> Balance {
+        payout.payout.get(&account(account_id)).expect("No payout").0
+    }
+
+    #[test]
+    fn payout_sums_to_balance() {
+        let contract = setup_contract();
+        let royalty = royalty(&[("artist.near", 1000), ("gallery.near", 333)]);
+        let payout = contract.internal_payout(account("holder.near"), &royalty, 1_000_001, 10);
+
+        //the owner receives the rounding dust
+        assert_eq!(payout.payout.len(), 3);
+        assert_eq!(paid(&payout, "artist.near"), 100_000);
+        assert_eq!(paid(&payout, "gallery.near"), 33_300);
+        assert_eq!(paid(&payout, "holder.near"), 866_701);
+        assert_eq!(total(&payout), 1_000_001);
+    }
+
+    #[test]
+    fn payout_never_exceeds_balance() {
+        //royalties above 10000 can't be minted anymore, but older tokens might have them
+        let contract = setup_contract();
+        let royalty = royalty(&[("artist.near", 8000), ("gallery.near", 5000)]);
+        let payout = contract.internal_payout(account("holder.near"), &royalty, 10_000, 10);
+
+        assert_eq!(paid(&payout, "artist.near"), 8_000);
+        assert_eq!(paid(&payout, "gallery.near"), 2_000);
+        assert_eq!(paid(&payout, "holder.near"), 0);
+        assert_eq!(total(&payout), 10_000);
+    }
+
+    #[test]
+    fn payout_of_royalties_totalling_10000() {
+        let contract = setup_contract();
+        let royalty = royalty(&[("artist.near", 7000), ("gallery.near", 3000)]);
+        let payout = contract.internal_payout(account("holder.near"), &royalty, 999, 10);
+
+        assert_eq!(paid(&payout, "artist.near"), 699);
+        assert_eq!(paid(&payout, "gallery.near"), 299);
+        assert_eq!(paid(&payout, "holder.near"), 1);
+        assert_eq!(total(&payout), 999);
+    }
+
+    #[test]
+    fn payout_with_owner_as_royalty_holder() {
+        let contract = setup_contract();
+        let royalty = royalty(&[("holder.near", 1000), ("artist.near", 1000)]);
+        let payout = contract.internal_payout(account("holder.near"), &royalty, 10_000, 10);
+
+        //the owner's royalty is part of the remainder instead of being paid twice
+        assert_eq!(payout.payout.len(), 2);
+        assert_eq!(paid(&payout, "artist.near"), 1_000);
+        assert_eq!(paid(&payout, "holder.near"), 9_000);
+    }
+
+    #[test]
+    fn payout_merges_smallest_shares_into_owner() {
+        let contract = setup_contract();
+        let royalty = royalty(&[("a.near", 3000), ("b.near", 2000), ("c.near", 1000)]);
+        let payout = contract.internal_payout(account("holder.near"), &royalty, 10_000, 3);
+
+        assert_eq!(payout.payout.len(), 3);
+        assert_eq!(paid(&payout, "a.near"), 3_000);
+        assert_eq!(paid(&payout, "b.near"), 2_000);
+        assert_eq!(paid(&payout, "holder.near"), 5_000);
+        assert_eq!(total(&payout), 10_000);
+    }
+
+    #[test]
+    fn payout_merges_smallest_shares_into_collector() {
+        let mut contract = setup_contract();
+        contract.payout_collector_id = Some(account("collector.near"));
+        let royalty = royalty(&[("a.near", 3000), ("b.near", 2000), ("c.near", 1000)]);
+        let payout = contract.internal_payout(account("holder.near"), &royalty, 10_000, 3);
+
+        assert_eq!(payout.payout.len(), 3);
+        assert_eq!(paid(&payout, "a.near"), 3_000);
+        assert_eq!(paid(&payout, "collector.near"), 3_000);
+        assert_eq!(paid(&payout, "holder.near"), 4_000);
+        assert_eq!(total(&payout), 10_000);
+    }
+
+    #[test]
+    fn payout_with_collector_as_royalty_holder() {
+        let mut contract = setup_contract();
+        contract.payout_collector_id = Some(account("b.near"));
+        let royalty = royalty(&[("a.near", 3000), ("b.near", 2000), ("c.near", 1000)]);
+        let payout = contract.internal_payout(account("holder.near"), &royalty, 10_000, 3);
+
+        //the collector receives its own share and the merged shares in a single entry
+        assert_eq!(payout.payout.len(), 3);
+        assert_eq!(paid(&payout, "a.near"), 3_000);
+        assert_eq!(paid(&payout, "b.near"), 3_000);
+        assert_eq!(paid(&payout, "holder.near"), 4_000);
+        assert_eq!(total(&payout), 10_000);
+    }
+
+    #[test]
+    fn payout_with_max_len_payout_of_1() {
+        let mut contract = setup_contract();
+        contract.payout_collector_id = Some(account("collector.near"));
+        let royalty = royalty(&[("a.near", 3000), ("b.near", 2000)]);
+        let payout = contract.internal_payout(account("holder.near"), &royalty, 10_000, 1);
+
+        //there is no room for the collector, so the owner receives everything
+        assert_eq!(payout.payout.len(), 1);
+        assert_eq!(paid(&payout, "holder.near"), 10_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Payout must have at least one receiver")]
+    fn payout_with_max_len_payout_of_0() {
+        let contract = setup_contract();
+        contract.internal_payout(account("holder.near"), &royalty(&[]), 10_000, 0);
+    }
+
+    #[test]
+    fn owner_sets_the_payout_collector() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.set_payout_collector(Some(account("collector.near")));
+        assert_eq!(contract.get_payout_collector(), Some(account("collector.near")));
+    }
+
+    #[test]
+    #[should_panic]
+    fn stranger_cannot_set_the_payout_collector() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("stranger.near").attached_deposit(1).build());
+        contract.set_payout_collector(Some(account("collector.near")));
+    }
+}
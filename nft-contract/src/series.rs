@@ -0,0 +1,522 @@
+use crate::*;
+
+pub type SeriesId = String;
+
+//a series (edition) of tokens that share the same metadata and royalties
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Series {
+    //the account that created the series and can mint copies from it
+    pub creator_id: AccountId,
+    //the metadata shared by all copies of the series
+    pub metadata: TokenMetadata,
+    //the maximum number of copies that can be minted (unlimited if None)
+    pub max_copies: Option<u64>,
+    //the royalties that each copy of the series will have
+    pub royalty: HashMap<AccountId, u32>,
+    //the price for anyone to mint a copy. If None, only the creator can mint copies
+    pub price: Option<Balance>,
+    //how many copies have been minted so far (including burned ones)
+    pub copies_minted: u64,
+    //whether the copies of the series are soulbound (non-transferable)
+    pub soulbound: bool,
+}
+
+//The Json series is what will be returned from view calls.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonSeries {
+    pub series_id: SeriesId,
+    pub creator_id: AccountId,
+    pub metadata: TokenMetadata,
+    pub max_copies: Option<u64>,
+    pub royalty: HashMap<AccountId, u32>,
+    pub price: Option<U128>,
+    pub copies_minted: u64,
+    pub soulbound: bool,
+    //how many copies currently exist
+    pub supply: u64,
+    //whether minting from the series has been locked
+    pub locked: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    //create a series of tokens that share the same metadata. The caller becomes the creator of the series.
+    #[payable]
+    pub fn nft_create_series(
+        &mut self,
+        series_id: SeriesId,
+        metadata: TokenMetadata,
+        max_copies: Option<u64>,
+        //we add an optional parameter for perpetual royalties that each copy will have
+        perpetual_royalties: Option<HashMap<AccountId, u32>>,
+        //an optional price for anyone to mint a copy
+        price: Option<U128>,
+        //an optional flag to make all copies soulbound (non-transferable)
+        soulbound: Option<bool>,
+    ) {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //the series ID becomes part of the token IDs, so it must not contain the separator
+        assert!(!series_id.contains(':'), "Series ID cannot contain ':'");
+
+        //only accounts that the minting policy allows to mint can create a series. Nothing is minted yet, so nothing is paid
+        self.internal_authorize_mint(0);
+
+        //use the perpetual royalties passed into the function, or the default royalty of the collection
+        let royalty = perpetual_royalties.unwrap_or_else(|| self.default_royalty.clone());
+        //make sure that the royalties can be paid out
+        assert_valid_royalty(&royalty);
+
+        let series = Series {
+            creator_id: env::predecessor_account_id(),
+            metadata,
+            max_copies,
+            royalty,
+            price: price.map(|price| price.0),
+            copies_minted: 0,
+            soulbound: soulbound.unwrap_or(false),
+        };
+
+        //insert the series and make sure that it doesn't exist
+        assert!(
+            self.series_by_id.insert(&series_id, &series).is_none(),
+            "Series already exists"
+        );
+
+        //calculate the required storage which was the used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        self.refund_deposit(required_storage_in_bytes);
+    }
+
+    //mint the next copy of a series with the token ID "{series_id}:{n}". The minting policy applies to every copy, so the
+    //caller has to be allowed to mint and pays the price of the policy. Anyone but the creator also has to pay the price
+    //of the series (if there is one) on top of the storage.
+    #[payable]
+    pub fn nft_mint_series(&mut self, series_id: SeriesId, receiver_id: AccountId) -> TokenId {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //get the series and make sure that copies can still be minted
+        let mut series = self.series_by_id.get(&series_id).expect("No series");
+        assert!(
+            !self.token_types_locked.contains(&series_id),
+            "Series is locked"
+        );
+        if let Some(max_copies) = series.max_copies {
+            assert!(series.copies_minted < max_copies, "All copies of the series have been minted");
+        }
+        //make sure that the minting policy allows the caller to mint and get the price they have to pay to the treasury.
+        //This also counts the copy towards the per-wallet limit and the max supply
+        let mint_price = self.internal_authorize_mint(1);
+
+        //only the creator can mint without paying the price of the series
+        let price = if env::predecessor_account_id() == series.creator_id {
+            0
+        } else {
+            series.price.expect("Only the creator can mint from this series")
+        };
+
+        //the token IDs are "{series_id}:{n}", starting with 1
+        series.copies_minted += 1;
+        let token_id = format!("{}:{}", series_id, series.copies_minted);
+
+        //specify the token struct, which takes its royalties from the series
+        let token = Token {
+            owner_id: receiver_id,
+            approved_account_ids: Default::default(),
+            next_approval_id: 0,
+            royalty: series.royalty.clone(),
+            series_id: Some(series_id.clone()),
+            creator_id: series.creator_id.clone(),
+            minter_id: env::predecessor_account_id(),
+            minted_at: env::block_timestamp() / 1_000_000,
+            soulbound: series.soulbound,
+        };
+
+        //insert the token ID and token struct and make sure that the token doesn't exist
+        assert!(
+            self.tokens_by_id.insert(&token_id, &token).is_none(),
+            "Token already exists"
+        );
+
+        //the metadata is not duplicated, instead we add the token to the series
+        self.internal_add_token_to_series(&series_id, &token_id);
+        self.series_by_id.insert(&series_id, &series);
+        self.total_supply += 1;
+
+        //call the internal methods for adding the token to the owner and the creator
+        self.internal_add_token_to_owner(&token.owner_id, &token_id);
+        self.internal_add_token_to_creator(&token.creator_id, &token_id);
+
+        // Construct the mint log as per the events standard.
+        let nft_mint_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftMint(vec![NftMintLog {
+                // Owner of the token.
+                owner_id: token.owner_id.to_string(),
+                // Vector of token IDs that were minted.
+                token_ids: vec![token_id.clone()],
+                // An optional memo to include.
+                memo: series.metadata.reference.clone(),
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_mint_log.to_string());
+
+        //calculate the required storage which was the used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        //get how much it would cost to store the information
+        let required_cost = env::storage_byte_cost() * Balance::from(required_storage_in_bytes);
+        //charge the storage and both prices and refund any excess deposit. Panic if they didn't attach enough to cover them.
+        self.internal_charge_storage(required_cost, mint_price + price);
+
+        //pay the price of the minting policy to the treasury and the price of the series to the creator
+        if mint_price > 0 {
+            Promise::new(self.mint_policy.treasury_id.clone()).transfer(mint_price);
+        }
+        if price > 0 {
+            Promise::new(series.creator_id).transfer(price);
+        }
+
+        token_id
+    }
+
+    //lock a series so that no more copies can be minted. Only the creator can call this.
+    #[payable]
+    pub fn nft_lock_series(&mut self, series_id: SeriesId) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        let series = self.series_by_id.get(&series_id).expect("No series");
+        assert_eq!(
+            env::predecessor_account_id(),
+            series.creator_id,
+            "Only the creator can lock the series"
+        );
+        self.token_types_locked.insert(&series_id);
+    }
+
+    //get the information for a specific series
+    pub fn nft_get_series(&self, series_id: SeriesId) -> Option<JsonSeries> {
+        self.series_by_id
+            .get(&series_id)
+            .map(|series| self.internal_json_series(series_id, series))
+    }
+
+    //Query for all series on the contract using pagination
+    pub fn nft_series(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonSeries> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.series_by_id
+            .iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(series_id, series)| self.internal_json_series(series_id, series))
+            .collect()
+    }
+
+    //get the number of existing copies of a series
+    pub fn nft_supply_for_series(&self, series_id: SeriesId) -> U128 {
+        U128(self.internal_series_supply(&series_id) as u128)
+    }
+
+    //Query for all the tokens of a series using pagination
+    pub fn nft_tokens_for_series(
+        &self,
+        series_id: SeriesId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        //if there is no set of tokens for the series, we'll simply return an empty vector
+        let tokens = match self.tokens_per_type.get(&series_id) {
+            Some(tokens) => tokens,
+            None => return vec![],
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        tokens
+            .iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|token_id| self.nft_token(token_id).unwrap())
+            .collect()
+    }
+}
+
+impl Contract {
+    //get the metadata of a token, which is shared with its series if it was minted from one
+    pub(crate) fn internal_token_metadata(&self, token_id: &TokenId, token: &Token) -> TokenMetadata {
+        match &token.series_id {
+            Some(series_id) => self.series_by_id.get(series_id).unwrap().metadata,
+            None => self.token_metadata_by_id.get(token_id).unwrap(),
+        }
+    }
+
+    //get the number of existing copies of a series
+    pub(crate) fn internal_series_supply(&self, series_id: &SeriesId) -> u64 {
+        self.tokens_per_type
+            .get(series_id)
+            .map(|tokens| tokens.len())
+            .unwrap_or(0)
+    }
+
+    //iterate over the IDs of all tokens starting at the given index, with the tokens minted from a series following the other tokens.
+    //whole series are skipped by their length, so only the IDs that are iterated over are read from storage
+    pub(crate) fn internal_token_ids_from(&self, from_index: u64) -> impl Iterator<Item = TokenId> + '_ {
+        let mut skip = from_index.saturating_sub(self.token_metadata_by_id.len());
+        let series_token_ids = self
+            .series_by_id
+            .keys()
+            .filter_map(move |series_id| self.tokens_per_type.get(&series_id))
+            .flat_map(move |tokens| {
+                let start = skip.min(tokens.len());
+                skip -= start;
+                (start..tokens.len()).map(move |index| tokens.as_vector().get(index).unwrap())
+            });
+
+        self.token_metadata_by_id
+            .keys()
+            .skip(from_index as usize)
+            .chain(series_token_ids)
+    }
+
+    //add a token to the set of tokens of a series
+    pub(crate) fn internal_add_token_to_series(&mut self, series_id: &SeriesId, token_id: &TokenId) {
+        //get the set of tokens for the given series
+        let mut tokens_set = self.tokens_per_type.get(series_id).unwrap_or_else(|| {
+            //if the series doesn't have any tokens, we create a new unordered set
+            UnorderedSet::new(
+                StorageKey::TokensPerTypeInner {
+                    //we get a new unique prefix for the collection
+                    token_type_hash: hash_string(series_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+
+        //we insert the token ID into the set and the set for the given series
+        tokens_set.insert(token_id);
+        self.tokens_per_type.insert(series_id, &tokens_set);
+    }
+
+    //remove a token from the set of tokens of a series
+    pub(crate) fn internal_remove_token_from_series(&mut self, series_id: &SeriesId, token_id: &TokenId) {
+        if let Some(mut tokens_set) = self.tokens_per_type.get(series_id) {
+            tokens_set.remove(token_id);
+
+            //if the token set is now empty, we remove the series from the tokens_per_type collection
+            if tokens_set.is_empty() {
+                self.tokens_per_type.remove(series_id);
+            } else {
+                self.tokens_per_type.insert(series_id, &tokens_set);
+            }
+        }
+    }
+
+    //convert a series into the Json series returned from view calls
+    fn internal_json_series(&self, series_id: SeriesId, series: Series) -> JsonSeries {
+        JsonSeries {
+            supply: self.internal_series_supply(&series_id),
+            locked: self.token_types_locked.contains(&series_id),
+            series_id,
+            creator_id: series.creator_id,
+            metadata: series.metadata,
+            max_copies: series.max_copies,
+            royalty: series.royalty,
+            price: series.price.map(U128),
+            copies_minted: series.copies_minted,
+            soulbound: series.soulbound,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    //create series "s" by creator.near with the given maximum copies and price
+    fn create_series(contract: &mut Contract, max_copies: Option<u64>, price: Option<u128>) {
+        open_minting(contract);
+        testing_env!(get_context("creator.near").build());
+        let mut metadata = token_metadata();
+        metadata.title = Some("Series".to_string());
+        contract.nft_create_series("s".to_string(), metadata, max_copies, None, price.map(U128), None);
+    }
+
+    fn mint_copy(contract: &mut Contract, minter: &str) -> TokenId {
+        testing_env!(get_context(minter).build());
+        contract.nft_mint_series("s".to_string(), account("holder.near"))
+    }
+
+    #[test]
+    fn copies_share_the_series_metadata() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, None, None);
+
+        assert_eq!(mint_copy(&mut contract, "creator.near"), "s:1");
+        assert_eq!(mint_copy(&mut contract, "creator.near"), "s:2");
+        let token = contract.nft_token("s:2".to_string()).unwrap();
+        assert_eq!(token.owner_id, account("holder.near"));
+        assert_eq!(token.metadata.title, Some("Series".to_string()));
+        assert!(contract.token_metadata_by_id.get(&"s:2".to_string()).is_none());
+
+        let series = contract.nft_get_series("s".to_string()).unwrap();
+        assert_eq!(series.copies_minted, 2);
+        assert_eq!(series.supply, 2);
+        assert_eq!(contract.nft_supply_for_series("s".to_string()), U128(2));
+        assert_eq!(contract.nft_tokens_for_series("s".to_string(), None, Some(1)).len(), 1);
+        assert_eq!(contract.nft_series(None, None).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "All copies of the series have been minted")]
+    fn copies_are_limited() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, Some(1), None);
+        mint_copy(&mut contract, "creator.near");
+        mint_copy(&mut contract, "creator.near");
+    }
+
+    #[test]
+    #[should_panic(expected = "Series is locked")]
+    fn locked_series_cannot_be_minted() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, None, None);
+
+        testing_env!(get_context("creator.near").attached_deposit(1).build());
+        contract.nft_lock_series("s".to_string());
+        assert!(contract.nft_get_series("s".to_string()).unwrap().locked);
+        mint_copy(&mut contract, "creator.near");
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the creator can lock the series")]
+    fn stranger_cannot_lock_a_series() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, None, None);
+
+        testing_env!(get_context("stranger.near").attached_deposit(1).build());
+        contract.nft_lock_series("s".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the creator can mint from this series")]
+    fn series_without_price_is_minted_by_the_creator() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, None, None);
+        mint_copy(&mut contract, "buyer.near");
+    }
+
+    #[test]
+    fn anyone_can_buy_a_copy() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, None, Some(ONE_NEAR / 2));
+        assert_eq!(mint_copy(&mut contract, "buyer.near"), "s:1");
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover price and storage")]
+    fn copies_require_the_price() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, None, Some(ONE_NEAR));
+        mint_copy(&mut contract, "buyer.near");
+    }
+
+    #[test]
+    #[should_panic(expected = "Series already exists")]
+    fn series_ids_are_unique() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, None, None);
+        create_series(&mut contract, None, None);
+    }
+
+    #[test]
+    fn burned_copies_leave_the_supply() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, None, None);
+        mint_copy(&mut contract, "creator.near");
+        mint_copy(&mut contract, "creator.near");
+
+        testing_env!(get_context("holder.near").attached_deposit(1).build());
+        contract.nft_burn("s:1".to_string());
+        let series = contract.nft_get_series("s".to_string()).unwrap();
+        assert_eq!(series.copies_minted, 2);
+        assert_eq!(series.supply, 1);
+        assert_eq!(mint_copy(&mut contract, "creator.near"), "s:3");
+    }
+
+    #[test]
+    fn tokens_are_paginated_across_series() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("owner.near").build());
+        contract.nft_mint("a".to_string(), token_metadata(), account("holder.near"), None, None);
+        create_series(&mut contract, None, None);
+        testing_env!(get_context("creator.near").build());
+        contract.nft_create_series("t".to_string(), token_metadata(), None, None, None, None);
+        for series_id in ["s", "t"] {
+            for _ in 0..2 {
+                testing_env!(get_context("creator.near").build());
+                contract.nft_mint_series(series_id.to_string(), account("holder.near"));
+            }
+        }
+
+        let token_ids = |from_index: u128, limit: u64| -> Vec<TokenId> {
+            contract.nft_tokens(Some(U128(from_index)), Some(limit)).into_iter().map(|token| token.token_id).collect()
+        };
+        assert_eq!(token_ids(0, 2), vec!["a", "s:1"]);
+        assert_eq!(token_ids(2, 2), vec!["s:2", "t:1"]);
+        assert_eq!(token_ids(3, 50), vec!["t:1", "t:2"]);
+        assert!(token_ids(5, 50).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract owner can mint")]
+    fn series_creation_follows_the_mint_policy() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("creator.near").build());
+        contract.nft_create_series("s".to_string(), token_metadata(), None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint would exceed the per-wallet limit")]
+    fn copies_count_towards_the_per_wallet_limit() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, None, Some(1));
+        let mut policy = contract.get_mint_policy();
+        policy.per_wallet_limit = Some(1);
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.set_mint_policy(policy);
+
+        mint_copy(&mut contract, "buyer.near");
+        mint_copy(&mut contract, "buyer.near");
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover price and storage")]
+    fn copies_require_the_policy_price() {
+        let mut contract = setup_contract();
+        create_series(&mut contract, None, None);
+        let mut policy = contract.get_mint_policy();
+        policy.price = U128(ONE_NEAR);
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.set_mint_policy(policy);
+
+        //the creator doesn't pay for the series, but for the public mint
+        mint_copy(&mut contract, "creator.near");
+    }
+}
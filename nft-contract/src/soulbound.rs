@@ -0,0 +1,113 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    //move a soulbound token to another account, e.g. when the holder lost access to their account.
+    //Only the contract owner can call this.
+    #[payable]
+    pub fn nft_recover_soulbound(&mut self, token_id: TokenId, receiver_id: AccountId, memo: Option<String>) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+
+        //get the token and make sure that it is soulbound, other tokens can be transferred by their owner
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        assert!(token.soulbound, "Only soulbound tokens can be recovered");
+
+        //move the token to the receiver, with the contract owner as the authorized account in the logs
+        let authorized_id = Some(env::predecessor_account_id().to_string());
+        let previous_token = self.internal_move_token(token, &receiver_id, &token_id, authorized_id, memo);
+
+        //we refund the previous owner for releasing the storage used up by the approved account IDs
+        self.refund_approved_account_ids(
+            previous_token.owner_id.clone(),
+            &previous_token.approved_account_ids,
+        );
+    }
+
+    //check whether a token is soulbound (non-transferable)
+    pub fn nft_is_soulbound(&self, token_id: TokenId) -> bool {
+        self.tokens_by_id.get(&token_id).expect("No token").soulbound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::NonFungibleTokenCore as _;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    //mint a soulbound token "1" to holder.near
+    fn setup_soulbound() -> Contract {
+        let mut contract = setup_contract();
+        contract.nft_mint("1".to_string(), token_metadata(), account("holder.near"), None, Some(true));
+        contract
+    }
+
+    #[test]
+    fn minted_tokens_are_transferable_by_default() {
+        let mut contract = setup_contract();
+        mint(&mut contract, "1", "holder.near");
+        assert!(!contract.nft_is_soulbound("1".to_string()));
+        assert!(!contract.nft_token("1".to_string()).unwrap().soulbound);
+    }
+
+    #[test]
+    #[should_panic(expected = "Soulbound tokens cannot be transferred")]
+    fn soulbound_token_cannot_be_transferred() {
+        let mut contract = setup_soulbound();
+        assert!(contract.nft_token("1".to_string()).unwrap().soulbound);
+        testing_env!(get_context("holder.near").attached_deposit(1).build());
+        contract.nft_transfer(account("buyer.near"), "1".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Soulbound tokens cannot be approved")]
+    fn soulbound_token_cannot_be_approved() {
+        let mut contract = setup_soulbound();
+        testing_env!(get_context("holder.near").build());
+        contract.nft_approve("1".to_string(), account("market.near"), None);
+    }
+
+    #[test]
+    fn owner_recovers_a_soulbound_token() {
+        let mut contract = setup_soulbound();
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.nft_recover_soulbound("1".to_string(), account("new.near"), None);
+
+        let token = contract.nft_token("1".to_string()).unwrap();
+        assert_eq!(token.owner_id, account("new.near"));
+        assert!(token.soulbound);
+        assert_eq!(contract.nft_supply_for_owner(account("holder.near")), U128(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn holder_cannot_recover_a_soulbound_token() {
+        let mut contract = setup_soulbound();
+        testing_env!(get_context("holder.near").attached_deposit(1).build());
+        contract.nft_recover_soulbound("1".to_string(), account("new.near"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only soulbound tokens can be recovered")]
+    fn transferable_token_cannot_be_recovered() {
+        let mut contract = setup_contract();
+        mint(&mut contract, "1", "holder.near");
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.nft_recover_soulbound("1".to_string(), account("new.near"), None);
+    }
+
+    #[test]
+    fn series_copies_are_soulbound() {
+        let mut contract = setup_contract();
+        open_minting(&mut contract);
+        testing_env!(get_context("creator.near").build());
+        contract.nft_create_series("s".to_string(), token_metadata(), None, None, None, Some(true));
+        let token_id = contract.nft_mint_series("s".to_string(), account("holder.near"));
+
+        assert!(contract.nft_get_series("s".to_string()).unwrap().soulbound);
+        assert!(contract.nft_is_soulbound(token_id));
+    }
+}
@@ -0,0 +1,292 @@
+use crate::*;
+
+//the most storage an account can use up by registering: the record overhead, the key with the longest account ID and the balance
+const STORAGE_REGISTRATION_BYTES: u64 = 40 + 1 + 4 + 64 + 16;
+
+//the storage balance of an account as per the storage management standard (NEP-145)
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+//the minimum and maximum storage balance as per the storage management standard (NEP-145)
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+#[near_bindgen]
+impl Contract {
+    //prepay storage for an account (the caller if no account ID is passed in). Minting, approving and updating
+    //metadata is paid from this balance first, and burning or revoking gives the released storage back to it.
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+
+        match self.storage_deposits.get(&account_id) {
+            //if the account is already registered, we add the deposit to its balance, unless only registering
+            Some(balance) => {
+                if registration_only.unwrap_or(false) {
+                    if amount > 0 {
+                        Promise::new(env::predecessor_account_id()).transfer(amount);
+                    }
+                } else {
+                    self.storage_deposits.insert(&account_id, &(balance + amount));
+                }
+            }
+            //otherwise we register the account, paying for the registration from the deposit
+            None => {
+                //measure the initial storage being used on the contract
+                let initial_storage_usage = env::storage_usage();
+                self.storage_deposits.insert(&account_id, &0);
+                let registration_cost = env::storage_byte_cost()
+                    * Balance::from(env::storage_usage() - initial_storage_usage);
+                assert!(
+                    amount >= registration_cost,
+                    "Must attach at least {} yoctoNEAR to register",
+                    registration_cost,
+                );
+
+                //only keep the registration cost if only registering
+                if registration_only.unwrap_or(false) {
+                    let refund = amount - registration_cost;
+                    if refund > 0 {
+                        Promise::new(env::predecessor_account_id()).transfer(refund);
+                    }
+                } else {
+                    self.storage_deposits.insert(&account_id, &(amount - registration_cost));
+                }
+            }
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    //withdraw prepaid storage. All of the available balance is withdrawn if no amount is passed in.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let balance = self.storage_deposits.get(&account_id).expect("Account is not registered");
+
+        //make sure that the account doesn't withdraw more than it has
+        let amount = amount.map(|amount| amount.0).unwrap_or(balance);
+        assert!(amount <= balance, "Cannot withdraw more than the available balance");
+
+        self.storage_deposits.insert(&account_id, &(balance - amount));
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    //unregister the caller and refund their balance, including the cost of the registration.
+    //Returns false if the caller wasn't registered.
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        //no balance is ever locked, so there is nothing to force
+        let _ = force;
+        let account_id = env::predecessor_account_id();
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+        let balance = match self.storage_deposits.remove(&account_id) {
+            Some(balance) => balance,
+            None => return false,
+        };
+        let registration_cost = env::storage_byte_cost()
+            * Balance::from(initial_storage_usage - env::storage_usage());
+
+        Promise::new(account_id).transfer(balance + registration_cost);
+        true
+    }
+
+    //get the minimum and maximum storage balance. The minimum covers the registration of any account.
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(env::storage_byte_cost() * Balance::from(STORAGE_REGISTRATION_BYTES)),
+            max: None,
+        }
+    }
+
+    //get the storage balance of an account, or None if it isn't registered
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        //no balance is ever locked, so all of it is available
+        self.storage_deposits.get(&account_id).map(|balance| StorageBalance {
+            total: U128(balance),
+            available: U128(balance),
+        })
+    }
+}
+
+impl Contract {
+    //pay for storage from the predecessor's prepaid storage balance first and the attached deposit for the rest.
+    //The attached deposit also has to cover the price, and whatever is left of it is refunded.
+    pub(crate) fn internal_charge_storage(&mut self, storage_cost: Balance, price: Balance) {
+        let account_id = env::predecessor_account_id();
+
+        //debit as much of the storage as possible from the prepaid storage balance
+        let mut storage_from_deposit = storage_cost;
+        if let Some(balance) = self.storage_deposits.get(&account_id) {
+            let storage_from_balance = balance.min(storage_cost);
+            if storage_from_balance > 0 {
+                self.storage_deposits.insert(&account_id, &(balance - storage_from_balance));
+            }
+            storage_from_deposit -= storage_from_balance;
+        }
+
+        //get the attached deposit
+        let attached_deposit = env::attached_deposit();
+
+        //make sure that the attached deposit is greater than or equal to the required cost
+        let required_cost = price + storage_from_deposit;
+        if price > 0 {
+            assert!(
+                required_cost <= attached_deposit,
+                "Must attach {} yoctoNEAR to cover price and storage",
+                required_cost,
+            );
+        } else {
+            assert!(
+                required_cost <= attached_deposit,
+                "Must attach {} yoctoNEAR to cover storage",
+                required_cost,
+            );
+        }
+
+        //get the refund amount from the attached deposit - required cost
+        let refund = attached_deposit - required_cost;
+
+        //if the refund is greater than 1 yocto NEAR, we refund the predecessor that amount
+        if refund > 1 {
+            Promise::new(account_id).transfer(refund);
+        }
+    }
+
+    //give back released storage to an account. It is added to their prepaid storage balance if they are registered,
+    //otherwise it is transferred to them.
+    pub(crate) fn internal_release_storage(&mut self, account_id: AccountId, amount: Balance) {
+        if amount == 0 {
+            return;
+        }
+
+        match self.storage_deposits.get(&account_id) {
+            Some(balance) => {
+                self.storage_deposits.insert(&account_id, &(balance + amount));
+            }
+            None => {
+                Promise::new(account_id).transfer(amount);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    //register `account_id` with the given deposit
+    fn register(contract: &mut Contract, account_id: &str, deposit: Balance) -> StorageBalance {
+        testing_env!(get_context(account_id).attached_deposit(deposit).build());
+        contract.storage_deposit(None, None)
+    }
+
+    #[test]
+    fn registration_is_paid_from_the_first_deposit() {
+        let mut contract = setup_contract();
+        let min = contract.storage_balance_bounds().min.0;
+        let balance = register(&mut contract, "alice.near", ONE_NEAR);
+        assert!(balance.total.0 < ONE_NEAR && balance.total.0 >= ONE_NEAR - min);
+        assert_eq!(balance.total, balance.available);
+
+        //later deposits are added in full
+        let topped_up = register(&mut contract, "alice.near", ONE_NEAR);
+        assert_eq!(topped_up.total.0, balance.total.0 + ONE_NEAR);
+    }
+
+    #[test]
+    fn registration_only_keeps_the_registration_cost() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("alice.near").build());
+        let balance = contract.storage_deposit(Some(account("bob.near")), Some(true));
+        assert_eq!(balance.total, U128(0));
+        assert!(contract.storage_balance_of(account("alice.near")).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "to register")]
+    fn registration_requires_its_cost() {
+        let mut contract = setup_contract();
+        register(&mut contract, "alice.near", 1);
+    }
+
+    #[test]
+    fn mint_and_burn_use_the_prepaid_balance() {
+        let mut contract = setup_contract();
+        let before = register(&mut contract, "owner.near", ONE_NEAR).total.0;
+
+        testing_env!(get_context("owner.near").attached_deposit(0).build());
+        contract.nft_mint("1".to_string(), token_metadata(), account("owner.near"), None, None);
+        let after_mint = contract.storage_balance_of(account("owner.near")).unwrap().total.0;
+        assert!(after_mint < before);
+
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.nft_burn("1".to_string());
+        let after_burn = contract.storage_balance_of(account("owner.near")).unwrap().total.0;
+        assert!(after_burn > after_mint);
+    }
+
+    #[test]
+    fn registered_account_withdraws_its_balance() {
+        let mut contract = setup_contract();
+        let balance = register(&mut contract, "alice.near", ONE_NEAR).total.0;
+
+        testing_env!(get_context("alice.near").attached_deposit(1).build());
+        assert_eq!(contract.storage_withdraw(Some(U128(1_000))).total.0, balance - 1_000);
+        assert_eq!(contract.storage_withdraw(None).total, U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot withdraw more than the available balance")]
+    fn withdraw_is_limited_to_the_balance() {
+        let mut contract = setup_contract();
+        let balance = register(&mut contract, "alice.near", ONE_NEAR).total.0;
+
+        testing_env!(get_context("alice.near").attached_deposit(1).build());
+        contract.storage_withdraw(Some(U128(balance + 1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Account is not registered")]
+    fn unregistered_account_cannot_withdraw() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("alice.near").attached_deposit(1).build());
+        contract.storage_withdraw(None);
+    }
+
+    #[test]
+    fn unregister_removes_the_account() {
+        let mut contract = setup_contract();
+        register(&mut contract, "alice.near", ONE_NEAR);
+
+        testing_env!(get_context("alice.near").attached_deposit(1).build());
+        assert!(contract.storage_unregister(None));
+        assert!(contract.storage_balance_of(account("alice.near")).is_none());
+        assert!(!contract.storage_unregister(None));
+    }
+}
@@ -0,0 +1,69 @@
+//fixtures shared by the unit tests of the contract
+use crate::*;
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::testing_env;
+
+pub const ONE_NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+
+pub fn account(account_id: &str) -> AccountId {
+    account_id.parse().unwrap()
+}
+
+//context for a call by `predecessor` to the contract at nft.near with one NEAR attached
+pub fn get_context(predecessor: &str) -> VMContextBuilder {
+    let mut builder = VMContextBuilder::new();
+    builder.current_account_id(account("nft.near"));
+    builder.predecessor_account_id(account(predecessor));
+    builder.attached_deposit(ONE_NEAR);
+    builder
+}
+
+//metadata without any fields set
+pub fn token_metadata() -> TokenMetadata {
+    TokenMetadata {
+        title: None,
+        description: None,
+        media: None,
+        media_hash: None,
+        copies: None,
+        issued_at: None,
+        expires_at: None,
+        starts_at: None,
+        updated_at: None,
+        extra: None,
+        reference: None,
+        reference_hash: None,
+    }
+}
+
+//create a contract owned by owner.near
+pub fn setup_contract() -> Contract {
+    testing_env!(get_context("owner.near").build());
+    Contract::new_default_meta(account("owner.near"))
+}
+
+//mint a token to `receiver_id`, called by owner.near
+pub fn mint(contract: &mut Contract, token_id: &str, receiver_id: &str) {
+    testing_env!(get_context("owner.near").build());
+    contract.nft_mint(token_id.to_string(), token_metadata(), account(receiver_id), None, None);
+}
+
+//royalty map from (account, basis points) entries
+pub fn royalty(entries: &[(&str, u32)]) -> HashMap<AccountId, u32> {
+    entries
+        .iter()
+        .map(|(account_id, amount)| (account(account_id), *amount))
+        .collect()
+}
+
+//let anyone mint for free, e.g. to create series
+pub fn open_minting(contract: &mut Contract) {
+    testing_env!(get_context("owner.near").attached_deposit(1).build());
+    contract.set_mint_policy(MintPolicy {
+        mode: MintMode::Public,
+        price: U128(0),
+        treasury_id: account("owner.near"),
+        max_supply: None,
+        per_wallet_limit: None,
+    });
+}
@@ -0,0 +1,258 @@
+use crate::*;
+
+//how the starts_at and expires_at fields of the token metadata are enforced
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum ValidityMode {
+    //the validity window is informational only (default)
+    Disabled,
+    //tokens can't be transferred, approved or paid out before starts_at and after expires_at
+    Enforced,
+    //same as enforced, and anyone can burn expired tokens with nft_burn_expired
+    EnforcedWithCleanup,
+}
+
+#[near_bindgen]
+impl Contract {
+    //change how the validity window of tokens is enforced. Only the owner can call this.
+    #[payable]
+    pub fn set_validity_mode(&mut self, mode: ValidityMode) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+        self.validity_mode = mode;
+    }
+
+    //get how the validity window of tokens is enforced
+    pub fn get_validity_mode(&self) -> ValidityMode {
+        self.validity_mode.clone()
+    }
+
+    //check whether a token is within its validity window, i.e. it has started and hasn't expired
+    pub fn nft_is_valid(&self, token_id: TokenId) -> bool {
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        let metadata = self.internal_token_metadata(&token_id, &token);
+        !is_before_start(&metadata) && !is_expired(&metadata)
+    }
+
+    //Query for the tokens of an owner that haven't expired using pagination. The pagination applies to the filtered tokens.
+    pub fn nft_unexpired_tokens_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        //if there is no set of tokens for the owner, we'll simply return an empty vector
+        let tokens = match self.tokens_per_owner.get(&account_id) {
+            Some(tokens) => tokens,
+            None => return vec![],
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        tokens
+            .iter()
+            .map(|token_id| self.nft_token(token_id).unwrap())
+            .filter(|token| !is_expired(&token.metadata))
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+
+    //Query for the expired tokens on the contract using pagination. The pagination applies to all tokens as in nft_tokens,
+    //so a page may contain less than "limit" expired tokens and the next page starts at from_index + limit.
+    pub fn nft_expired_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonToken> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.internal_token_ids_from(start as u64)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|token_id| self.nft_token(token_id).unwrap())
+            .filter(|token| is_expired(&token.metadata))
+            .collect()
+    }
+
+    //burn expired tokens. Anyone can call this if the owner enabled the cleanup. The freed storage is refunded to the token owners.
+    pub fn nft_burn_expired(&mut self, token_ids: Vec<TokenId>) {
+        assert!(
+            self.validity_mode == ValidityMode::EnforcedWithCleanup,
+            "Burning expired tokens is disabled"
+        );
+        let caller_id = env::predecessor_account_id();
+
+        for token_id in token_ids {
+            //get the token and make sure that it has expired
+            let token = self.tokens_by_id.get(&token_id).expect("No token");
+            let metadata = self.internal_token_metadata(&token_id, &token);
+            assert!(is_expired(&metadata), "Token {} hasn't expired", token_id);
+
+            //the caller is only logged as the authorized account if they don't own the token
+            let authorized_id = if caller_id != token.owner_id {
+                Some(caller_id.to_string())
+            } else {
+                None
+            };
+            self.internal_burn(&token_id, token, authorized_id, Some("expired".to_string()));
+        }
+    }
+}
+
+impl Contract {
+    //make sure that a token is within its validity window if the validity is enforced
+    pub(crate) fn internal_assert_valid(&self, token_id: &TokenId, token: &Token) {
+        if self.validity_mode == ValidityMode::Disabled {
+            return;
+        }
+
+        let metadata = self.internal_token_metadata(token_id, token);
+        assert!(!is_before_start(&metadata), "Token is not valid yet");
+        assert!(!is_expired(&metadata), "Token has expired");
+    }
+}
+
+//the current block time as Unix epoch in milliseconds, as used by the token metadata
+fn now_ms() -> u64 {
+    env::block_timestamp() / 1_000_000
+}
+
+//check whether the validity window of a token hasn't started yet
+fn is_before_start(metadata: &TokenMetadata) -> bool {
+    matches!(metadata.starts_at, Some(starts_at) if now_ms() < starts_at)
+}
+
+//check whether the validity window of a token has ended
+fn is_expired(metadata: &TokenMetadata) -> bool {
+    matches!(metadata.expires_at, Some(expires_at) if now_ms() >= expires_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    //the block time of the tests, in ms
+    const NOW_MS: u64 = 2_000;
+
+    //context at NOW_MS
+    fn context_now(predecessor: &str) -> near_sdk::test_utils::VMContextBuilder {
+        let mut builder = get_context(predecessor);
+        builder.block_timestamp(NOW_MS * 1_000_000);
+        builder
+    }
+
+    //mint "expired", "valid" and "upcoming" tokens to holder.near and set the validity mode
+    fn setup_tokens(mode: ValidityMode) -> Contract {
+        let mut contract = setup_contract();
+        let windows = [("expired", None, Some(1_000)), ("valid", Some(1_000), Some(5_000)), ("upcoming", Some(5_000), None)];
+        for (token_id, starts_at, expires_at) in windows {
+            let mut metadata = token_metadata();
+            metadata.starts_at = starts_at;
+            metadata.expires_at = expires_at;
+            testing_env!(context_now("owner.near").build());
+            contract.nft_mint(token_id.to_string(), metadata, account("holder.near"), None, None);
+        }
+        testing_env!(context_now("owner.near").attached_deposit(1).build());
+        contract.set_validity_mode(mode);
+        contract
+    }
+
+    fn transfer(contract: &mut Contract, token_id: &str) {
+        testing_env!(context_now("holder.near").attached_deposit(1).build());
+        contract.nft_transfer(account("buyer.near"), token_id.to_string(), None, None);
+    }
+
+    fn token_ids(tokens: Vec<JsonToken>) -> Vec<TokenId> {
+        tokens.into_iter().map(|token| token.token_id).collect()
+    }
+
+    #[test]
+    fn validity_is_informational_by_default() {
+        let mut contract = setup_tokens(ValidityMode::Disabled);
+        assert!(contract.get_validity_mode() == ValidityMode::Disabled);
+        transfer(&mut contract, "expired");
+        transfer(&mut contract, "upcoming");
+        assert_eq!(contract.nft_token("expired".to_string()).unwrap().owner_id, account("buyer.near"));
+    }
+
+    #[test]
+    fn tokens_within_their_window_are_valid() {
+        let mut contract = setup_tokens(ValidityMode::Enforced);
+        assert!(!contract.nft_is_valid("expired".to_string()));
+        assert!(contract.nft_is_valid("valid".to_string()));
+        assert!(!contract.nft_is_valid("upcoming".to_string()));
+        transfer(&mut contract, "valid");
+    }
+
+    #[test]
+    #[should_panic(expected = "Token has expired")]
+    fn expired_token_cannot_be_transferred() {
+        let mut contract = setup_tokens(ValidityMode::Enforced);
+        transfer(&mut contract, "expired");
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is not valid yet")]
+    fn upcoming_token_cannot_be_transferred() {
+        let mut contract = setup_tokens(ValidityMode::Enforced);
+        transfer(&mut contract, "upcoming");
+    }
+
+    #[test]
+    #[should_panic]
+    fn stranger_cannot_set_the_validity_mode() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("stranger.near").attached_deposit(1).build());
+        contract.set_validity_mode(ValidityMode::Enforced);
+    }
+
+    #[test]
+    fn expired_tokens_are_paginated_over_all_tokens() {
+        let contract = setup_tokens(ValidityMode::Disabled);
+        testing_env!(context_now("holder.near").build());
+        assert_eq!(token_ids(contract.nft_expired_tokens(None, Some(1))), vec!["expired".to_string()]);
+        assert!(contract.nft_expired_tokens(Some(U128(1)), Some(2)).is_empty());
+    }
+
+    #[test]
+    fn expired_tokens_are_filtered() {
+        let contract = setup_tokens(ValidityMode::Disabled);
+        testing_env!(context_now("holder.near").build());
+        assert_eq!(token_ids(contract.nft_expired_tokens(None, None)), vec!["expired".to_string()]);
+        assert_eq!(
+            token_ids(contract.nft_unexpired_tokens_for_owner(account("holder.near"), None, None)),
+            vec!["valid".to_string(), "upcoming".to_string()]
+        );
+        assert_eq!(
+            token_ids(contract.nft_unexpired_tokens_for_owner(account("holder.near"), Some(U128(1)), Some(1))),
+            vec!["upcoming".to_string()]
+        );
+    }
+
+    #[test]
+    fn anyone_burns_expired_tokens_with_cleanup() {
+        let mut contract = setup_tokens(ValidityMode::EnforcedWithCleanup);
+        testing_env!(context_now("stranger.near").build());
+        contract.nft_burn_expired(vec!["expired".to_string()]);
+        assert!(contract.nft_token("expired".to_string()).is_none());
+        assert_eq!(contract.nft_supply_for_owner(account("holder.near")), U128(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Burning expired tokens is disabled")]
+    fn expired_tokens_cannot_be_burned_without_cleanup() {
+        let mut contract = setup_tokens(ValidityMode::Enforced);
+        testing_env!(context_now("stranger.near").build());
+        contract.nft_burn_expired(vec!["expired".to_string()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token valid hasn't expired")]
+    fn valid_tokens_cannot_be_burned() {
+        let mut contract = setup_tokens(ValidityMode::EnforcedWithCleanup);
+        testing_env!(context_now("stranger.near").build());
+        contract.nft_burn_expired(vec!["valid".to_string()]);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::*;
+use near_sdk::CurveType;
+use std::convert::TryInto;
+
+//a voucher signed off-chain by the owner that lets a buyer mint a token by paying its price.
+//The signed message is the borsh serialization of the voucher.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintVoucher {
+    //the contract the voucher can be redeemed on, so that it can't be replayed on another collection
+    pub contract_id: AccountId,
+    //the ID of the token that will be minted
+    pub token_id: TokenId,
+    //sha256 hash of the borsh serialized token metadata
+    pub metadata_hash: Base64VecU8,
+    //the price in yoctoNEAR that has to be paid to the treasury (on top of storage)
+    pub price: U128,
+    //if set, the token can only be minted to this account. Otherwise it is minted to the caller
+    pub receiver_id: Option<AccountId>,
+    //when the voucher expires, Unix epoch in milliseconds
+    pub expires_at: Option<u64>,
+    //the perpetual royalties of the token
+    pub perpetual_royalties: Option<HashMap<AccountId, u32>>,
+}
+
+#[near_bindgen]
+impl Contract {
+    //set the ed25519 key used to sign mint vouchers, or disable vouchers by passing None. Only the owner can call this.
+    #[payable]
+    pub fn set_voucher_public_key(&mut self, public_key: Option<PublicKey>) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        self.assert_owner();
+        if let Some(public_key) = &public_key {
+            assert!(
+                public_key.curve_type() == CurveType::ED25519,
+                "Voucher key must be an ed25519 key"
+            );
+        }
+        self.voucher_public_key = public_key;
+    }
+
+    //get the key used to sign mint vouchers
+    pub fn get_voucher_public_key(&self) -> Option<PublicKey> {
+        self.voucher_public_key.clone()
+    }
+
+    //check whether the voucher for a token ID has already been redeemed
+    pub fn is_voucher_redeemed(&self, token_id: TokenId) -> bool {
+        self.redeemed_vouchers.contains(&token_id)
+    }
+
+    //mint a token from a voucher signed by the owner. The caller pays the price of the voucher plus storage.
+    #[payable]
+    pub fn nft_redeem_voucher(
+        &mut self,
+        voucher: MintVoucher,
+        metadata: TokenMetadata,
+        //the base64 encoded ed25519 signature of the borsh serialized voucher
+        signature: Base64VecU8,
+    ) -> TokenId {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //make sure that the voucher was issued for this contract
+        assert_eq!(
+            voucher.contract_id,
+            env::current_account_id(),
+            "Voucher is for another contract"
+        );
+
+        //make sure that the voucher was signed by the owner's key
+        let public_key = self.voucher_public_key.as_ref().expect("Vouchers are disabled");
+        let public_key: &[u8; 32] = public_key.as_bytes()[1..].try_into().expect("Invalid voucher key");
+        let signature: &[u8; 64] = signature.0.as_slice().try_into().expect("Invalid signature length");
+        assert!(
+            ed25519_verify(signature, &voucher.try_to_vec().unwrap(), public_key),
+            "Invalid voucher signature"
+        );
+
+        //make sure that the voucher is still valid and the metadata is the one that was signed
+        if let Some(expires_at) = voucher.expires_at {
+            assert!(env::block_timestamp() / 1_000_000 < expires_at, "Voucher has expired");
+        }
+        assert_eq!(
+            env::sha256(&metadata.try_to_vec().unwrap()),
+            voucher.metadata_hash.0,
+            "Metadata doesn't match the voucher"
+        );
+
+        //a voucher can only be redeemed once, even if its token has been burned since
+        assert!(
+            self.redeemed_vouchers.insert(&voucher.token_id),
+            "Voucher has already been redeemed"
+        );
+
+        //the max supply of the minting policy also applies to vouchers
+        self.internal_assert_supply_cap(1);
+
+        //the token is minted to the receiver of the voucher, or the caller if there is none
+        let receiver_id = voucher.receiver_id.unwrap_or_else(env::predecessor_account_id);
+
+        //the memo of the mint log is the reference of the metadata
+        let memo = metadata.reference.clone();
+
+        //mint the token. The owner signed the voucher, so they are its creator
+        let token = self.internal_mint(
+            &voucher.token_id,
+            metadata,
+            receiver_id,
+            voucher.perpetual_royalties,
+            self.owner_id.clone(),
+            false,
+        );
+
+        // Construct the mint log as per the events standard.
+        let nft_mint_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftMint(vec![NftMintLog {
+                // Owner of the token.
+                owner_id: token.owner_id.to_string(),
+                // Vector of token IDs that were minted.
+                token_ids: vec![voucher.token_id.clone()],
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_mint_log.to_string());
+
+        //calculate the required storage which was the used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        //pay the treasury and refund any excess deposit. Panic if they didn't attach enough to cover the price and storage.
+        self.refund_deposit_with_price(required_storage_in_bytes, voucher.price.0, self.mint_policy.treasury_id.clone());
+
+        voucher.token_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use ed25519_dalek::{Keypair, SecretKey, Signer};
+    use near_sdk::testing_env;
+
+    fn keypair(seed: u8) -> Keypair {
+        let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = (&secret).into();
+        Keypair { secret, public }
+    }
+
+    fn voucher_key(seed: u8) -> PublicKey {
+        let mut key = vec![CurveType::ED25519 as u8];
+        key.extend(keypair(seed).public.to_bytes());
+        key.try_into().unwrap()
+    }
+
+    fn voucher(token_id: &str) -> MintVoucher {
+        MintVoucher {
+            contract_id: account("nft.near"),
+            token_id: token_id.to_string(),
+            metadata_hash: env::sha256(&token_metadata().try_to_vec().unwrap()).into(),
+            price: U128(ONE_NEAR / 2),
+            receiver_id: None,
+            expires_at: Some(1_000),
+            perpetual_royalties: None,
+        }
+    }
+
+    fn sign(voucher: &MintVoucher, seed: u8) -> Base64VecU8 {
+        keypair(seed).sign(&voucher.try_to_vec().unwrap()).to_bytes().to_vec().into()
+    }
+
+    //contract whose vouchers are signed with the key of seed 1
+    fn setup_vouchers() -> Contract {
+        let mut contract = setup_contract();
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.set_voucher_public_key(Some(voucher_key(1)));
+        contract
+    }
+
+    fn redeem(contract: &mut Contract, voucher: MintVoucher, signature: Base64VecU8) -> TokenId {
+        testing_env!(get_context("buyer.near").build());
+        contract.nft_redeem_voucher(voucher, token_metadata(), signature)
+    }
+
+    #[test]
+    fn buyer_redeems_a_voucher() {
+        let mut contract = setup_vouchers();
+        let voucher = voucher("1");
+        let signature = sign(&voucher, 1);
+
+        assert_eq!(redeem(&mut contract, voucher, signature), "1");
+        assert_eq!(contract.nft_token("1".to_string()).unwrap().owner_id, account("buyer.near"));
+        assert!(contract.is_voucher_redeemed("1".to_string()));
+    }
+
+    #[test]
+    fn voucher_mints_to_its_receiver() {
+        let mut contract = setup_vouchers();
+        let mut voucher = voucher("1");
+        voucher.receiver_id = Some(account("friend.near"));
+        let signature = sign(&voucher, 1);
+
+        redeem(&mut contract, voucher, signature);
+        assert_eq!(contract.nft_token("1".to_string()).unwrap().owner_id, account("friend.near"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid voucher signature")]
+    fn voucher_signed_with_another_key_is_rejected() {
+        let mut contract = setup_vouchers();
+        let voucher = voucher("1");
+        let signature = sign(&voucher, 2);
+        redeem(&mut contract, voucher, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid voucher signature")]
+    fn tampered_voucher_is_rejected() {
+        let mut contract = setup_vouchers();
+        let mut voucher = voucher("1");
+        let signature = sign(&voucher, 1);
+        voucher.price = U128(0);
+        redeem(&mut contract, voucher, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Voucher has already been redeemed")]
+    fn voucher_is_redeemed_once() {
+        let mut contract = setup_vouchers();
+        let voucher = voucher("1");
+        let signature = sign(&voucher, 1);
+        redeem(&mut contract, voucher, signature.clone());
+
+        testing_env!(get_context("buyer.near").attached_deposit(1).build());
+        contract.nft_burn("1".to_string());
+        redeem(&mut contract, self::voucher("1"), signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Voucher has expired")]
+    fn expired_voucher_is_rejected() {
+        let mut contract = setup_vouchers();
+        let voucher = voucher("1");
+        let signature = sign(&voucher, 1);
+
+        testing_env!(get_context("buyer.near").block_timestamp(1_000 * 1_000_000).build());
+        contract.nft_redeem_voucher(voucher, token_metadata(), signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Metadata doesn't match the voucher")]
+    fn other_metadata_is_rejected() {
+        let mut contract = setup_vouchers();
+        let voucher = voucher("1");
+        let signature = sign(&voucher, 1);
+        let mut metadata = token_metadata();
+        metadata.title = Some("Other".to_string());
+
+        testing_env!(get_context("buyer.near").build());
+        contract.nft_redeem_voucher(voucher, metadata, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover price and storage")]
+    fn voucher_requires_the_price() {
+        let mut contract = setup_vouchers();
+        let mut voucher = voucher("1");
+        voucher.price = U128(ONE_NEAR);
+        let signature = sign(&voucher, 1);
+        redeem(&mut contract, voucher, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Vouchers are disabled")]
+    fn vouchers_are_disabled_by_default() {
+        let mut contract = setup_contract();
+        let voucher = voucher("1");
+        let signature = sign(&voucher, 1);
+        redeem(&mut contract, voucher, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Voucher key must be an ed25519 key")]
+    fn voucher_key_must_be_ed25519() {
+        let mut contract = setup_contract();
+        let mut key = vec![CurveType::SECP256K1 as u8];
+        key.extend([1; 64]);
+
+        testing_env!(get_context("owner.near").attached_deposit(1).build());
+        contract.set_voucher_public_key(Some(key.try_into().unwrap()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Voucher is for another contract")]
+    fn voucher_of_another_contract_is_rejected() {
+        let mut contract = setup_vouchers();
+        let mut voucher = voucher("1");
+        voucher.contract_id = account("other-nft.near");
+        let signature = sign(&voucher, 1);
+        redeem(&mut contract, voucher, signature);
+    }
+}